@@ -0,0 +1,548 @@
+use std::fmt;
+use std::fmt::Write;
+use yaml_rust::Yaml;
+
+use super::gltf::Gltf;
+
+/// segment 0 animations: start frame, end frame and channel count as big endian u16s plus 2 unused
+/// bytes, then each channel is a u16 (bone << 4 | transform), a u16 keyframe count and 4 byte
+/// keyframes, a u16 (flags << 14 | frame) and an s16 value in 1/64ths
+pub const HEADER_LEN : usize = 8;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Transform{
+    RotX,
+    RotY,
+    RotZ,
+    ScaleX,
+    ScaleY,
+    ScaleZ,
+    TransX,
+    TransY,
+    TransZ,
+    Unknown(u8),
+}
+
+impl Transform{
+    pub fn from_u8(code: u8) -> Transform{
+        match code{
+            0 => Transform::RotX,
+            1 => Transform::RotY,
+            2 => Transform::RotZ,
+            3 => Transform::ScaleX,
+            4 => Transform::ScaleY,
+            5 => Transform::ScaleZ,
+            6 => Transform::TransX,
+            7 => Transform::TransY,
+            8 => Transform::TransZ,
+            _ => Transform::Unknown(code),
+        }
+    }
+
+    pub fn to_u8(&self) -> u8{
+        match self{
+            Transform::RotX => 0,
+            Transform::RotY => 1,
+            Transform::RotZ => 2,
+            Transform::ScaleX => 3,
+            Transform::ScaleY => 4,
+            Transform::ScaleZ => 5,
+            Transform::TransX => 6,
+            Transform::TransY => 7,
+            Transform::TransZ => 8,
+            Transform::Unknown(code) => *code,
+        }
+    }
+}
+
+impl fmt::Display for Transform{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Transform::Unknown(code) => write!(f, "Unknown({})", code),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::str::FromStr for Transform{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Transform, String>{
+        let known = (0..9).map(Transform::from_u8).find(|t| t.to_string() == s);
+        let unknown = s.strip_prefix("Unknown(").and_then(|v| v.strip_suffix(")")).and_then(|v| v.parse::<u8>().ok()).filter(|c| *c < 16);
+        match (known, unknown){
+            (Some(t), _) => Ok(t),
+            (None, Some(code)) => Ok(Transform::from_u8(code)),
+            _ => Err(format!("unrecognized transform \"{}\", expected RotX/Y/Z, ScaleX/Y/Z, TransX/Y/Z or Unknown(n)", s)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Keyframe{
+    pub frame: u16, //14 bits
+    pub flags: u8, //2 bits, how the player eases into the key
+    pub value: i16, //degrees, scale or units times 64
+}
+
+impl Keyframe{
+    pub fn value_f32(&self) -> f32{
+        return self.value as f32 / 64.0
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AnimChannel{
+    pub bone: u16, //12 bits
+    pub transform: Transform,
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AnimFile{
+    pub start_frame: u16,
+    pub end_frame: u16,
+    pub channels: Vec<AnimChannel>,
+    pub pad: u16, //header bytes 6 and 7
+    pub trailing: Vec<u8>, //bytes after the last channel
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum AnimError{
+    Truncated{offset: usize, needed: usize, len: usize},
+    NotParsed,
+    BadScale(f32),
+    BadRange{start: u16, end: u16},
+    BoneOutOfRange{bone: u16, bones: usize},
+    UnknownTransform{bone: u16, code: u8},
+}
+
+impl fmt::Display for AnimError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            AnimError::Truncated{offset, needed, len} => write!(f, "needs 0x{:X} bytes at 0x{:X} but the file is 0x{:X} bytes", needed, offset, len),
+            AnimError::NotParsed => write!(f, "animation did not parse, only its raw bytes are known"),
+            AnimError::BadScale(factor) => write!(f, "can't scale time by {}", factor),
+            AnimError::BadRange{start, end} => write!(f, "frames {} to {} are not a range", start, end),
+            AnimError::BoneOutOfRange{bone, bones} => write!(f, "bone {} is animated but the skeleton has {} bones", bone, bones),
+            AnimError::UnknownTransform{bone, code} => write!(f, "bone {} has a channel with unknown transform {}", bone, code),
+        }
+    }
+}
+
+/// problems found by `AnimFile::validate`, channels are counted from 0 in file order
+#[derive(PartialEq, Debug, Clone)]
+pub enum AnimIssue{
+    BoneOutOfRange{channel: usize, bone: u16, bones: usize},
+    Unordered{channel: usize, key: usize, frame: u16, previous: u16}, //key not after the one before it
+    EmptyChannel{channel: usize},
+}
+
+impl fmt::Display for AnimIssue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            AnimIssue::BoneOutOfRange{channel, bone, bones} => write!(f, "channel {}: bone {} but the model has {} bones", channel, bone, bones),
+            AnimIssue::Unordered{channel, key, frame, previous} => write!(f, "channel {} keyframe {}: frame {} does not come after frame {}", channel, key, frame, previous),
+            AnimIssue::EmptyChannel{channel} => write!(f, "channel {}: no keyframes", channel),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AnimStats{
+    pub frame_count: u16,
+    pub channels: usize,
+    pub bones_animated: usize, //distinct bones with at least one channel
+    pub keyframes: usize,
+}
+
+impl AnimFile{
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnimFile, AnimError>{
+        let u16_at = |o: usize| -> Result<u16, AnimError>{
+            match bytes.get(o..o + 2){
+                Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+                None => Err(AnimError::Truncated{offset: o, needed: 2, len: bytes.len()}),
+            }
+        };
+        let start_frame = u16_at(0)?;
+        let end_frame = u16_at(2)?;
+        let channel_cnt = u16_at(4)? as usize;
+        let pad = u16_at(6)?;
+
+        let mut offset = HEADER_LEN;
+        let mut channels = Vec::with_capacity(channel_cnt);
+        for _ in 0..channel_cnt{
+            let id = u16_at(offset)?;
+            let key_cnt = u16_at(offset + 2)? as usize;
+            offset += 4;
+            if bytes.len() < offset + 4*key_cnt{
+                return Err(AnimError::Truncated{offset: offset, needed: 4*key_cnt, len: bytes.len()});
+            }
+            let keyframes = bytes[offset..offset + 4*key_cnt].chunks_exact(4).map(|k|{
+                let frame = u16::from_be_bytes([k[0], k[1]]);
+                Keyframe{frame: frame & 0x3FFF, flags: (frame >> 14) as u8, value: i16::from_be_bytes([k[2], k[3]])}
+            }).collect();
+            offset += 4*key_cnt;
+            channels.push(AnimChannel{bone: id >> 4, transform: Transform::from_u8((id & 0xF) as u8), keyframes: keyframes});
+        }
+        return Ok(AnimFile{start_frame: start_frame, end_frame: end_frame, channels: channels, pad: pad, trailing: bytes[offset..].to_vec()})
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out : Vec<u8> = Vec::new();
+        out.extend_from_slice(&self.start_frame.to_be_bytes());
+        out.extend_from_slice(&self.end_frame.to_be_bytes());
+        out.extend_from_slice(&(self.channels.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.pad.to_be_bytes());
+        for channel in self.channels.iter(){
+            out.extend_from_slice(&((channel.bone << 4) | channel.transform.to_u8() as u16).to_be_bytes());
+            out.extend_from_slice(&(channel.keyframes.len() as u16).to_be_bytes());
+            for key in channel.keyframes.iter(){
+                out.extend_from_slice(&(((key.flags as u16) << 14) | key.frame).to_be_bytes());
+                out.extend_from_slice(&key.value.to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&self.trailing);
+        return out
+    }
+
+    /// frames from start to end frame, both included
+    pub fn frame_count(&self) -> u16{
+        return self.end_frame.saturating_add(1).saturating_sub(self.start_frame)
+    }
+
+    /// bones the animation can address, one past the highest bone index
+    pub fn bone_count(&self) -> usize{
+        return self.channels.iter().map(|c| c.bone as usize + 1).max().unwrap_or(0)
+    }
+
+    /// checks bone indices against the model's bone count and the order of each channel's keys
+    pub fn validate(&self, bone_count: usize) -> Vec<AnimIssue>{
+        let mut issues = Vec::new();
+        for (i, channel) in self.channels.iter().enumerate(){
+            if channel.bone as usize >= bone_count{
+                issues.push(AnimIssue::BoneOutOfRange{channel: i, bone: channel.bone, bones: bone_count});
+            }
+            if channel.keyframes.is_empty(){
+                issues.push(AnimIssue::EmptyChannel{channel: i});
+            }
+            for (j, pair) in channel.keyframes.windows(2).enumerate(){
+                if pair[1].frame <= pair[0].frame{
+                    issues.push(AnimIssue::Unordered{channel: i, key: j + 1, frame: pair[1].frame, previous: pair[0].frame});
+                }
+            }
+        }
+        return issues
+    }
+
+    pub fn stats(&self) -> AnimStats{
+        let mut bones : Vec<u16> = self.channels.iter().map(|c| c.bone).collect();
+        bones.sort();
+        bones.dedup();
+        return AnimStats{
+            frame_count: self.frame_count(),
+            channels: self.channels.len(),
+            bones_animated: bones.len(),
+            keyframes: self.channels.iter().map(|c| c.keyframes.len()).sum(),
+        }
+    }
+}
+
+fn yaml_value(y: &Yaml) -> Result<i16, String>{
+    let v = match y{
+        Yaml::Integer(i) => *i as f64,
+        Yaml::Real(_) => y.as_f64().ok_or("value is not a number")?,
+        _ => return Err(String::from("value missing or not a number")),
+    };
+    let raw = v*64.0;
+    if raw.fract() != 0.0 || raw < i16::MIN as f64 || raw > i16::MAX as f64{
+        return Err(format!("value {} is not a multiple of 1/64 between -512 and 512", v));
+    }
+    return Ok(raw as i16)
+}
+
+fn yaml_u16(y: &Yaml, key: &str, max: u16) -> Result<u16, String>{
+    match y[key].as_i64(){
+        Some(v) if (0..=max as i64).contains(&v) => Ok(v as u16),
+        Some(v) => Err(format!("{} {} is not between 0 and {}", key, v, max)),
+        None => Err(format!("{} missing or not an integer", key)),
+    }
+}
+
+impl AnimFile{
+    /// channels with one keyframe per line, values are in degrees, scale or units
+    pub fn to_yaml(&self) -> String{
+        let mut out = String::new();
+        writeln!(out, "start_frame: {}", self.start_frame).unwrap();
+        writeln!(out, "end_frame: {}", self.end_frame).unwrap();
+        if self.pad != 0{
+            writeln!(out, "pad: 0x{:04X}", self.pad).unwrap();
+        }
+        if !self.trailing.is_empty(){
+            let vals : Vec<String> = self.trailing.iter().map(|b| format!("0x{:02X}", b)).collect();
+            writeln!(out, "trailing: [{}]", vals.join(", ")).unwrap();
+        }
+        if self.channels.is_empty(){
+            writeln!(out, "channels: []").unwrap();
+            return out
+        }
+        writeln!(out, "channels:").unwrap();
+        for channel in self.channels.iter(){
+            writeln!(out, "  - bone: {}", channel.bone).unwrap();
+            writeln!(out, "    transform: {}", channel.transform).unwrap();
+            if channel.keyframes.is_empty(){
+                writeln!(out, "    keyframes: []").unwrap();
+                continue;
+            }
+            writeln!(out, "    keyframes:").unwrap();
+            for key in channel.keyframes.iter(){
+                //f64 prints every 1/64 step exactly, f32 rounds the large ones and they no longer read back
+                let value = key.value as f64 / 64.0;
+                match key.flags{
+                    0 => writeln!(out, "      - {{frame: {}, value: {}}}", key.frame, value).unwrap(),
+                    flags => writeln!(out, "      - {{frame: {}, value: {}, flags: {}}}", key.frame, value, flags).unwrap(),
+                }
+            }
+        }
+        return out
+    }
+
+    pub fn from_yaml(doc: &Yaml) -> Result<AnimFile, String>{
+        let pad = match doc["pad"].is_badvalue(){
+            true => 0,
+            false => yaml_u16(doc, "pad", u16::MAX)?,
+        };
+        let trailing = match &doc["trailing"]{
+            Yaml::BadValue => Vec::new(),
+            Yaml::Array(bytes) => bytes.iter().map(|b| b.as_i64().filter(|b| (0..=0xFF).contains(b)).map(|b| b as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or("trailing is not a list of bytes")?,
+            _ => return Err(String::from("trailing is not a list of bytes")),
+        };
+        let channels = doc["channels"].as_vec().ok_or("channels missing or not a list")?.iter().enumerate().map(|(i, c)|{
+            let err = |reason: String| format!("channel {}: {}", i, reason);
+            let bone = yaml_u16(c, "bone", 0x0FFF).map_err(err)?;
+            let transform = c["transform"].as_str().ok_or(err(String::from("transform missing")))?.parse::<Transform>().map_err(err)?;
+            let keyframes = c["keyframes"].as_vec().ok_or(err(String::from("keyframes missing or not a list")))?.iter().enumerate().map(|(j, k)|{
+                let err = |reason: String| format!("channel {} keyframe {}: {}", i, j, reason);
+                let flags = match k["flags"].is_badvalue(){
+                    true => 0,
+                    false => yaml_u16(k, "flags", 3).map_err(err)? as u8,
+                };
+                return Ok(Keyframe{frame: yaml_u16(k, "frame", 0x3FFF).map_err(err)?, flags: flags, value: yaml_value(&k["value"]).map_err(err)?})
+            }).collect::<Result<Vec<Keyframe>, String>>()?;
+            return Ok(AnimChannel{bone: bone, transform: transform, keyframes: keyframes})
+        }).collect::<Result<Vec<AnimChannel>, String>>()?;
+        return Ok(AnimFile{
+            start_frame: yaml_u16(doc, "start_frame", u16::MAX)?,
+            end_frame: yaml_u16(doc, "end_frame", u16::MAX)?,
+            channels: channels,
+            pad: pad,
+            trailing: trailing,
+        })
+    }
+}
+
+/// frames the game plays per second
+pub const ANIM_FPS : f32 = 30.0;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Bone{
+    pub parent: Option<usize>,
+    pub position: [f32; 3], //model space
+}
+
+/// the bone tree animations are played on
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ModelSkeleton{
+    pub bones: Vec<Bone>,
+}
+
+impl ModelSkeleton{
+    /// adds a node per bone, bone i ends up as node `first + i` where `first` is returned
+    pub fn add_nodes(&self, gltf: &mut Gltf) -> usize{
+        let first = gltf.count("nodes");
+        for (i, bone) in self.bones.iter().enumerate(){
+            let offset = match bone.parent.and_then(|p| self.bones.get(p)){
+                Some(parent) => [0, 1, 2].map(|a| bone.position[a] - parent.position[a]),
+                None => bone.position,
+            };
+            let children : Vec<String> = self.bones.iter().enumerate().filter(|(_, b)| b.parent == Some(i)).map(|(c, _)| (first + c).to_string()).collect();
+            let mut node = format!("{{\"name\": \"bone_{}\", \"translation\": [{}, {}, {}]", i, offset[0], offset[1], offset[2]);
+            if !children.is_empty(){
+                node += &format!(", \"children\": [{}]", children.join(", "));
+            }
+            gltf.add("nodes", node + "}");
+            if bone.parent.filter(|p| *p < self.bones.len()).is_none(){
+                gltf.scene_nodes.push(first + i);
+            }
+        }
+        return first
+    }
+}
+
+/// one animated property of a bone node, `values` holds 4 floats a key for rotations and 3 otherwise
+#[derive(PartialEq, Debug, Clone)]
+pub struct GltfChannel{
+    pub node: usize,
+    pub path: &'static str, //rotation, translation or scale
+    pub times: Vec<f32>, //seconds
+    pub values: Vec<f32>,
+}
+
+//linear between keys, held before the first and after the last
+fn sample(keys: &[Keyframe], frame: f32) -> f32{
+    let next = keys.iter().position(|k| k.frame as f32 >= frame);
+    return match next{
+        None => keys.last().map(|k| k.value_f32()).unwrap_or(0.0),
+        Some(0) => keys[0].value_f32(),
+        Some(i) => {
+            let (a, b) = (&keys[i - 1], &keys[i]);
+            let t = (frame - a.frame as f32) / (b.frame - a.frame) as f32;
+            a.value_f32() + t*(b.value_f32() - a.value_f32())
+        },
+    }
+}
+
+fn raw_value(value: f32) -> i16{
+    return (value*64.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+//x y z w quaternion for euler degrees applied yaw (y), pitch (x), then roll (z) like the game's bone matrices
+fn euler_to_quat(deg: [f32; 3]) -> [f32; 4]{
+    let half = |d: f32| (d.to_radians()/2.0).sin_cos();
+    let (sx, cx) = half(deg[0]);
+    let (sy, cy) = half(deg[1]);
+    let (sz, cz) = half(deg[2]);
+    let mul = |a: [f32; 4], b: [f32; 4]| [
+        a[3]*b[0] + a[0]*b[3] + a[1]*b[2] - a[2]*b[1],
+        a[3]*b[1] - a[0]*b[2] + a[1]*b[3] + a[2]*b[0],
+        a[3]*b[2] + a[0]*b[1] - a[1]*b[0] + a[2]*b[3],
+        a[3]*b[3] - a[0]*b[0] - a[1]*b[1] - a[2]*b[2],
+    ];
+    return mul(mul([0.0, sy, 0.0, cy], [sx, 0.0, 0.0, cx]), [0.0, 0.0, sz, cz])
+}
+
+impl AnimFile{
+    /// the keyframes as glTF channels on the skeleton's bone nodes. the x, y and z channels of a
+    /// transform are sampled together at every frame any of them has a key, keyframe easing flags are not kept
+    pub fn to_gltf_channels(&self, skeleton: &ModelSkeleton) -> Result<Vec<GltfChannel>, AnimError>{
+        for channel in self.channels.iter(){
+            if channel.bone as usize >= skeleton.bones.len(){
+                return Err(AnimError::BoneOutOfRange{bone: channel.bone, bones: skeleton.bones.len()});
+            }
+            if let Transform::Unknown(code) = channel.transform{
+                return Err(AnimError::UnknownTransform{bone: channel.bone, code: code});
+            }
+        }
+        let mut bones : Vec<u16> = self.channels.iter().map(|c| c.bone).collect();
+        bones.sort();
+        bones.dedup();
+
+        let mut out = Vec::new();
+        for bone in bones{
+            for (path, base) in [("rotation", 0u8), ("scale", 3), ("translation", 6)]{
+                let axes : Vec<Option<&AnimChannel>> = (0..3).map(|a| self.channels.iter()
+                    .find(|c| c.bone == bone && c.transform.to_u8() == base + a && !c.keyframes.is_empty())).collect();
+                let mut frames : Vec<u16> = axes.iter().flatten().flat_map(|c| c.keyframes.iter().map(|k| k.frame)).collect();
+                if frames.is_empty(){
+                    continue;
+                }
+                frames.sort();
+                frames.dedup();
+
+                let rest = skeleton.bones[bone as usize].parent.and_then(|p| skeleton.bones.get(p))
+                    .map(|p| [0, 1, 2].map(|a| skeleton.bones[bone as usize].position[a] - p.position[a]))
+                    .unwrap_or(skeleton.bones[bone as usize].position);
+                let mut values = Vec::new();
+                for frame in frames.iter(){
+                    let xyz = [0, 1, 2].map(|a| axes[a].map(|c| sample(&c.keyframes, *frame as f32)));
+                    match path{
+                        "rotation" => values.extend_from_slice(&euler_to_quat(xyz.map(|v| v.unwrap_or(0.0)))),
+                        "scale" => values.extend(xyz.iter().map(|v| v.unwrap_or(1.0))),
+                        _ => values.extend((0..3).map(|a| rest[a] + xyz[a].unwrap_or(0.0))),
+                    }
+                }
+                let times = frames.iter().map(|f| f.saturating_sub(self.start_frame) as f32/ANIM_FPS).collect();
+                out.push(GltfChannel{node: bone as usize, path: path, times: times, values: values});
+            }
+        }
+        return Ok(out)
+    }
+
+    /// stretches the animation from its start frame, keys that land between frames move to the
+    /// nearest one and take the value the stretched curve has there
+    pub fn scale_time(&mut self, factor: f32) -> Result<(), AnimError>{
+        if !factor.is_finite() || factor <= 0.0{
+            return Err(AnimError::BadScale(factor));
+        }
+        let start = self.start_frame as f32;
+        let scale = |frame: u16| ((start + (frame as f32 - start)*factor).round().max(0.0) as u16).min(0x3FFF);
+        for channel in self.channels.iter_mut(){
+            let old = channel.keyframes.clone();
+            let mut keys : Vec<Keyframe> = Vec::with_capacity(old.len());
+            for key in old.iter(){
+                let frame = scale(key.frame);
+                //keys squeezed onto the same frame keep the first
+                if keys.last().map(|k| k.frame) == Some(frame){
+                    continue;
+                }
+                let source = start + (frame as f32 - start)/factor;
+                let value = match source == key.frame as f32{
+                    true => key.value,
+                    false => raw_value(sample(&old, source)),
+                };
+                keys.push(Keyframe{frame: frame, flags: key.flags, value: value});
+            }
+            channel.keyframes = keys;
+        }
+        self.end_frame = scale(self.end_frame).max(self.start_frame);
+        return Ok(())
+    }
+
+    /// keeps frames `start` to `end`, channels with keys cut off get a key interpolated at the new edge
+    pub fn trim(&mut self, start: u16, end: u16) -> Result<(), AnimError>{
+        if end < start{
+            return Err(AnimError::BadRange{start: start, end: end});
+        }
+        for channel in self.channels.iter_mut(){
+            let old = &channel.keyframes;
+            let mut keys : Vec<Keyframe> = old.iter().filter(|k| (start..=end).contains(&k.frame)).copied().collect();
+            let edge = |frame: u16| Keyframe{frame: frame, flags: 0, value: raw_value(sample(old, frame as f32))};
+            if old.iter().any(|k| k.frame < start) && keys.first().map(|k| k.frame) != Some(start){
+                keys.insert(0, edge(start));
+            }
+            if old.iter().any(|k| k.frame > end) && keys.last().map(|k| k.frame) != Some(end){
+                keys.push(edge(end));
+            }
+            channel.keyframes = keys;
+        }
+        self.start_frame = start;
+        self.end_frame = end;
+        return Ok(())
+    }
+
+    /// adds the animation to a document whose bone nodes start at `first_node`
+    pub fn add_to_gltf(&self, gltf: &mut Gltf, skeleton: &ModelSkeleton, first_node: usize, name: &str) -> Result<usize, AnimError>{
+        let mut samplers = Vec::new();
+        let mut channels = Vec::new();
+        for channel in self.to_gltf_channels(skeleton)?{
+            let input = gltf.add_floats(&channel.times, "SCALAR", true);
+            let output = gltf.add_floats(&channel.values, if channel.path == "rotation" { "VEC4" } else { "VEC3" }, false);
+            channels.push(format!("{{\"sampler\": {}, \"target\": {{\"node\": {}, \"path\": \"{}\"}}}}", samplers.len(), first_node + channel.node, channel.path));
+            samplers.push(format!("{{\"input\": {}, \"output\": {}, \"interpolation\": \"LINEAR\"}}", input, output));
+        }
+        let json = format!("{{\"name\": {:?}, \"samplers\": [{}], \"channels\": [{}]}}", name, samplers.join(", "), channels.join(", "));
+        return Ok(gltf.add("animations", json))
+    }
+}
+
+/// a .gltf holding the skeleton and one clip per named animation
+pub fn to_gltf(skeleton: &ModelSkeleton, animations: &[(&str, &AnimFile)]) -> Result<String, AnimError>{
+    let mut gltf = Gltf::new();
+    let first = skeleton.add_nodes(&mut gltf);
+    for (name, animation) in animations.iter(){
+        animation.add_to_gltf(&mut gltf, skeleton, first, name)?;
+    }
+    return Ok(gltf.to_gltf())
+}
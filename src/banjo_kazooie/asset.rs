@@ -1,22 +1,42 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::{self, File, DirBuilder};
-use std::io::{Write, Read, BufWriter};
+use std::io::{Write, BufWriter};
+use std::ops::Range;
 use std::path::Path;
 use yaml_rust::{Yaml, YamlLoader};
 use png;
 
+use super::magic;
+use super::text;
+use super::charset;
+use super::anim;
+use super::gfx;
+use super::gltf::Gltf;
+use super::midi;
+use super::model;
+
 pub fn from_seg_indx_and_bytes(segment :usize, i :usize, in_bytes: &[u8]) -> Box<dyn Asset>{
     return match segment{
         0 => Box::new(Animation::from_bytes(in_bytes)),
-        1 | 3 => match in_bytes { //models and sprites
-            [0x00, 0x00, 0x00, 0x0B, ..] => Box::new(Model::from_bytes(in_bytes)),
-            _ => Box::new(Sprite::from_bytes(in_bytes)),
+        1 | 3 => match magic::starts_with_magic(in_bytes, AssetType::Model) { //models and sprites
+            true => Box::new(Model::from_bytes(in_bytes)),
+            false => Box::new(Sprite::from_bytes(in_bytes)),
         }, //sprites
         2 => Box::new(LevelSetup::from_bytes(in_bytes)),
-        4 => match in_bytes { //Dialog, GruntyQuestions, QuizQuestions, DemoButtonFiles
-                [0x01, 0x01, 0x02, 0x05, 0x00, ..] => Box::new(QuizQuestion::from_bytes(in_bytes)),
-                [0x01, 0x03, 0x00, 0x05, 0x00, ..] => Box::new(GruntyQuestion::from_bytes(in_bytes)),
-                [0x01, 0x03, 0x00,..] => Box::new(Dialog::from_bytes(in_bytes)),
-                _ => Box::new(DemoButtonFile::from_bytes(in_bytes)),
+        4 => match text::detect_kind(in_bytes).kind { //Dialog, GruntyQuestions, QuizQuestions, DemoButtonFiles
+                kind @ (AssetType::QuizQuestion | AssetType::GruntyQuestion) => match QuestionAsset::parse(kind, in_bytes){
+                    Ok(q) => Box::new(q),
+                    Err(e) => {eprintln!("warning: asset {:04X}, {}, keeping question as binary", i, e); Box::new(Binary::from_bytes(in_bytes))},
+                },
+                AssetType::Dialog => match Dialog::parse(in_bytes){
+                    Ok(d) => Box::new(d),
+                    Err(e) => {eprintln!("warning: asset {:04X}, {}, keeping dialog as binary", i, e); Box::new(Binary::from_bytes(in_bytes))},
+                },
+                _ => match DemoButtonFile::parse(in_bytes){
+                    Ok(d) => Box::new(d),
+                    Err(e) => {eprintln!("warning: asset {:04X}, {}, keeping demo as binary", i, e); Box::new(Binary::from_bytes(in_bytes))},
+                },
             },
         5 => Box::new(Model::from_bytes(in_bytes)),
         6 => Box::new(MidiSeqFile::from_bytes(in_bytes)),
@@ -37,6 +57,45 @@ pub enum ImgFmt{
     Unknown(u16),
 }
 
+impl fmt::Display for ImgFmt{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ImgFmt::Unknown(code) => write!(f, "Unknown(0x{:04X})", code),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::str::FromStr for ImgFmt{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ImgFmt, String>{
+        match s {
+            "CI4"    => Ok(ImgFmt::CI4),
+            "CI8"    => Ok(ImgFmt::CI8),
+            "I4"     => Ok(ImgFmt::I4),
+            "I8"     => Ok(ImgFmt::I8),
+            "RGBA16" => Ok(ImgFmt::RGBA16),
+            "RGBA32" => Ok(ImgFmt::RGBA32),
+            "IA4"    => Ok(ImgFmt::IA4),
+            "IA8"    => Ok(ImgFmt::IA8),
+            _ => {
+                //older descriptors wrote the code in decimal
+                let code = s.strip_prefix("Unknown(").and_then(|v| v.strip_suffix(")"))
+                    .and_then(|v| match v.strip_prefix("0x"){
+                        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                        None => v.parse::<u16>().ok(),
+                    });
+                match code{
+                    Some(code) => Ok(ImgFmt::Unknown(code)),
+                    None => Err(format!("unrecognized image format \"{}\", expected one of CI4, CI8, I4, I8, IA4, IA8, RGBA16, RGBA32 or Unknown(0xNNNN)", s)),
+                }
+            },
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
 pub enum AssetType{
     Animation,
     Binary,
@@ -50,6 +109,66 @@ pub enum AssetType{
     Sprite(ImgFmt),
 }
 
+/// whether a yaml may be rebuilt into something other than the bytes it was extracted from
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Reproduction{
+    Exact,
+    Normalized,
+}
+
+impl Reproduction{
+    pub fn from_yaml(yaml: &Yaml) -> Result<Reproduction, String>{
+        match yaml.as_str(){
+            None | Some("exact") => Ok(Reproduction::Exact),
+            Some("normalized") => Ok(Reproduction::Normalized),
+            Some(x) => Err(format!("unknown reproduction mode \"{}\", expected exact or normalized", x)),
+        }
+    }
+
+    /// asks before a serializer normalizes something, never allowed in exact mode
+    pub fn normalize(&self, what: &str) -> bool{
+        match self{
+            Reproduction::Exact => false,
+            Reproduction::Normalized => {
+                eprintln!("warning: normalizing {}", what);
+                true
+            },
+        }
+    }
+
+    /// for edits that can't be written back verbatim, only an error in exact mode
+    pub fn require_exact(&self, reproducible: bool, what: &str) -> Result<(), NotReproducible>{
+        if reproducible { return Ok(()); }
+        match self{
+            Reproduction::Exact => Err(NotReproducible{what: what.to_string()}),
+            Reproduction::Normalized => {
+                eprintln!("warning: {} can not be reproduced exactly", what);
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NotReproducible{
+    pub what: String,
+}
+
+impl fmt::Display for NotReproducible{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{} can not be reproduced exactly, set `reproduction: normalized` to allow it", self.what)
+    }
+}
+
+impl fmt::Display for Reproduction{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Reproduction::Exact => write!(f, "exact"),
+            Reproduction::Normalized => write!(f, "normalized"),
+        }
+    }
+}
+
 pub struct Binary{
     bytes: Vec<u8>,
 }
@@ -79,78 +198,240 @@ impl Asset for Binary{
     }
 }
 
+/// problems reading a hand edited yaml, `section` and `index` point at the broken entry
+#[derive(Debug)]
+pub enum ReadError{
+    Io{file: String, reason: String},
+    Yaml{file: String, reason: String},
+    Toml{file: String, reason: String},
+    Field{file: String, section: &'static str, index: Option<usize>, reason: String},
+    Text{file: String, error: text::TextError}, //parsed fine but can't be serialized
+}
+
+impl fmt::Display for ReadError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ReadError::Io{file, reason} =>
+                write!(f, "{}: {}", file, reason),
+            ReadError::Yaml{file, reason} =>
+                write!(f, "{}: invalid yaml: {}", file, reason),
+            ReadError::Toml{file, reason} =>
+                write!(f, "{}: invalid toml: {}", file, reason),
+            ReadError::Field{file, section, index: None, reason} =>
+                write!(f, "{}: {}: {}", file, section, reason),
+            ReadError::Field{file, section, index: Some(index), reason} =>
+                write!(f, "{}: {} entry {}: {}", file, section, index, reason),
+            ReadError::Text{file, error} =>
+                write!(f, "{}: {}", file, error),
+        }
+    }
+}
+
+//loads a yaml document and checks its `type`
+//json text assets load through here as well, json being a subset of yaml
+fn load_yaml(path: &Path, expected_type: &str) -> Result<Yaml, ReadError>{
+    let file = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|e| ReadError::Io{file: file.clone(), reason: e.to_string()})?;
+    let doc = YamlLoader::load_from_str(&text).map_err(|e| ReadError::Yaml{file: file.clone(), reason: e.to_string()})?
+        .into_iter()
+        .next()
+        .ok_or(ReadError::Yaml{file: file.clone(), reason: String::from("empty document")})?;
+    match doc["type"].as_str(){
+        Some(t) if t == expected_type => Ok(doc),
+        Some(t) => Err(ReadError::Field{file: file, section: "type", index: None, reason: format!("expected {}, found {}", expected_type, t)}),
+        None => Err(ReadError::Field{file: file, section: "type", index: None, reason: String::from("missing")}),
+    }
+}
+
+//reads the BKString list stored under `section`, a missing or empty section has no strings
+fn read_strings(doc: &Yaml, path: &Path, section: &'static str, table: &charset::CharTable) -> Result<Vec<BKString>, ReadError>{
+    let field_err = |index: Option<usize>, reason: String| ReadError::Field{file: path.display().to_string(), section: section, index: index, reason: reason};
+    //older versions wrote empty sections as a bare `top:`
+    if doc[section].is_null() || doc[section].is_badvalue(){
+        return Ok(Vec::new());
+    }
+    doc[section].as_vec()
+        .ok_or_else(|| field_err(None, String::from("not a list")))?
+        .iter()
+        .enumerate()
+        .map(|(i, y)| BKString::from_yaml(y, table).map_err(|reason| field_err(Some(i), reason)))
+        .collect()
+}
+
+fn read_reproduction(doc: &Yaml, path: &Path) -> Result<Reproduction, ReadError>{
+    Reproduction::from_yaml(&doc["reproduction"]).map_err(|reason| ReadError::Field{file: path.display().to_string(), section: "reproduction", index: None, reason: reason})
+}
+
+//`count` strings of a cmd byte, a length byte and the string bytes, starting at `offset`
+fn read_bk_strings(in_bytes: &[u8], offset: &mut usize, count: usize) -> Result<Vec<BKString>, text::TextError>{
+    let mut out = Vec::with_capacity(count);
+    for _ in 0..count{
+        let truncated = text::TextError::Truncated{offset: *offset, len: in_bytes.len()};
+        let cmd : u8 = *in_bytes.get(*offset).ok_or(truncated.clone())?;
+        let str_size : u8 = *in_bytes.get(*offset + 1).ok_or(truncated.clone())?;
+        let string = in_bytes.get(*offset + 2 .. *offset + 2 + str_size as usize).ok_or(truncated)?;
+        out.push(BKString{cmd : cmd, string : string.to_vec()});
+        *offset += 2 + str_size as usize;
+    }
+    return Ok(out)
+}
+
+fn write_yaml_strings(out: &mut File, section: &str, entries: &[BKString], table: &charset::CharTable){
+    if entries.is_empty(){
+        writeln!(out, "{}: []", section).unwrap();
+        return;
+    }
+    writeln!(out, "{}:", section).unwrap();
+    for text in entries.iter(){
+        writeln!(out,"  - {}", text.to_yaml(table)).unwrap()
+    }
+}
+
 #[derive(Clone)]
-struct BKString{
-    cmd: u8,
-    string: Vec<u8>,
+pub struct BKString{
+    pub cmd: u8,
+    pub string: Vec<u8>,
 }
 
 impl BKString{
-    pub fn from_yaml(yaml: &Yaml) -> BKString{
-        let cmd = yaml["cmd"].as_i64().unwrap() as u8;
-        let string = string_to_vecu8(&yaml["string"].as_str().unwrap());            
+    pub fn from_yaml(yaml: &Yaml, table: &charset::CharTable) -> Result<BKString, String>{
+        //older yamls only have the raw byte
+        let cmd = match (&yaml["cmd"], yaml["cmd"].as_i64()){
+            (_, Some(cmd)) if (0..=0xFF).contains(&cmd) => cmd as u8,
+            (_, Some(cmd)) => return Err(format!("cmd {} does not fit in a byte", cmd)),
+            (Yaml::String(name), None) => u8::from(name.parse::<text::DialogCmd>()?),
+            (Yaml::BadValue, None) => return Err(String::from("missing cmd")),
+            (_, None) => return Err(String::from("cmd is neither a byte nor a name")),
+        };
+        let mut string = match &yaml["string"]{
+            Yaml::String(string) => string_to_vecu8(string, table),
+            Yaml::BadValue => return Err(String::from("missing string")),
+            _ => return Err(String::from("string is not a string, try quoting it")),
+        };
+        match &yaml["terminated"]{
+            Yaml::Boolean(false) => {string.pop();},
+            Yaml::Boolean(true) | Yaml::BadValue => {},
+            _ => return Err(String::from("terminated is not true or false")),
+        }
         
-        BKString{cmd : cmd, string: string}
+        Ok(BKString{cmd : cmd, string: string})
+    }
+
+    /// strings end in a 0 byte, the few that don't are marked `terminated: false`
+    pub fn is_terminated(&self) -> bool{
+        return self.string.last() == Some(&0)
+    }
+
+    fn to_yaml(&self, table: &charset::CharTable) -> String{
+        let cmd = text::DialogCmd::from(self.cmd);
+        if self.is_terminated(){
+            return format!("{{ cmd: {}, string: {}}}", cmd, yaml_string(&self.string, table))
+        }
+        eprintln!("warning: string {} has no terminating 0 byte, writing it with terminated: false", yaml_string(&self.string, table));
+        return format!("{{ cmd: {}, string: {}, terminated: false}}", cmd, yaml_string(&self.string, table))
     }
 }
 
+#[derive(Clone)]
 pub struct Dialog{
+    header: [u8; 3], //magic::DIALOG for every known dialog
     bottom: Vec<BKString>,
     top: Vec<BKString>,
+    reproduction: Reproduction,
 }
 
 impl Dialog{
-    pub fn from_bytes(in_bytes: &[u8])->Dialog{
+    /// the header, then the bottom and top sections, each a count byte followed by its strings
+    pub fn parse(in_bytes: &[u8])->Result<Dialog, text::TextError>{
+        let header : [u8; 3] = in_bytes.get(..3).and_then(|h| h.try_into().ok()).ok_or(text::TextError::Truncated{offset: 0, len: in_bytes.len()})?;
+        Dialog::check_header(&header);
         let mut offset : usize = 3;
-            
-        let mut bottom = Vec::new();
-        let bottom_size : u8 = in_bytes[offset];
-        offset += 1;
-        let mut i = 0;
-        for i in 0..bottom_size{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            bottom.push(i_string);
-            offset += 2 + str_size as usize;
-        }
-
-        let mut top = Vec::new();
-        let top_size : u8 = in_bytes[offset];
-        offset += 1;
-        let mut i = 0;
-        for i in 0..top_size{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            top.push(i_string);
-            offset += 2 + str_size as usize;
-        }
-
-        return Dialog{ bottom: bottom, top: top,};
-    }
-
-    pub fn read(path: &Path) -> Dialog{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
-        let doc_type = doc["type"].as_str().unwrap();
-        assert_eq!(doc_type, "Dialog");
-        let bottom_obj = doc["bottom"].as_vec().unwrap();
-        let bottom : Vec<BKString> = bottom_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
+        let section = |offset: &mut usize|{
+            let count = *in_bytes.get(*offset).ok_or(text::TextError::Truncated{offset: *offset, len: in_bytes.len()})?;
+            *offset += 1;
+            read_bk_strings(in_bytes, offset, count as usize)
+        };
+        let bottom = section(&mut offset)?;
+        let top = section(&mut offset)?;
+        return Ok(Dialog{ header: header, bottom: bottom, top: top, reproduction: Reproduction::Exact});
+    }
 
-        let top_obj = doc["top"].as_vec().unwrap();
-        let top : Vec<BKString> = top_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
+    pub fn read(path: &Path) -> Result<Dialog, ReadError>{
+        return Dialog::read_with(path, &charset::DEFAULT)
+    }
+
+    /// reads a yaml written with `table`
+    pub fn read_with(path: &Path, table: &charset::CharTable) -> Result<Dialog, ReadError>{
+        let doc = load_yaml(path, "Dialog")?;
+        let bottom = read_strings(&doc, path, "bottom", table)?;
+        let top = read_strings(&doc, path, "top", table)?;
+
+        let header = match &doc["header"]{
+            Yaml::BadValue => magic::DIALOG,
+            Yaml::Array(bytes) => match bytes.iter().map(|b| b.as_i64().filter(|b| (0..=0xFF).contains(b)).map(|b| b as u8)).collect::<Option<Vec<u8>>>(){
+                Some(bytes) if bytes.len() == 3 => [bytes[0], bytes[1], bytes[2]],
+                _ => return Err(ReadError::Field{file: path.display().to_string(), section: "header", index: None, reason: String::from("expected 3 bytes")}),
+            },
+            _ => return Err(ReadError::Field{file: path.display().to_string(), section: "header", index: None, reason: String::from("not a list of bytes")}),
+        };
+        Dialog::check_header(&header);
+
+        let out = Dialog{header: header, bottom: bottom, top: top, reproduction: read_reproduction(&doc, path)?};
+        text::TextAsset::validate(&out).map_err(|e| ReadError::Text{file: path.display().to_string(), error: e})?;
+        Ok(out)
+    }
+
+    pub fn stats(&self) -> text::TextStats{
+        return text::text_stats(self, self.to_bytes().len())
+    }
+
+    /// builds a dialog from plain text, lines are word wrapped into strings that fit the textbox
+    pub fn from_plain_text(bottom: &str, top: &str, options: &text::PlainTextOptions) -> Result<Dialog, text::TextError>{
+        let bottom = text::wrap_plain_text(bottom, "bottom", options)?;
+        let top = text::wrap_plain_text(top, "top", options)?;
+        return Ok(Dialog{header: magic::DIALOG, bottom: bottom, top: top, reproduction: Reproduction::Exact})
+    }
+
+    /// the visible text of the bottom and top strings, one string per line
+    pub fn to_plain_text(&self, table: &charset::CharTable) -> (String, String){
+        let join = |entries: &[BKString]| entries.iter().map(|t| text::visible_text(&t.string, table)).collect::<Vec<String>>().join("\n");
+        return (join(&self.bottom), join(&self.top))
+    }
+
+    /// writes the yaml or json with the strings encoded through `table`
+    pub fn write_with(&self, path: &Path, table: &charset::CharTable){
+        if TextFormat::from_path(path) == TextFormat::Json{
+            return write_text_json(path, "Dialog", self.reproduction, Some(&self.header), text::TextAsset::sections(self), table);
+        }
+        let mut bin_file = File::create(path).unwrap();
+        
+        writeln!(bin_file, "type: Dialog").unwrap();
+        writeln!(bin_file, "reproduction: {}", self.reproduction).unwrap();
+        writeln!(bin_file, "header: [0x{:02X}, 0x{:02X}, 0x{:02X}]", self.header[0], self.header[1], self.header[2]).unwrap();
+        write_yaml_strings(&mut bin_file, "bottom", &self.bottom, table);
+        write_yaml_strings(&mut bin_file, "top", &self.top, table);
+    }
+
+    fn check_header(header: &[u8; 3]){
+        if *header != magic::DIALOG{
+            eprintln!("warning: unknown dialog header {:02X?}, expected {:02X?}", header, magic::DIALOG);
+        }
+    }
+}
+
+impl text::TextAsset for Dialog{
+    fn sections(&self) -> Vec<(&'static str, &[BKString])>{
+        vec![("bottom", &self.bottom), ("top", &self.top)]
+    }
 
-        Dialog{bottom: bottom, top: top}
+    fn sections_mut(&mut self) -> Vec<(&'static str, &mut [BKString])>{
+        vec![("bottom", &mut self.bottom), ("top", &mut self.top)]
     }
 }
 
 impl Asset for Dialog{
     fn to_bytes(&self)->Vec<u8>{
-        let mut out :Vec<u8> = vec![0x01, 0x03, 0x00];
+        let mut out :Vec<u8> = self.header.to_vec();
         out.push(self.bottom.len() as u8);
         for text in self.bottom.iter(){
             out.push(text.cmd);
@@ -171,152 +452,90 @@ impl Asset for Dialog{
     }
 
     fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        
-        writeln!(bin_file, "type: Dialog").unwrap();
-        writeln!(bin_file, "bottom:").unwrap();
-        for text in self.bottom.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
-        writeln!(bin_file, "top:").unwrap();
-        for text in self.top.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
+        self.write_with(path, &charset::DEFAULT)
     }
 }
 
-pub struct QuizQuestion{
+/// shared body of QuizQuestion and GruntyQuestion, they only differ in their header and yaml type
+pub struct QuestionAsset{
+    asset_type: AssetType,
     question: Vec<BKString>,
     options: [BKString; 3],
+    reproduction: Reproduction,
 }
 
-impl QuizQuestion{
-    pub fn from_bytes(in_bytes: &[u8])->QuizQuestion{
-        let mut texts = Vec::new();
-        let mut str_cnt = in_bytes[5];
-        let mut offset : usize = 6;
-        for _i in 0..str_cnt{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            texts.push(i_string);
-            offset += 2 + str_size as usize;
+impl QuestionAsset{
+    pub fn stats(&self) -> text::TextStats{
+        return text::text_stats(self, self.to_bytes().len())
+    }
+
+    fn type_name(asset_type: AssetType) -> &'static str{
+        match asset_type{
+            AssetType::GruntyQuestion => "GruntyQuestion",
+            _ => "QuizQuestion",
+        }
+    }
+
+    /// the last three strings are the options, so at least three have to be there
+    pub fn parse(asset_type: AssetType, in_bytes: &[u8])->Result<QuestionAsset, text::TextError>{
+        let str_cnt = *in_bytes.get(5).ok_or(text::TextError::Truncated{offset: 5, len: in_bytes.len()})?;
+        if str_cnt < 3{
+            return Err(text::TextError::MissingOptions{found: str_cnt as usize});
         }
+        let texts = read_bk_strings(in_bytes, &mut 6, str_cnt as usize)?;
         let (q_text, o_text) = texts.split_at(texts.len() - 3); 
 
         let options : [BKString; 3] = [o_text[0].clone(), o_text[1].clone(), o_text[2].clone()];
-        return QuizQuestion{ question: q_text.to_vec(), options: options};
+        return Ok(QuestionAsset{asset_type: asset_type, question: q_text.to_vec(), options: options, reproduction: Reproduction::Exact});
     }
 
-    pub fn read(path: &Path) -> QuizQuestion{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
-        let doc_type = doc["type"].as_str().unwrap();
-        assert_eq!(doc_type, "QuizQuestion");
-        let q_obj = doc["question"].as_vec().unwrap();
-        let q : Vec<BKString> = q_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
-
-        let a_obj = doc["options"].as_vec().unwrap();
-        let a : Vec<BKString> = a_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
+    pub fn read(asset_type: AssetType, path: &Path, table: &charset::CharTable) -> Result<QuestionAsset, ReadError>{
+        let doc = load_yaml(path, QuestionAsset::type_name(asset_type))?;
+        let q = read_strings(&doc, path, "question", table)?;
+        let a = read_strings(&doc, path, "options", table)?;
+        if a.len() != 3{
+            return Err(ReadError::Field{file: path.display().to_string(), section: "options", index: None, reason: format!("expected 3 options, found {}", a.len())});
+        }
 
         let options : [BKString; 3] = [a[0].clone(), a[1].clone(), a[2].clone()];
 
-        QuizQuestion{question: q, options: options}
+        let out = QuestionAsset{asset_type: asset_type, question: q, options: options, reproduction: read_reproduction(&doc, path)?};
+        text::TextAsset::validate(&out).map_err(|e| ReadError::Text{file: path.display().to_string(), error: e})?;
+        Ok(out)
     }
-}
 
-impl Asset for QuizQuestion{
-    fn to_bytes(&self)->Vec<u8>{
-        let mut out :Vec<u8> = vec![0x01, 0x01, 0x02, 0x05, 0x00];
-        out.push((self.question.len() + self.options.len()) as u8);
-        for text in self.question.iter(){
-            out.push(text.cmd);
-            out.push(text.string.len() as u8);
-            out.append(&mut text.string.clone());
-        }
-        for text in self.options.iter(){
-            out.push(text.cmd);
-            out.push(text.string.len() as u8);
-            out.append(&mut text.string.clone());
+    pub fn write_with(&self, path: &Path, table: &charset::CharTable){
+        if TextFormat::from_path(path) == TextFormat::Json{
+            return write_text_json(path, QuestionAsset::type_name(self.asset_type), self.reproduction, None, text::TextAsset::sections(self), table);
         }
-        return out;
-    }
-    
-    fn get_type(&self)->AssetType{
-        return AssetType::QuizQuestion
-    }
-
-    fn write(&self, path: &Path){
         let mut bin_file = File::create(path).unwrap();
         
-        writeln!(bin_file, "type: QuizQuestion").unwrap();
-        writeln!(bin_file, "question:").unwrap();
-        for text in self.question.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
-        writeln!(bin_file, "options:").unwrap();
-        for text in self.options.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
+        writeln!(bin_file, "type: {}", QuestionAsset::type_name(self.asset_type)).unwrap();
+        writeln!(bin_file, "reproduction: {}", self.reproduction).unwrap();
+        write_yaml_strings(&mut bin_file, "question", &self.question, table);
+        write_yaml_strings(&mut bin_file, "options", &self.options, table);
     }
 }
 
-pub struct GruntyQuestion{
-    question: Vec<BKString>,
-    options: [BKString; 3],
-}
-
-impl GruntyQuestion{
-    pub fn from_bytes(in_bytes: &[u8])->GruntyQuestion{
-        let mut texts = Vec::new();
-        let mut str_cnt = in_bytes[5];
-        let mut offset : usize = 6;
-        for _i in 0..str_cnt{
-            let cmd : u8 = in_bytes[offset];
-            let str_size : u8 = in_bytes[offset + 1];
-            let i_string = BKString{cmd : cmd, string : in_bytes[offset + 2 .. offset + 2 + str_size as usize].to_vec()};
-            texts.push(i_string);
-            offset += 2 + str_size as usize;
-        }
-        let (q_text, o_text) = texts.split_at(texts.len() - 3); 
-
-        let options : [BKString; 3] = [o_text[0].clone(), o_text[1].clone(), o_text[2].clone()];
-        return GruntyQuestion{ question: q_text.to_vec(), options: options};
+impl text::TextAsset for QuestionAsset{
+    fn sections(&self) -> Vec<(&'static str, &[BKString])>{
+        vec![("question", &self.question), ("options", &self.options)]
     }
 
-    pub fn read(path: &Path) -> GruntyQuestion{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
-        let doc_type = doc["type"].as_str().unwrap();
-        assert_eq!(doc_type, "GruntyQuestion");
-        let q_obj = doc["question"].as_vec().unwrap();
-        let q : Vec<BKString> = q_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
-
-        let a_obj = doc["options"].as_vec().unwrap();
-        let a : Vec<BKString> = a_obj.iter()
-            .map(|y|{BKString::from_yaml(y)})
-            .collect();
-
-        let options : [BKString; 3] = [a[0].clone(), a[1].clone(), a[2].clone()];
+    fn sections_mut(&mut self) -> Vec<(&'static str, &mut [BKString])>{
+        vec![("question", &mut self.question), ("options", &mut self.options)]
+    }
 
-        GruntyQuestion{question: q, options: options}
+    fn shares_count(&self) -> bool{
+        true
     }
 }
 
-impl Asset for GruntyQuestion{
+impl Asset for QuestionAsset{
     fn to_bytes(&self)->Vec<u8>{
-        let mut out :Vec<u8> = vec![0x01, 0x03, 0x00, 0x05, 0x00];
+        let mut out :Vec<u8> = magic::for_type(self.asset_type).unwrap().to_vec();
         out.push((self.question.len() + self.options.len()) as u8);
-        for text in self.question.iter(){
-            out.push(text.cmd);
-            out.push(text.string.len() as u8);
-            out.append(&mut text.string.clone());
-        }
-        for text in self.options.iter(){
+        for text in self.question.iter().chain(self.options.iter()){
             out.push(text.cmd);
             out.push(text.string.len() as u8);
             out.append(&mut text.string.clone());
@@ -325,755 +544,4767 @@ impl Asset for GruntyQuestion{
     }
     
     fn get_type(&self)->AssetType{
-        return AssetType::GruntyQuestion
+        return self.asset_type
     }
 
     fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        
-        writeln!(bin_file, "type: GruntyQuestion").unwrap();
-        writeln!(bin_file, "question:").unwrap();
-        for text in self.question.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
-        writeln!(bin_file, "options:").unwrap();
-        for text in self.options.iter(){
-            writeln!(bin_file,"  - {{ cmd: 0x{:02X}, string: \"{}\"}}", text.cmd, vecu8_to_string(&text.string)).unwrap()
-        }
+        self.write_with(path, &charset::DEFAULT)
     }
 }
 
-pub trait Asset {
-    fn to_bytes(&self)->Vec<u8>;
-    fn get_type(&self)->AssetType;
-    fn write(&self, path: &Path);
-}
+pub struct QuizQuestion(QuestionAsset);
 
-fn string_to_vecu8(string: &str) -> Vec<u8>{
-    let mut string = string.as_bytes().to_vec();
-    let mut squig_indx : Vec<usize> = string.windows(2)
-        .enumerate()
-        .filter(|(_, win)|{match win {[0xC3, 0xBD]=> true, _=>false,} })
-        .map(|(i, _)|{i})
-        .collect();
-    squig_indx.reverse();
-    for i in squig_indx{
-        string[i] = 0xFD;
-        string.remove(i+1);
+impl QuizQuestion{
+    pub fn parse(in_bytes: &[u8])->Result<QuizQuestion, text::TextError>{
+        return Ok(QuizQuestion(QuestionAsset::parse(AssetType::QuizQuestion, in_bytes)?))
     }
-    string.push(0);
-    return string
-}
 
-fn vecu8_to_string(bytes: &Vec<u8>) -> String{
-    let mut out : String = String::new();
-    for b in &bytes[..bytes.len() - 1]{
-        let ch = *b as char;
-        if !ch.is_ascii() || *b < 0x20 {
-            out += format!("\\x{:02X}", ch as u8).as_str();
-        }
-        else{
-            out.push(ch);
-        }
+    pub fn read(path: &Path) -> Result<QuizQuestion, ReadError>{
+        return QuizQuestion::read_with(path, &charset::DEFAULT)
     }
-    return out
-}
 
-struct ContInput{
-    x: i8,
-    y: i8,
-    buttons: u16,
-    frames: u8,
-}
+    pub fn read_with(path: &Path, table: &charset::CharTable) -> Result<QuizQuestion, ReadError>{
+        return Ok(QuizQuestion(QuestionAsset::read(AssetType::QuizQuestion, path, table)?))
+    }
 
-impl ContInput{
-    fn to_bytes(&self)->Vec<u8>{
-        let b = self.buttons.to_be_bytes();
-        return vec![self.x as u8, self.y as u8, b[0], b[1], self.frames, 0x00];
+    pub fn write_with(&self, path: &Path, table: &charset::CharTable){
+        self.0.write_with(path, table)
     }
 
-    fn from_yaml(yaml: &Yaml)->ContInput{
-        let x = yaml["x"].as_i64().unwrap() as i8;
-        let y = yaml["y"].as_i64().unwrap() as i8;
-        let buttons = yaml["buttons"].as_i64().unwrap() as u16;
-        let frames = yaml["frames"].as_i64().unwrap() as u8;
-        return ContInput{x: x, y: y, buttons: buttons, frames: frames}
+    pub fn stats(&self) -> text::TextStats{
+        return self.0.stats()
     }
 }
 
-pub struct DemoButtonFile{
-    inputs: Vec<ContInput>,
-    frame1_flag: u8,
-}
+impl text::TextAsset for QuizQuestion{
+    fn sections(&self) -> Vec<(&'static str, &[BKString])>{
+        self.0.sections()
+    }
 
-impl DemoButtonFile{
-    pub fn from_bytes(in_bytes: &[u8])->DemoButtonFile{
-        if in_bytes.len() < 4 { return DemoButtonFile{inputs: Vec::new(), frame1_flag: 0}}
-        let expect_len : usize =  u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
-        let f1f = in_bytes[9];
-        let inputs : Vec<ContInput> = in_bytes[4..].chunks_exact(6)
-            .map(|a|{
-                ContInput{
-                    x : a[0] as i8, 
-                    y : a[1] as i8,
-                    buttons : u16::from_be_bytes([a[2], a[3]]),
-                    frames : a[4],
-                }
-            })
-            .collect();
-        assert_eq!(expect_len, inputs.len()*6);
-        DemoButtonFile{inputs: inputs, frame1_flag: f1f}
+    fn sections_mut(&mut self) -> Vec<(&'static str, &mut [BKString])>{
+        self.0.sections_mut()
     }
 
-    pub fn read(path: &Path) -> DemoButtonFile{
-        let doc = &YamlLoader::load_from_str(&fs::read_to_string(path).expect("could not open yaml")).unwrap()[0];
-        let doc_type = doc["type"].as_str().unwrap();
-        let f1f = doc["flag"].as_i64().unwrap() as u8;
-        assert_eq!(doc_type, "DemoInput");
-        
-        let inputs_yaml = doc["inputs"].as_vec().unwrap();
-        let mut inputs : Vec<ContInput> = inputs_yaml.iter().map(|y|{
-            ContInput::from_yaml(y)
-        })
-        .collect();
-        return DemoButtonFile{inputs:inputs, frame1_flag: f1f}
+    fn shares_count(&self) -> bool{
+        self.0.shares_count()
     }
 }
 
-impl Asset for DemoButtonFile{
+impl Asset for QuizQuestion{
     fn to_bytes(&self)->Vec<u8>{
-        if self.inputs.is_empty() { return Vec::new(); }
-
-        let mut output : Vec<u8> = (6*self.inputs.len() as u32).to_be_bytes().to_vec();
-        let mut input_bytes : Vec<u8> = self.inputs.iter().map(|i|{
-            i.to_bytes()
-        })
-        .flatten()
-        .collect();
-        input_bytes[5] = self.frame1_flag;
-        output.append(&mut input_bytes);
-        return output;
+        self.0.to_bytes()
     }
 
     fn get_type(&self)->AssetType{
-        return AssetType::DemoInput;
+        self.0.get_type()
     }
 
     fn write(&self, path: &Path){
-        let mut demo_file = File::create(path).unwrap();
-        writeln!(demo_file, "type: DemoInput").unwrap();
-        writeln!(demo_file, "flag: 0x{:02X}", self.frame1_flag).unwrap();
-        if(self.inputs.len() == 0){
-            writeln!(demo_file, "inputs: []").unwrap();
-            return;
-        }
-        writeln!(demo_file, "inputs:").unwrap();
-        for input in self.inputs.iter(){
-            writeln!(demo_file, "  - {{x: {:3}, y: {:3}, buttons: 0x{:04X}, frames: {}}}", input.x, input.y, input.buttons, input.frames).unwrap();
-        }
+        self.0.write(path)
     }
 }
 
-/// MidiSeqFile TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
-
-pub struct MidiSeqFile{
-    bytes: Vec<u8>,
-}
+pub struct GruntyQuestion(QuestionAsset);
 
-impl MidiSeqFile{
-    pub fn from_bytes(in_bytes: &[u8])->MidiSeqFile{
-        MidiSeqFile{bytes: in_bytes.to_vec()}
+impl GruntyQuestion{
+    pub fn parse(in_bytes: &[u8])->Result<GruntyQuestion, text::TextError>{
+        return Ok(GruntyQuestion(QuestionAsset::parse(AssetType::GruntyQuestion, in_bytes)?))
     }
 
-    pub fn read(path: &Path) -> MidiSeqFile{
-        MidiSeqFile{bytes: fs::read(path).unwrap()}
+    pub fn read(path: &Path) -> Result<GruntyQuestion, ReadError>{
+        return GruntyQuestion::read_with(path, &charset::DEFAULT)
     }
-}
 
-impl Asset for MidiSeqFile{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+    pub fn read_with(path: &Path, table: &charset::CharTable) -> Result<GruntyQuestion, ReadError>{
+        return Ok(GruntyQuestion(QuestionAsset::read(AssetType::GruntyQuestion, path, table)?))
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Midi;
+    pub fn write_with(&self, path: &Path, table: &charset::CharTable){
+        self.0.write_with(path, table)
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+    pub fn stats(&self) -> text::TextStats{
+        return self.0.stats()
     }
 }
 
-/// LevelSetup TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
-
-pub struct LevelSetup{
-    bytes: Vec<u8>,
-}
+impl text::TextAsset for GruntyQuestion{
+    fn sections(&self) -> Vec<(&'static str, &[BKString])>{
+        self.0.sections()
+    }
 
-impl LevelSetup{
-    pub fn from_bytes(in_bytes: &[u8])->LevelSetup{
-        LevelSetup{bytes: in_bytes.to_vec()}
+    fn sections_mut(&mut self) -> Vec<(&'static str, &mut [BKString])>{
+        self.0.sections_mut()
     }
 
-    pub fn read(path: &Path) -> LevelSetup{
-        LevelSetup{bytes: fs::read(path).unwrap()}
+    fn shares_count(&self) -> bool{
+        self.0.shares_count()
     }
 }
 
-impl Asset for LevelSetup{
+impl Asset for GruntyQuestion{
     fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+        self.0.to_bytes()
     }
 
     fn get_type(&self)->AssetType{
-        return AssetType::LevelSetup;
+        self.0.get_type()
     }
 
     fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+        self.0.write(path)
     }
 }
 
-/// Animation TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
-
-pub struct Animation{
-    bytes: Vec<u8>,
+pub trait Asset {
+    fn to_bytes(&self)->Vec<u8>;
+    fn get_type(&self)->AssetType;
+    fn write(&self, path: &Path);
 }
 
-impl Animation{
-    pub fn from_bytes(in_bytes: &[u8])->Animation{
-        Animation{bytes: in_bytes.to_vec()}
+/// inverse of `vecu8_to_string`, understands `\xNN`, `\\` and `\"`. always appends the terminating 0
+/// chars up to U+00FF are single bytes, which is also what yaml hands back for `\xNN` inside double quotes,
+/// so hand edited double quoted strings keep working next to the single quoted ones we write
+pub fn string_to_vecu8(string: &str, table: &charset::CharTable) -> Vec<u8>{
+    let mut out : Vec<u8> = Vec::new();
+    let mut chars = string.char_indices().peekable();
+    while let Some((i, ch)) = chars.next(){
+        if ch == '\\'{
+            match chars.peek(){
+                Some((_, '\\')) | Some((_, '"')) => {out.push(chars.next().unwrap().1 as u8); continue;},
+                Some((_, 'x')) => {
+                    let hex : String = chars.clone().skip(1).take(2).map(|(_, c)| c).collect();
+                    if hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit()){
+                        out.push(u8::from_str_radix(&hex, 16).unwrap());
+                        chars.nth(2);
+                        continue;
+                    }
+                },
+                _ => {},
+            }
+        }
+        if let Some((byte, len)) = table.match_prefix(&string[i..]){
+            out.push(byte);
+            while chars.peek().is_some_and(|(j, _)| *j < i + len){ chars.next(); }
+            continue;
+        }
+        match ch as u32{
+            0..=0xFF => out.push(ch as u8),
+            _ => out.extend_from_slice(ch.to_string().as_bytes()),
+        }
     }
+    out.push(0);
+    return out
+}
 
-    pub fn read(path: &Path) -> Animation{
-        Animation{bytes: fs::read(path).unwrap()}
+pub fn vecu8_to_string(bytes: &[u8], table: &charset::CharTable) -> String{
+    let body = match bytes.last(){
+        Some(0) => &bytes[..bytes.len() - 1],
+        _ => bytes,
+    };
+    //built from the back, `string_to_vecu8` reads a glyph wherever one starts, so text that would be
+    //read back as a different byte is escaped. escapes start with a backslash, which no glyph has
+    let mut out = String::new();
+    for b in body.iter().rev(){
+        let ch = *b as char;
+        let text = match table.glyph(*b){
+            Some(glyph) => glyph.to_string(),
+            None if !ch.is_ascii() || *b < 0x20 || *b == 0x7F => format!("\\x{:02X}", b),
+            None if ch == '\\' => String::from("\\\\"),
+            None => ch.to_string(),
+        };
+        let read_back = match text.starts_with('\\'){
+            true => None,
+            false => table.match_prefix(&(text.clone() + &out)),
+        };
+        let expected = table.glyph(*b).map(|g| (*b, g.len()));
+        out = match read_back == expected{
+            true => text + &out,
+            false => format!("\\x{:02X}", b) + &out,
+        };
     }
+    return out
 }
 
-impl Asset for Animation{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
-    }
+/// single quoted yaml scalar, yaml leaves the escapes from `vecu8_to_string` alone in these
+/// so `string_to_vecu8` sees them as written. quotes inside are doubled
+fn yaml_string(bytes: &[u8], table: &charset::CharTable) -> String{
+    return format!("'{}'", vecu8_to_string(bytes, table).replace('\'', "''"))
+}
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Animation;
+/// how text and demo assets are written, picked from the file extension so either can be read back
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TextFormat{
+    Yaml,
+    Json,
+}
+
+impl TextFormat{
+    pub fn from_path(path: &Path) -> TextFormat{
+        match path.extension().and_then(|e| e.to_str()){
+            Some("json") => TextFormat::Json,
+            _ => TextFormat::Yaml,
+        }
     }
+}
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+//escapes from `vecu8_to_string` are kept as text, so their backslashes get escaped again for json
+fn json_string(bytes: &[u8], table: &charset::CharTable) -> String{
+    return format!("\"{}\"", vecu8_to_string(bytes, table).replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `{"type": .., "reproduction": .., "header": [..], "<section>": [{"cmd": .., "string": ..}, ..]}`,
+/// the same fields the yaml has
+fn write_text_json(path: &Path, type_name: &str, reproduction: Reproduction, header: Option<&[u8]>, sections: Vec<(&'static str, &[BKString])>, table: &charset::CharTable){
+    let mut out = File::create(path).unwrap();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"type\": \"{}\",", type_name).unwrap();
+    write!(out, "  \"reproduction\": \"{}\"", reproduction).unwrap();
+    if let Some(header) = header{
+        let bytes : Vec<String> = header.iter().map(|b| b.to_string()).collect();
+        write!(out, ",\n  \"header\": [{}]", bytes.join(", ")).unwrap();
+    }
+    for (name, entries) in sections{
+        write!(out, ",\n  \"{}\": [", name).unwrap();
+        for (i, text) in entries.iter().enumerate(){
+            let terminated = match text.is_terminated(){
+                true => "",
+                false => ", \"terminated\": false",
+            };
+            let sep = if i + 1 < entries.len() {","} else {""};
+            write!(out, "\n    {{\"cmd\": \"{}\", \"string\": {}{}}}{}", text::DialogCmd::from(text.cmd), json_string(&text.string, table), terminated, sep).unwrap();
+        }
+        write!(out, "{}]", if entries.is_empty() {""} else {"\n  "}).unwrap();
     }
+    writeln!(out, "\n}}").unwrap();
 }
 
-/// Model TODO !!!!!!!!!
-///     - struct members
-///     - from_bytes
-///     - read
-///     - to_bytes
-///     - write
+/// N64 controller button bits as they sit in `ContInput::buttons`, the two bits not listed are unused
+pub const BUTTONS : [(u16, &str); 14] = [
+    (0x8000, "A"), (0x4000, "B"), (0x2000, "Z"), (0x1000, "Start"),
+    (0x0800, "DUp"), (0x0400, "DDown"), (0x0200, "DLeft"), (0x0100, "DRight"),
+    (0x0020, "L"), (0x0010, "R"),
+    (0x0008, "CUp"), (0x0004, "CDown"), (0x0002, "CLeft"), (0x0001, "CRight"),
+];
 
-pub struct Model{
-    bytes: Vec<u8>,
+fn button_mask() -> u16{
+    return BUTTONS.iter().fold(0, |mask, (bit, _)| mask | bit)
 }
 
-impl Model{
-    pub fn from_bytes(in_bytes: &[u8])->Model{
-        Model{bytes: in_bytes.to_vec()}
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct ContInput{
+    pub x: i8,
+    pub y: i8,
+    pub buttons: u16,
+    pub frames: u8,
+    pub flag: u8, //sixth byte, only seen set on the first input
+}
+
+impl ContInput{
+    fn to_bytes(self)->Vec<u8>{
+        let b = self.buttons.to_be_bytes();
+        return vec![self.x as u8, self.y as u8, b[0], b[1], self.frames, self.flag];
     }
 
-    pub fn read(path: &Path) -> Model{
-        Model{bytes: fs::read(path).unwrap()}
+    //`key` as a T, None when it is absent. values that don't fit are refused instead of wrapping
+    fn field<T: TryFrom<i64>>(yaml: &Yaml, key: &str) -> Result<Option<T>, String>{
+        match &yaml[key]{
+            Yaml::BadValue => Ok(None),
+            Yaml::Integer(v) => T::try_from(*v).map(Some).map_err(|_| format!("{} {} is out of range for {}", key, v, std::any::type_name::<T>())),
+            _ => Err(format!("{} is not an integer", key)),
+        }
     }
-}
 
-impl Asset for Model{
-    fn to_bytes(&self)->Vec<u8>{
-        return self.bytes.clone();
+    fn from_yaml(yaml: &Yaml)->Result<ContInput, String>{
+        let x = ContInput::field::<i8>(yaml, "x")?.ok_or("x is missing")?;
+        let y = ContInput::field::<i8>(yaml, "y")?.ok_or("y is missing")?;
+        //older yamls have the raw mask in buttons
+        let buttons = match &yaml["buttons"]{
+            Yaml::Integer(mask) => u16::try_from(*mask).map_err(|_| format!("buttons 0x{:X} is out of range for u16", mask))?,
+            Yaml::Array(names) => names.iter().map(|n|{
+                let name = n.as_str().ok_or_else(|| format!("button {:?} is not a name", n))?;
+                BUTTONS.iter().find(|(_, b)| *b == name).map(|(bit, _)| *bit)
+                    .ok_or_else(|| format!("unknown button \"{}\", expected one of {}", name, BUTTONS.iter().map(|(_, b)| *b).collect::<Vec<&str>>().join(", ")))
+            }).collect::<Result<Vec<u16>, String>>()?.iter().fold(0, |mask, bit| mask | bit),
+            _ => return Err(String::from("buttons is missing or neither a mask nor a list of names")),
+        };
+        let raw = ContInput::field::<u16>(yaml, "raw")?.unwrap_or(0);
+        let frames = ContInput::field::<u8>(yaml, "frames")?.ok_or("frames is missing")?;
+        let flag = ContInput::field::<u8>(yaml, "flag")?.unwrap_or(0);
+        return Ok(ContInput{x: x, y: y, buttons: buttons | raw, frames: frames, flag: flag})
     }
 
-    fn get_type(&self)->AssetType{
-        return AssetType::Model;
+    fn to_yaml(self) -> String{
+        let flag = match self.flag{
+            0 => String::new(),
+            f => format!(", flag: 0x{:02X}", f),
+        };
+        return format!("{{x: {:3}, y: {:3}, buttons: {}, frames: {}{}}}", self.x, self.y, self.buttons_yaml(), self.frames, flag)
     }
 
-    fn write(&self, path: &Path){
-        let mut bin_file = File::create(path).unwrap();
-        bin_file.write_all(&self.bytes).unwrap();
+    fn to_json(self) -> String{
+        let names : Vec<String> = BUTTONS.iter().filter(|(bit, _)| self.buttons & bit != 0).map(|(_, name)| format!("\"{}\"", name)).collect();
+        let mut out = format!("{{\"x\": {}, \"y\": {}, \"buttons\": [{}]", self.x, self.y, names.join(", "));
+        let unknown = self.buttons & !button_mask();
+        if unknown != 0 { out += &format!(", \"raw\": {}", unknown); }
+        out += &format!(", \"frames\": {}", self.frames);
+        if self.flag != 0 { out += &format!(", \"flag\": {}", self.flag); }
+        return out + "}"
+    }
+
+    //`[A, Z]` style list of the pressed buttons, plus the bits without a name
+    fn buttons_yaml(&self) -> String{
+        let names : Vec<&str> = BUTTONS.iter().filter(|(bit, _)| self.buttons & bit != 0).map(|(_, name)| *name).collect();
+        let unknown = self.buttons & !button_mask();
+        return match unknown{
+            0 => format!("[{}]", names.join(", ")),
+            _ => format!("[{}], raw: 0x{:04X}", names.join(", "), unknown),
+        }
     }
 }
 
-pub struct Texture {
-    texture_type: ImgFmt,
-    w : usize,
-    h : usize,
+#[derive(Debug)]
+pub enum DemoError{
+    NotM64,
+    UnsupportedVersion(u32),
+    Controllers(u8), //demos only have one controller
+    StartsFromSnapshot,
+    Truncated{needed: usize, available: usize},
+    FrameRange{start: usize, len: usize, available: usize},
+    Reset{frame: usize},
+    Length{declared: usize, payload: usize},
+}
 
-    palette : Option<Vec<u8>>,
-    pixel_data : Vec<u8>,
+impl fmt::Display for DemoError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            DemoError::NotM64 => write!(f, "not an m64 movie, signature does not match"),
+            DemoError::UnsupportedVersion(v) => write!(f, "m64 version {} is not supported, expected 3", v),
+            DemoError::Controllers(cnt) => write!(f, "movie uses {} controllers, demos only have 1", cnt),
+            DemoError::StartsFromSnapshot => write!(f, "movie starts from a savestate, demos start from power on"),
+            DemoError::Truncated{needed, available} => write!(f, "needs 0x{:X} bytes, only 0x{:X} available", needed, available),
+            DemoError::FrameRange{start, len, available} => write!(f, "frames {}..{} are out of range, movie has {} frames", start, start + len, available),
+            DemoError::Reset{frame} => write!(f, "frame {} resets the console, demos can not reset", frame),
+            DemoError::Length{declared, payload} => {
+                write!(f, "demo declares 0x{:X} input bytes but holds 0x{:X}", declared, payload)?;
+                if declared % 6 != 0 { write!(f, ", not a whole number of 6 byte inputs")?; }
+                match payload > declared{
+                    true => write!(f, ", 0x{:X} trailing bytes would be ignored", payload - declared),
+                    false => write!(f, ", the last inputs are cut off"),
+                }
+            },
+        }
+    }
 }
 
-impl Texture {
-    pub fn new(texture_type: ImgFmt, w : usize, h : usize, bin : &[u8])->Texture{
-        let palette : Option<Vec<u8>> = match texture_type{
-            ImgFmt::CI4 => Some(bin[0.. 0x20].to_vec()),
-            ImgFmt::CI8 => Some(bin[0.. 0x200].to_vec()),
-            _=> None,
-        };
-        
-        let pixel_data = match texture_type {
-            ImgFmt::CI4 => &bin[0x20..],
-            ImgFmt::CI8 => &bin[0x200..],
-            _ => bin,
-        };
+// mupen64 .m64 layout, all little endian
+const M64_SIGNATURE : [u8; 4] = [b'M', b'6', b'4', 0x1A];
+const M64_HEADER_LEN : usize = 0x400;
+const M64_RESET_BITS : u16 = 0x00C0; //reserved bits, used for resets by mupen
 
-        return Texture{
-            texture_type : texture_type, 
-            w : w,
-            h : h,
-            palette : palette,
-            pixel_data : pixel_data.to_vec(),
-        }
+/// header fields of an exported .m64, strings are cut to fit their fields
+#[derive(Clone)]
+pub struct M64Options{
+    pub author: String,
+    pub description: String,
+    pub rom_name: String,
+    pub fps: u8,
+}
+
+impl Default for M64Options{
+    fn default() -> M64Options{
+        M64Options{author: String::new(), description: String::from("bk_asset_tool demo export"), rom_name: String::from("Banjo-Kazooie"), fps: 60}
     }
+}
 
-    pub fn to_rgba32(&self) -> Vec<u8>{
-        match self.texture_type{
-            ImgFmt::CI4 => 
-            {   
-                match &self.palette{
-                    None => panic!("Expected CI4 palette, but none found"),
-                    Some(pal) => Texture::ci4_to_rgba32(&self.pixel_data, &pal)
-                }
-            },
-            ImgFmt::CI8 => 
-            {   
-                match &self.palette{
-                    None => panic!("Expected CI8 palette, but none found"),
-                    Some(pal) => Texture::ci8_to_rgba32(&self.pixel_data, &pal)
-                }
-            }
-            ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&self.pixel_data),
-            ImgFmt::RGBA32 => self.pixel_data.clone(),
-            ImgFmt::I4 => Texture::i4_to_rgba32(&self.pixel_data),
-            ImgFmt::I8 => Texture::i8_to_rgba32(&self.pixel_data),
-            ImgFmt::IA4 => Texture::ia4_to_rgba32(&self.pixel_data),
-            ImgFmt::IA8 => Texture::ia8_to_rgba32(&self.pixel_data),
-            _ => {panic!("Image type not implemented yet");},
+/// inputs that play back differently on console and emulators, found by `DemoButtonFile::validate`
+#[derive(PartialEq, Debug, Clone)]
+pub enum DemoIssue{
+    StickRange{index: usize, x: i8, y: i8, limit: u8},
+    OpposingDpad{index: usize, buttons: u16}, //left and right or up and down held together
+}
 
+impl fmt::Display for DemoIssue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            DemoIssue::StickRange{index, x, y, limit} =>
+                write!(f, "input {}: stick ({}, {}) goes past {}, the game clamps it", index, x, y, limit),
+            DemoIssue::OpposingDpad{index, buttons} =>
+                write!(f, "input {}: buttons 0x{:04X} press opposite d-pad directions", index, buttons),
         }
     }
+}
 
-    pub fn rgba16_to_rgba32(rgba16 : &[u8])->Vec<u8>{
-        return rgba16.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
+/// how `DemoButtonFile::read` treats `DemoIssue`s
+#[derive(Clone)]
+pub struct DemoValidation{
+    pub stick_limit: u8, //the stick physically reaches about 80 each way
+    pub strict: bool, //issues fail the read instead of printing warnings
+}
 
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+impl Default for DemoValidation{
+    fn default() -> DemoValidation{
+        DemoValidation{stick_limit: 80, strict: false}
+    }
+}
 
-                return [r32, g32, b32, a32]
+#[derive(PartialEq, Debug, Clone)]
+pub struct DemoSummary{
+    pub total_frames: usize,
+    pub inputs: usize,
+    pub presses: Vec<(&'static str, usize)>, //times each button goes down, in `BUTTONS` order
+}
+
+pub struct DemoButtonFile{
+    inputs: Vec<ContInput>,
+    reproduction: Reproduction,
+}
+
+impl DemoButtonFile{
+    /// a power on .m64 for controller 1 with one input record per demo frame
+    pub fn to_m64(&self, options: &M64Options) -> Vec<u8>{
+        let samples : u32 = self.inputs.iter().map(|i| i.frames as u32).sum();
+        let mut out = vec![0u8; M64_HEADER_LEN];
+        let mut put = |offset: usize, bytes: &[u8]| out[offset..offset + bytes.len()].copy_from_slice(bytes);
+        put(0x0, &M64_SIGNATURE);
+        put(0x4, &3u32.to_le_bytes());
+        put(0xC, &samples.to_le_bytes()); //vi count, one per input is close enough for playback
+        put(0x14, &[options.fps, 1]);
+        put(0x18, &samples.to_le_bytes());
+        put(0x1C, &2u16.to_le_bytes()); //start from power on
+        put(0x20, &1u32.to_le_bytes()); //controller 1 present
+        let text = |s: &str, max: usize| s.as_bytes()[..s.len().min(max - 1)].to_vec();
+        put(0xC4, &text(&options.rom_name, 0x20));
+        put(0x222, &text(&options.author, 0xDE));
+        put(0x300, &text(&options.description, 0x100));
+
+        for input in self.inputs.iter(){
+            let b = input.buttons.to_be_bytes();
+            for _ in 0..input.frames{
+                out.extend_from_slice(&[b[0], b[1], input.x as u8, input.y as u8]);
+            }
+        }
+        return out
+    }
+
+    /// converts `len` frames of controller 1 from `start_frame` on, identical frames become one input
+    pub fn from_m64(bytes: &[u8], start_frame: usize, len: usize) -> Result<DemoButtonFile, DemoError>{
+        if bytes.len() < M64_HEADER_LEN{
+            return Err(match bytes.starts_with(&M64_SIGNATURE){
+                true => DemoError::Truncated{needed: M64_HEADER_LEN, available: bytes.len()},
+                false => DemoError::NotM64,
             })
-            .flatten()
-            .collect()
+        }
+        if !bytes.starts_with(&M64_SIGNATURE){
+            return Err(DemoError::NotM64);
+        }
+        let u32_at = |o: usize| u32::from_le_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+        let version = u32_at(0x4);
+        if version != 3{
+            return Err(DemoError::UnsupportedVersion(version));
+        }
+        let controllers = bytes[0x15];
+        if controllers != 1{
+            return Err(DemoError::Controllers(controllers));
+        }
+        if u16::from_le_bytes([bytes[0x1C], bytes[0x1D]]) == 1{
+            return Err(DemoError::StartsFromSnapshot);
+        }
+        let samples = u32_at(0x18) as usize;
+        if start_frame + len > samples{
+            return Err(DemoError::FrameRange{start: start_frame, len: len, available: samples});
+        }
+        let needed = M64_HEADER_LEN + 4*(start_frame + len);
+        if bytes.len() < needed{
+            return Err(DemoError::Truncated{needed: needed, available: bytes.len()});
+        }
+
+        let mut frames : Vec<ContInput> = Vec::new();
+        for frame in start_frame..start_frame + len{
+            let o = M64_HEADER_LEN + 4*frame;
+            //the button bytes read big endian match the N64 mask
+            let buttons = u16::from_be_bytes([bytes[o], bytes[o + 1]]);
+            if buttons & M64_RESET_BITS != 0{
+                return Err(DemoError::Reset{frame: frame});
+            }
+            frames.push(ContInput{x: bytes[o + 2] as i8, y: bytes[o + 3] as i8, buttons: buttons, frames: 1, flag: 0});
+        }
+        return Ok(DemoButtonFile{inputs: DemoButtonFile::encode_runs(&frames), reproduction: Reproduction::Exact})
     }
 
-    pub fn ci4_to_rgba32(ci4 : &[u8], palatte: &[u8])->Vec<u8>{
-        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
+    pub fn validate(&self, stick_limit: u8) -> Vec<DemoIssue>{
+        let mut issues = Vec::new();
+        for (index, i) in self.inputs.iter().enumerate(){
+            if i.x.unsigned_abs() > stick_limit || i.y.unsigned_abs() > stick_limit{
+                issues.push(DemoIssue::StickRange{index: index, x: i.x, y: i.y, limit: stick_limit});
+            }
+            let both = |a: u16, b: u16| i.buttons & a != 0 && i.buttons & b != 0;
+            if both(0x0200, 0x0100) || both(0x0800, 0x0400){
+                issues.push(DemoIssue::OpposingDpad{index: index, buttons: i.buttons});
+            }
+        }
+        return issues
+    }
 
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+    pub fn total_frames(&self) -> usize{
+        return self.inputs.iter().map(|i| i.frames as usize).sum()
+    }
 
-                [r32, g32, b32, a32]
-            })
-            .collect();
+    pub fn duration_seconds(&self, fps: f32) -> f32{
+        return self.total_frames() as f32 / fps
+    }
 
-        return ci4
-            .into_iter()
-            .map(|a|{[a >> 4, a & 0xF]}) //cvt to ci8
-            .flatten()
-            .map(|indx|{pal[indx as usize]})
-            .flatten()
-            .collect()
+    /// `(x, y, buttons)` for every frame, runs unrolled
+    pub fn expanded_frames(&self) -> impl Iterator<Item = (i8, i8, u16)> + '_{
+        return self.inputs.iter().flat_map(|i| std::iter::repeat_n((i.x, i.y, i.buttons), i.frames as usize))
     }
-    pub fn ci8_to_rgba32(ci8 : &[u8], palatte: &[u8])->Vec<u8>{
-        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
-            .map(|a|{
-                let val = u16::from_be_bytes([a[0], a[1]]);
-                let r16 = ((val >> 11) & 0x1f) as u8;
-                let g16 = ((val >> 6) & 0x1f) as u8;
-                let b16 = ((val >> 1) & 0x1f) as u8;
-                let a16 = (val & 0x1) as u8;
 
-                let r32 = (r16 << 3) | (r16 >> 3);
-                let g32 = (g16 << 3) | (g16 >> 3);
-                let b32 = (b16 << 3) | (b16 >> 3);
-                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+    /// `other` played after this demo, the inputs meeting at the seam merge if they are the same
+    pub fn concat(&self, other: &DemoButtonFile) -> DemoButtonFile{
+        let frames : Vec<ContInput> = self.frame_inputs().chain(other.frame_inputs()).collect();
+        return DemoButtonFile{inputs: DemoButtonFile::encode_runs(&frames), reproduction: self.reproduction}
+    }
 
-                [r32, g32, b32, a32]
-            })
-            .collect();
+    /// replaces the frames in `range` with `replacement`, whose inputs last their own `frames` each
+    pub fn splice(&mut self, range: Range<usize>, replacement: &[ContInput]) -> Result<(), DemoError>{
+        let total = self.total_frames();
+        if range.start > range.end || range.end > total{
+            return Err(DemoError::FrameRange{start: range.start, len: range.end.saturating_sub(range.start), available: total});
+        }
+        let mut frames : Vec<ContInput> = self.frame_inputs().collect();
+        let new_frames = replacement.iter().flat_map(|i| std::iter::repeat_n(ContInput{frames: 1, ..*i}, i.frames as usize));
+        frames.splice(range, new_frames);
+        self.inputs = DemoButtonFile::encode_runs(&frames);
+        return Ok(())
+    }
 
-        return ci8
-            .iter()
-            .map(|indx|{pal[*indx as usize]})
-            .flatten()
-            .collect()
+    //one single frame input per frame, the flag byte stays with every frame of its run
+    fn frame_inputs(&self) -> impl Iterator<Item = ContInput> + '_{
+        return self.inputs.iter().flat_map(|i| std::iter::repeat_n(ContInput{frames: 1, ..*i}, i.frames as usize))
     }
 
-    pub fn i4_to_rgba32(i_4 : &[u8])->Vec<u8>{
-        return i_4.into_iter()
-            .map(|a|{
-                let val1 = (a & 0xF0) | (a >> 4);
-                let val2 = (a << 4) | (a & 0xF);
-                [val1, val1, val1, 0xFF, val2, val2, val2, 0xFF]
-            })
-            .flatten()
-            .collect()
+    //merges identical consecutive single frame inputs, runs are capped at 255 frames
+    fn encode_runs(frames: &[ContInput]) -> Vec<ContInput>{
+        let mut out : Vec<ContInput> = Vec::new();
+        for f in frames.iter(){
+            match out.last_mut(){
+                Some(last) if ContInput{frames: 1, ..*last} == *f && last.frames < 0xFF => last.frames += 1,
+                _ => out.push(*f),
+            }
+        }
+        return out
     }
 
-    pub fn i8_to_rgba32(i_8 : &[u8])->Vec<u8>{
-        return i_8.iter()
-            .map(|a|{
-                let val = *a;
-                [val, val, val, 0xFF]
-            })
-            .flatten()
-            .collect()
+    pub fn summary(&self) -> DemoSummary{
+        let mut presses : Vec<(&'static str, usize)> = BUTTONS.iter().map(|(_, name)| (*name, 0)).collect();
+        let mut held : u16 = 0;
+        for (_, _, buttons) in self.expanded_frames(){
+            for (i, (bit, _)) in BUTTONS.iter().enumerate(){
+                if buttons & bit != 0 && held & bit == 0{
+                    presses[i].1 += 1;
+                }
+            }
+            held = buttons;
+        }
+        return DemoSummary{total_frames: self.total_frames(), inputs: self.inputs.len(), presses: presses}
     }
 
-    pub fn ia4_to_rgba32(ia4 : &[u8])->Vec<u8>{
-        return ia4
-            .into_iter()
+    /// the length word has to match the 6 byte inputs that follow it exactly
+    pub fn parse(in_bytes: &[u8])->Result<DemoButtonFile, DemoError>{
+        if in_bytes.is_empty() { return Ok(DemoButtonFile{inputs: Vec::new(), reproduction: Reproduction::Exact}) }
+        if in_bytes.len() < 4{
+            return Err(DemoError::Truncated{needed: 4, available: in_bytes.len()});
+        }
+        let declared : usize =  u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
+        let payload = in_bytes.len() - 4;
+        if !declared.is_multiple_of(6) || declared != payload{
+            return Err(DemoError::Length{declared: declared, payload: payload});
+        }
+        let inputs : Vec<ContInput> = in_bytes[4..].chunks_exact(6)
             .map(|a|{
-                let i1 = (a & 0xE0) | (a >> 3) | (a >> 6);
-                let a1 = (((a << 3) as i8) >> 7) as u8;
-                let i2 = (a >> 1) & 0x7;
-                let i2 = (i2 << 5) | (i2 << 2) | (i2 >> 1);
-                let a2 = (((a << 7) as i8) >> 7) as u8;
-                [i1, i1, i1, a1, i2, i2, i2, a2]
+                ContInput{
+                    x : a[0] as i8, 
+                    y : a[1] as i8,
+                    buttons : u16::from_be_bytes([a[2], a[3]]),
+                    frames : a[4],
+                    flag : a[5],
+                }
             })
-            .flatten()
-            .collect()
+            .collect();
+        return Ok(DemoButtonFile{inputs: inputs, reproduction: Reproduction::Exact})
     }
 
-    pub fn ia8_to_rgba32(ia8 : &[u8])->Vec<u8>{
-        return ia8
-            .iter()
-            .map(|a|{
-                let val = (*a & 0xF0) | (*a >> 4);
-                let alpha = (*a << 4) | (*a & 0xF);
-                [val, val, val, alpha]
-            })
-            .flatten()
-            .collect()
+    pub fn read(path: &Path) -> Result<DemoButtonFile, ReadError>{
+        return DemoButtonFile::read_with(path, &DemoValidation::default())
     }
-}
 
-struct SpriteChunk {
-    x : isize,
-    y : isize,
-    w : usize,
-    h : usize,
-    pub pixel_data : Vec<u8>,
+    pub fn read_with(path: &Path, validation: &DemoValidation) -> Result<DemoButtonFile, ReadError>{
+        let doc = load_yaml(path, "DemoInput")?;
+        let field_err = |section: &'static str, index: Option<usize>, reason: String| ReadError::Field{file: path.display().to_string(), section: section, index: index, reason: reason};
+        
+        let inputs_yaml = doc["inputs"].as_vec().ok_or_else(|| field_err("inputs", None, String::from("missing or not a list")))?;
+        let mut inputs : Vec<ContInput> = inputs_yaml.iter().enumerate().map(|(i, y)|{
+            ContInput::from_yaml(y).map_err(|reason| field_err("inputs", Some(i), reason))
+        })
+        .collect::<Result<Vec<ContInput>, ReadError>>()?;
+
+        //older yamls keep the first input's flag at the top level
+        match (&doc["flag"], inputs.first_mut()){
+            (Yaml::BadValue, _) => {},
+            (Yaml::Integer(f), Some(first)) if inputs_yaml[0]["flag"].is_badvalue() => {
+                first.flag = u8::try_from(*f).map_err(|_| field_err("flag", None, format!("flag {} is out of range for u8", f)))?;
+            },
+            (Yaml::Integer(_), _) => {},
+            _ => return Err(field_err("flag", None, String::from("not an integer"))),
+        }
+        let out = DemoButtonFile{inputs:inputs, reproduction: read_reproduction(&doc, path)?};
+        for issue in out.validate(validation.stick_limit){
+            match validation.strict{
+                true => return Err(field_err("inputs", None, issue.to_string())),
+                false => eprintln!("warning: {}: {}", path.display(), issue),
+            }
+        }
+        return Ok(out)
+    }
 }
 
-impl SpriteChunk {
-    pub fn new(bin : &[u8], file_offset : &mut usize, format : &ImgFmt)->SpriteChunk{
-        let chunk_bin = &bin[*file_offset..];
-        let x = i16::from_be_bytes([chunk_bin[0], chunk_bin[1]]) as isize;
-        let y = i16::from_be_bytes([chunk_bin[2], chunk_bin[3]]) as isize;
-        let w = u16::from_be_bytes([chunk_bin[4], chunk_bin[5]]) as usize;
-        let h = u16::from_be_bytes([chunk_bin[6], chunk_bin[7]]) as usize;
-        // println!("\t\t{:02X?}", &chunk_bin[..8]);
-        *file_offset += 8;
-        *file_offset = (*file_offset + (8 - 1)) & !(8 - 1); //align
-        let pxl_size : usize = match format{
-            ImgFmt::I4 | ImgFmt::IA4 | ImgFmt::CI4 => 4,
-            ImgFmt::I8 | ImgFmt::IA8 | ImgFmt::CI8 => 8,
-            ImgFmt::RGBA16 => 16,
-            ImgFmt::RGBA32 => 32,
-            _=> 0,
-        };
-        let data_size : usize = w*h*pxl_size/8;
+impl Asset for DemoButtonFile{
+    fn to_bytes(&self)->Vec<u8>{
+        if self.inputs.is_empty() { return Vec::new(); }
 
-        let data : Vec<u8> = bin[*file_offset .. *file_offset + data_size].to_vec();
-        *file_offset += data_size;
+        let mut output : Vec<u8> = (6*self.inputs.len() as u32).to_be_bytes().to_vec();
+        let mut input_bytes : Vec<u8> = self.inputs.iter().flat_map(|i|{
+            i.to_bytes()
+        })
+        .collect();
+        output.append(&mut input_bytes);
+        return output;
+    }
 
-        SpriteChunk{
-            x : x, 
-            y : y, 
-            w : w, 
-            h : h,
-            pixel_data : data, 
+    fn get_type(&self)->AssetType{
+        return AssetType::DemoInput;
+    }
+
+    fn write(&self, path: &Path){
+        let mut demo_file = File::create(path).unwrap();
+        if TextFormat::from_path(path) == TextFormat::Json{
+            let inputs : Vec<String> = self.inputs.iter().map(|i| format!("    {}", i.to_json())).collect();
+            writeln!(demo_file, "{{").unwrap();
+            writeln!(demo_file, "  \"type\": \"DemoInput\",").unwrap();
+            writeln!(demo_file, "  \"reproduction\": \"{}\",", self.reproduction).unwrap();
+            match inputs.is_empty(){
+                true => writeln!(demo_file, "  \"inputs\": []").unwrap(),
+                false => writeln!(demo_file, "  \"inputs\": [\n{}\n  ]", inputs.join(",\n")).unwrap(),
+            }
+            writeln!(demo_file, "}}").unwrap();
+            return;
+        }
+        writeln!(demo_file, "type: DemoInput").unwrap();
+        writeln!(demo_file, "reproduction: {}", self.reproduction).unwrap();
+        if self.inputs.is_empty(){
+            writeln!(demo_file, "inputs: []").unwrap();
+            return;
+        }
+        writeln!(demo_file, "inputs:").unwrap();
+        for input in self.inputs.iter(){
+            writeln!(demo_file, "  - {}", input.to_yaml()).unwrap();
         }
     }
 }
 
-pub struct SpriteFrame {
-    w : usize,
-    h : usize,
-    pub header: Vec<u8>,
-    pub chk_hdrs: Vec<Vec<u8>>,
-    palette : Option<Vec<u8>>,
-    pixel_data : Vec<u8>,
+pub struct MidiSeqFile{
+    bytes: Vec<u8>,
+    header: Option<midi::SeqHeader>, //None when the header does not parse, the file stays opaque
 }
 
-impl SpriteFrame {
-    pub fn new(bin : &[u8], file_offset : usize, format : &ImgFmt)->SpriteFrame{
-        let header = bin[file_offset..file_offset+0x14].to_vec();
-        // println!("\t{:02X?}", &header);
-        let frame_bin = &bin[file_offset..];
-        let x = i16::from_be_bytes([frame_bin[0], frame_bin[1]]) as isize;
-        let y = i16::from_be_bytes([frame_bin[2], frame_bin[3]]) as isize;
-        let w = u16::from_be_bytes([frame_bin[4], frame_bin[5]]) as usize;
-        let h = u16::from_be_bytes([frame_bin[6], frame_bin[7]]) as usize;
-        let mut pxl_data : Vec<Vec<[u8;4]>> = vec![vec![[0; 4]; w]; h];
-        
-        let chunk_cnt = u16::from_be_bytes([frame_bin[8], frame_bin[9]]);
-        let mut palette :Vec<u8> = Vec::new();
+impl MidiSeqFile{
+    /// files that don't validate stay as opaque bytes, they still write back unchanged
+    pub fn from_bytes(in_bytes: &[u8])->MidiSeqFile{
+        match MidiSeqFile::parse(in_bytes){
+            Ok(seq) => seq,
+            Err(e) => {
+                eprintln!("warning: music sequence kept as raw bytes: {}", e);
+                MidiSeqFile{bytes: in_bytes.to_vec(), header: None}
+            },
+        }
+    }
 
-        let mut offset = file_offset + 0x14;
-        let mut chunks : Vec<SpriteChunk> = Vec::new();
-        let mut chk_hdrs : Vec<Vec<u8>> = Vec::new();
+    pub fn parse(in_bytes: &[u8]) -> Result<MidiSeqFile, midi::MidiError>{
+        let header = midi::SeqHeader::parse(in_bytes)?;
+        midi::validate(in_bytes, &header)?;
+        return Ok(MidiSeqFile{bytes: in_bytes.to_vec(), header: Some(header)})
+    }
 
-        match format {
-            ImgFmt::CI4 => {
-                //align with file
-                offset = (offset + (8 - 1)) & !(8 - 1) ; //align to 0x8
-                palette  = bin[offset.. offset + 0x20].to_vec();
-                offset += 0x20;
-                
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }                
-            }
-            ImgFmt::CI8 => {
-                //align with file
-                offset = (offset + (8 - 1)) & !(8 - 1) ; //align to 0x8
-                palette  = bin[offset.. offset + 0x200].to_vec();
-                offset += 0x200;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::I4 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::I8 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::RGBA32 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            ImgFmt::RGBA16 => {
-                offset = offset;
-                let mut i = 0;
-                while i < chunk_cnt{
-                    chk_hdrs.push(bin[offset.. offset + 8].to_vec());
-                    chunks.push(SpriteChunk::new(bin, &mut offset, format));
-                    i += 1;
-                }
-            }
-            _ => {}
+    /// reads the `.midi.yaml` written next to the bin, any other path is read as the raw sequence
+    pub fn read(path: &Path) -> Result<MidiSeqFile, ReadError>{
+        if path.extension().and_then(|e| e.to_str()) != Some("yaml"){
+            let bytes = fs::read(path).map_err(|e| ReadError::Io{file: path.display().to_string(), reason: e.to_string()})?;
+            return Ok(MidiSeqFile::from_bytes(&bytes))
         }
+        let doc = load_yaml(path, "Midi")?;
+        let bytes = midi::from_yaml(&doc).map_err(|reason| ReadError::Field{file: path.display().to_string(), section: "tracks", index: None, reason: reason})?;
+        return Ok(MidiSeqFile::from_bytes(&bytes))
+    }
 
-        for chnk in chunks{
-            let raw_data = match format {
-                ImgFmt::CI4    => Texture::ci4_to_rgba32(&chnk.pixel_data, &palette),
-                ImgFmt::CI8    => Texture::ci8_to_rgba32(&chnk.pixel_data, &palette),
-                ImgFmt::I4     => Texture::i4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::I8     => Texture::i4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::RGBA16 => Texture::rgba16_to_rgba32(&chnk.pixel_data),
-                ImgFmt::RGBA32 => chnk.pixel_data,
-                ImgFmt::IA4    => Texture::ia4_to_rgba32(&chnk.pixel_data),
-                ImgFmt::IA8    => Texture::ia4_to_rgba32(&chnk.pixel_data),
-                _=> Vec::new(),
-            };
+    /// the event listing written next to the bin, None when the sequence is opaque or the
+    /// listing would not rebuild the exact bytes
+    pub fn to_yaml(&self) -> Option<String>{
+        let text = midi::to_yaml(&self.bytes, self.header.as_ref()?).ok()?;
+        let doc = YamlLoader::load_from_str(&text).ok()?.into_iter().next()?;
+        match midi::from_yaml(&doc){
+            Ok(bytes) if bytes == self.bytes => Some(String::from("type: Midi\n") + &text),
+            _ => None,
+        }
+    }
 
-            if(chunk_cnt) == 1{
-                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
+    pub fn header(&self) -> Option<&midi::SeqHeader>{
+        return self.header.as_ref()
+    }
 
-                for (j,row) in row_data.iter().enumerate(){
-                    for (i, pxl) in row.chunks_exact(4).enumerate(){
-                        let fx :isize = i as isize;
-                        let fy :isize = j as isize;
-                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
-                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
-                        }
-                    }
-                }
-            }
-            else{
-                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
-                for (j,row) in row_data.iter().enumerate(){
-                    for (i, pxl) in row.chunks_exact(4).enumerate(){
-                        let fx :isize = (chnk.x + i as isize) as isize;
-                        let fy :isize = (chnk.y + j as isize) as isize;
-                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
-                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
-                        }
-                    }
-                }
-            }
-        }
+    pub fn division(&self) -> Option<u32>{
+        return self.header.as_ref().map(|h| h.division)
+    }
 
-        let pal = match format{
-            ImgFmt::CI4 | ImgFmt::CI8 => Some(palette),
-            _ => None,
-        };
+    /// the compressed event data of one of the 16 tracks
+    pub fn track(&self, track: usize) -> Option<&[u8]>{
+        let range = self.header.as_ref()?.track_range(track, self.bytes.len())?;
+        return Some(&self.bytes[range])
+    }
+
+    /// start and end tick of the loop
+    pub fn loop_points(&self) -> Option<(u32, u32)>{
+        return midi::loop_points(&self.bytes, self.header.as_ref()?)
+    }
 
-        SpriteFrame{w: w as usize,h: h as usize, header: header, chk_hdrs:chk_hdrs, palette : pal, pixel_data: pxl_data.into_iter().flatten().flatten().collect()}
+    pub fn set_loop_points(&mut self, start: u32, end: u32) -> Result<(), midi::MidiError>{
+        let header = self.header.as_ref().ok_or(midi::MidiError::NoHeader)?;
+        let bytes = midi::set_loop_points(&self.bytes, header, start, end)?;
+        *self = MidiSeqFile::from_bytes(&bytes);
+        return Ok(())
+    }
+
+    /// microseconds per quarter note at the start of the sequence
+    pub fn tempo(&self) -> Option<u32>{
+        return midi::tempo(&self.bytes, self.header.as_ref()?)
+    }
+
+    pub fn set_tempo(&mut self, tempo_us: u32) -> Result<(), midi::MidiError>{
+        let header = self.header.as_ref().ok_or(midi::MidiError::NoHeader)?;
+        let bytes = midi::set_tempo(&self.bytes, header, tempo_us)?;
+        *self = MidiSeqFile::from_bytes(&bytes);
+        return Ok(())
+    }
+
+    /// programs and banks the tracks switch to, for checking which instruments a song needs
+    pub fn instruments(&self) -> Result<Vec<midi::InstrumentUse>, midi::MidiError>{
+        let header = self.header.as_ref().ok_or(midi::MidiError::NoHeader)?;
+        return midi::instruments(&self.bytes, header)
+    }
+
+    /// the sequence as an SMF type 1 file
+    pub fn to_standard_midi(&self) -> Result<Vec<u8>, midi::MidiError>{
+        let header = self.header.as_ref().ok_or(midi::MidiError::NoHeader)?;
+        return midi::to_standard_midi(&self.bytes, header)
+    }
+
+    pub fn write_with(&self, path: &Path, options: &MidiWriteOptions){
+        let mut bin_file = File::create(path).unwrap();
+        bin_file.write_all(&self.bytes).unwrap();
+        match self.to_yaml(){
+            Some(text) => fs::write(path.with_extension("yaml"), text).unwrap(),
+            None if self.header.is_some() => eprintln!("warning: {} has no event listing, it does not rebuild byte for byte", path.display()),
+            None => (),
+        }
+        if !options.standard_midi{
+            return;
+        }
+        //the .bin stays the source of truth, a sequence that can't be converted only gets a warning
+        match self.to_standard_midi(){
+            Ok(smf) => fs::write(path.with_extension("mid"), smf).unwrap(),
+            Err(e) => eprintln!("warning: {} not converted to .mid: {}", path.display(), e),
+        }
     }
 }
 
-pub struct Sprite{
-    format: ImgFmt,
-    pub frame: Vec<SpriteFrame>,
-    bytes: Vec<u8>,
+#[derive(Default)]
+pub struct MidiWriteOptions{
+    pub standard_midi: bool, //also write a .mid next to the .bin
 }
 
-impl Sprite{
-    pub fn from_bytes(in_bytes: &[u8])->Sprite{
-        let frame_cnt = u16::from_be_bytes([in_bytes[0], in_bytes[1]]);
-        let format = u16::from_be_bytes([in_bytes[2], in_bytes[3]]);
-        let frmt = match format{
-            0x0001 => ImgFmt::CI4,
-            0x0004 => ImgFmt::CI8,
-            0x0020 => ImgFmt::I4,
-            0x0040 => ImgFmt::I8,
-            0x0400 => ImgFmt::RGBA16,
-            0x0800 => ImgFmt::RGBA32,
-            _ => ImgFmt::Unknown(format),
-        };
-        match frmt {
-            ImgFmt::Unknown(_) => {return Sprite{format: frmt, frame: Vec::new(), bytes: in_bytes.to_vec()}},
-            _=> {}
-        }
+impl MidiWriteOptions{
+    pub fn standard_midi(mut self, enable: bool) -> MidiWriteOptions{
+        self.standard_midi = enable;
+        return self
+    }
+}
 
-        if frame_cnt > 0x100{
-            let mut offset = 8 as usize;
-            let chunk = SpriteChunk::new(in_bytes, &mut offset, &ImgFmt::RGBA16);
-            let frame = SpriteFrame{w:chunk.w, h:chunk.h, header: Vec::new(), chk_hdrs: vec![in_bytes[8..16].to_vec()], palette: None, pixel_data: Texture::rgba16_to_rgba32(&chunk.pixel_data)};
-            return Sprite{format: frmt, frame: vec![frame], bytes: in_bytes.to_vec()};
-        }
-        // println!("{:02X?}", &in_bytes[..0x10]);
-        let frames : Vec<SpriteFrame>= in_bytes[0x10..]
-                .chunks_exact(0x4)
-                .take(frame_cnt as usize)
-                .map(|a|{
-                    let offset = u32::from_be_bytes(a.try_into().unwrap());
-                    SpriteFrame::new(in_bytes, 0x10 + offset as usize + 4*frame_cnt as usize, &frmt)
-                })
-                .collect(); 
-        return Sprite{format: frmt, frame: frames, bytes: in_bytes.to_vec()};
+impl Asset for MidiSeqFile{
+    fn to_bytes(&self)->Vec<u8>{
+        return self.bytes.clone();
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Midi;
     }
 
-    pub fn read(path: &Path) -> Sprite{
-        Sprite{format: ImgFmt::Unknown(0), frame: Vec::new(), bytes: fs::read(path).unwrap()}
+    fn write(&self, path: &Path){
+        self.write_with(path, &MidiWriteOptions::default());
     }
 }
 
-/// Sprite TODO !!!!!!!!!
+/// LevelSetup TODO !!!!!!!!!
 ///     - struct members
+///     - from_bytes
 ///     - read
 ///     - to_bytes
+///     - write
+pub struct LevelSetup{
+    bytes: Vec<u8>,
+    reproduction: Reproduction,
+}
 
-impl Asset for Sprite{
+impl LevelSetup{
+    pub fn from_bytes(in_bytes: &[u8])->LevelSetup{
+        LevelSetup{bytes: in_bytes.to_vec(), reproduction: Reproduction::Exact}
+    }
+
+    /// reads the bin, or the .lvl_setup.yaml next to it which records the size the bin was extracted with.
+    /// in exact mode a bin edited to another size is refused
+    pub fn read(path: &Path) -> Result<LevelSetup, ReadError>{
+        let file = path.display().to_string();
+        let read_bin = |bin: &Path| fs::read(bin).map_err(|e| ReadError::Io{file: bin.display().to_string(), reason: e.to_string()});
+        if path.extension().and_then(|e| e.to_str()) != Some("yaml"){
+            return Ok(LevelSetup::from_bytes(&read_bin(path)?))
+        }
+        let doc = load_yaml(path, "LevelSetup")?;
+        let field_err = |section: &'static str, reason: String| ReadError::Field{file: file.clone(), section: section, index: None, reason: reason};
+        let reproduction = read_reproduction(&doc, path)?;
+        let bin_name = doc["bin"].as_str().ok_or(field_err("bin", String::from("missing")))?;
+        let bytes = read_bin(&path.with_file_name(bin_name))?;
+        let size = doc["size"].as_i64().ok_or(field_err("size", String::from("missing")))? as usize;
+        reproduction.require_exact(bytes.len() == size, &format!("{} resized from 0x{:X} to 0x{:X} bytes", bin_name, size, bytes.len()))
+            .map_err(|e| field_err("size", e.to_string()))?;
+        return Ok(LevelSetup{bytes: bytes, reproduction: reproduction})
+    }
+}
+
+impl Asset for LevelSetup{
     fn to_bytes(&self)->Vec<u8>{
         return self.bytes.clone();
     }
 
     fn get_type(&self)->AssetType{
-        return AssetType::Sprite(self.format);
+        return AssetType::LevelSetup;
     }
 
     fn write(&self, path: &Path){
-        //write bin. TODO remove once one to 1 conversion
         let mut bin_file = File::create(path).unwrap();
         bin_file.write_all(&self.bytes).unwrap();
+        let bin_name = path.file_name().unwrap().to_str().unwrap();
+        let mut yaml_file = File::create(path.with_extension("yaml")).unwrap();
+        writeln!(yaml_file, "type: LevelSetup").unwrap();
+        writeln!(yaml_file, "reproduction: {}", self.reproduction).unwrap();
+        writeln!(yaml_file, "bin: {:?}", bin_name).unwrap();
+        writeln!(yaml_file, "size: 0x{:X}", self.bytes.len()).unwrap();
+    }
+}
 
-        //write descriptor yaml and folder containing frame pngs
-        let base_name = Path::new(path.file_stem().unwrap());
-        let fmt_str = base_name.extension().unwrap();
-        let new_base = Path::new(base_name.file_stem().unwrap());
-        let base_name = Path::new(new_base.file_stem().unwrap());
-        let base_path = path.parent().unwrap().join(base_name);
-        let mut desc_path = base_path.clone();
-        desc_path.set_extension("sprite.yaml");
-        let mut desc_f = File::create(desc_path).unwrap();
-        writeln!(desc_f, "type: Sprite").unwrap();
-        writeln!(desc_f, "format: {:?}", self.format).unwrap();
-        writeln!(desc_f, "frames:").unwrap();
-        
-        DirBuilder::new().recursive(true).create(&base_path.clone()).unwrap();
-        for(i, frame) in self.frame.iter().enumerate(){
-            let mut i_path = base_path.join(format!("{:02X}.", i));
-            i_path.set_extension(format!("{}.png",fmt_str.to_str().unwrap()));
-            writeln!(desc_f, "  - {:?}", i_path).unwrap();
-            let texture_f = File::create(i_path).unwrap();
-            let ref mut w = BufWriter::new(texture_f);
+pub struct Animation{
+    bytes: Vec<u8>,
+    file: Option<anim::AnimFile>, //None when the file does not parse, it stays opaque
+}
+
+impl Animation{
+    pub fn from_bytes(in_bytes: &[u8])->Animation{
+        match anim::AnimFile::from_bytes(in_bytes){
+            Ok(file) => Animation{bytes: in_bytes.to_vec(), file: Some(file)},
+            Err(e) => {
+                eprintln!("warning: animation kept as raw bytes: {}", e);
+                Animation{bytes: in_bytes.to_vec(), file: None}
+            },
+        }
+    }
+
+    /// reads the yaml `write` puts out, `.bin` paths from older extractions are read as raw bytes
+    pub fn read(path: &Path) -> Result<Animation, ReadError>{
+        let file_err = |e: String| ReadError::Io{file: path.display().to_string(), reason: e};
+        if path.extension().and_then(|e| e.to_str()) == Some("bin"){
+            return Ok(Animation::from_bytes(&fs::read(path).map_err(|e| file_err(e.to_string()))?))
+        }
+        let doc = load_yaml(path, "Animation")?;
+        if let Yaml::Array(raw) = &doc["raw"]{
+            let bytes = raw.iter().map(|b| b.as_i64().filter(|b| (0..=0xFF).contains(b)).map(|b| b as u8)).collect::<Option<Vec<u8>>>()
+                .ok_or(ReadError::Field{file: path.display().to_string(), section: "raw", index: None, reason: String::from("not a list of bytes")})?;
+            return Ok(Animation{bytes: bytes, file: None})
+        }
+        let file = anim::AnimFile::from_yaml(&doc).map_err(|reason| ReadError::Field{file: path.display().to_string(), section: "channels", index: None, reason: reason})?;
+        return Ok(Animation{bytes: file.to_bytes(), file: Some(file)})
+    }
+
+    pub fn file(&self) -> Option<&anim::AnimFile>{
+        return self.file.as_ref()
+    }
+
+    pub fn frame_count(&self) -> Option<u16>{
+        return self.file.as_ref().map(|f| f.frame_count())
+    }
 
-            let mut encoder = png::Encoder::new(w, frame.w as u32, frame.h as u32);
-            encoder.set_color(png::ColorType::Rgba);
-            encoder.set_depth(png::BitDepth::Eight);
-            let mut writer = encoder.write_header().unwrap();
+    pub fn bone_count(&self) -> Option<usize>{
+        return self.file.as_ref().map(|f| f.bone_count())
+    }
+
+    pub fn validate(&self, bone_count: usize) -> Result<Vec<anim::AnimIssue>, anim::AnimError>{
+        return Ok(self.file.as_ref().ok_or(anim::AnimError::NotParsed)?.validate(bone_count))
+    }
+
+    /// None for animations kept as raw bytes
+    pub fn stats(&self) -> Option<anim::AnimStats>{
+        return self.file.as_ref().map(|f| f.stats())
+    }
+
+    pub fn scale_time(&mut self, factor: f32) -> Result<(), anim::AnimError>{
+        return self.file.as_mut().ok_or(anim::AnimError::NotParsed)?.scale_time(factor)
+    }
+
+    pub fn trim(&mut self, start_frame: u16, end_frame: u16) -> Result<(), anim::AnimError>{
+        return self.file.as_mut().ok_or(anim::AnimError::NotParsed)?.trim(start_frame, end_frame)
+    }
+
+    pub fn to_gltf_channels(&self, skeleton: &anim::ModelSkeleton) -> Result<Vec<anim::GltfChannel>, anim::AnimError>{
+        return self.file.as_ref().ok_or(anim::AnimError::NotParsed)?.to_gltf_channels(skeleton)
+    }
+}
+
+impl Asset for Animation{
+    fn to_bytes(&self)->Vec<u8>{
+        return match &self.file{
+            Some(file) => file.to_bytes(),
+            None => self.bytes.clone(),
+        }
+    }
 
-            let data = &frame.pixel_data;
-            // let mirrored : Vec<u8> = data.rchunks_exact(4*frame.w).map(|a|{a.to_vec()}).flatten().collect();
+    fn get_type(&self)->AssetType{
+        return AssetType::Animation;
+    }
 
-            writer.write_image_data(&data).unwrap(); // Save
+    fn write(&self, path: &Path){
+        let mut anim_file = File::create(path).unwrap();
+        writeln!(anim_file, "type: Animation").unwrap();
+        match &self.file{
+            Some(file) => write!(anim_file, "{}", file.to_yaml()).unwrap(),
+            //files the parser can't follow keep their bytes
+            None => writeln!(anim_file, "raw: {}", hex_list(&self.bytes)).unwrap(),
         }
     }
 }
+
+/// Model TODO !!!!!!!!!
+///     - sections past the header
+///     - write
+pub struct Model{
+    bytes: Vec<u8>,
+    header: Option<model::ModelHeader>, //None when the header does not parse, the file stays opaque
+    meshes: Vec<model::MeshInfo>,
+}
+
+impl Model{
+    /// files that don't parse stay as opaque bytes, they still write back unchanged
+    pub fn from_bytes(in_bytes: &[u8])->Model{
+        match Model::parse(in_bytes){
+            Ok(model) => model,
+            Err(e) => {
+                eprintln!("warning: model kept as raw bytes: {}", e);
+                Model{bytes: in_bytes.to_vec(), header: None, meshes: Vec::new()}
+            },
+        }
+    }
+
+    pub fn parse(in_bytes: &[u8]) -> Result<Model, model::ModelError>{
+        let header = model::ModelHeader::parse(in_bytes)?;
+        //geometry still exports without the mesh list, it only groups it
+        let meshes = header.meshes(in_bytes).unwrap_or_else(|e|{
+            eprintln!("warning: model mesh list skipped: {}", e);
+            Vec::new()
+        });
+        return Ok(Model{bytes: in_bytes.to_vec(), header: Some(header), meshes: meshes})
+    }
+
+    /// reads a .model.bin, or the .model.yaml descriptor after checking it still describes the bin it names
+    pub fn read(path: &Path) -> Result<Model, ReadError>{
+        let file = path.display().to_string();
+        let read_bin = |bin: &Path| fs::read(bin).map_err(|e| ReadError::Io{file: bin.display().to_string(), reason: e.to_string()});
+        if path.extension().and_then(|e| e.to_str()) != Some("yaml"){
+            return Ok(Model::from_bytes(&read_bin(path)?))
+        }
+        let doc = load_yaml(path, "Model")?;
+        let bin_name = doc["bin"].as_str().ok_or(ReadError::Field{file: file.clone(), section: "bin", index: None, reason: String::from("missing")})?;
+        let model = Model::from_bytes(&read_bin(&path.with_file_name(bin_name))?);
+        let expected = model.descriptor(bin_name, &[]).and_then(|text| YamlLoader::load_from_str(&text).ok()?.into_iter().next());
+        let expected = expected.ok_or(ReadError::Field{file: file.clone(), section: "bin", index: None, reason: format!("{} is not a model that parses", bin_name)})?;
+        match yaml_mismatch(&expected, &doc, ""){
+            Some(reason) => Err(ReadError::Field{file: file, section: "descriptor", index: None, reason: format!("{}, it no longer matches {}", reason, bin_name)}),
+            None => Ok(model),
+        }
+    }
+
+    /// the .model.yaml text: header fields, where each section sits, the texture list, the mesh
+    /// list and counts. `texture_files` are the png names by texture index when they were written
+    pub fn descriptor(&self, bin_name: &str, texture_files: &[Option<String>]) -> Option<String>{
+        let h = self.header.as_ref()?;
+        let mut out = format!("type: Model\nbin: {:?}\n", bin_name);
+        out += &format!("header: {{geo_layout: 0x{:X}, texture_list: 0x{:X}, geo_type: 0x{:X}, gfx_list: 0x{:X}, vtx_list: 0x{:X}, unk14: 0x{:X}, bone_list: 0x{:X}, collision_list: 0x{:X}, mesh_list: 0x{:X}, effects_setup: 0x{:X}, unk28: 0x{:X}, anim_texture_list: 0x{:X}, tri_count: {}, vtx_count: {}, unk34: 0x{:X}}}\n",
+            h.geo_layout, h.texture_list, h.geo_type, h.gfx_list, h.vtx_list, h.unk14, h.bone_list, h.collision_list, h.mesh_list, h.effects_setup, h.unk28, h.anim_texture_list, h.tri_count, h.vtx_count, h.unk34);
+        let spans = model::section_spans(h, self.bytes.len());
+        out += if spans.is_empty() { "sections: []\n" } else { "sections:\n" };
+        for (name, offset, size) in spans{
+            out += &format!("  - {{name: {}, offset: 0x{:X}, size: 0x{:X}}}\n", name, offset, size);
+        }
+        let entries = self.texture_entries();
+        out += if entries.is_empty() { "textures: []\n" } else { "textures:\n" };
+        for (i, e) in entries.iter().enumerate(){
+            out += &format!("  - {{format: {}, w: {}, h: {}, offset: 0x{:X}", e.format, e.width, e.height, e.offset);
+            if let Some(Some(file)) = texture_files.get(i){
+                out += &format!(", file: {:?}", file);
+            }
+            out += "}\n";
+        }
+        out += if self.meshes.is_empty() { "meshes: []\n" } else { "meshes:\n" };
+        for mesh in self.meshes.iter(){
+            let ranges : Vec<String> = mesh.ranges().iter().map(|r| format!("[{}, {}]", r.start, r.end)).collect();
+            out += &format!("  - {{id: {}, vertices: {}, ranges: [{}]}}\n", mesh.id, mesh.vertices.len(), ranges.join(", "));
+        }
+        let stats = self.stats().unwrap_or_default();
+        out += &format!("stats: {{vertices: {}, triangles: {}, textures: {}, texture_bytes: 0x{:X}, gfx_commands: {}, meshes: {}, bones: {}}}\n",
+            stats.vertices, stats.triangles, stats.textures, stats.texture_bytes, stats.gfx_commands, self.meshes.len(), self.bone_count());
+        return Some(out)
+    }
+
+    pub fn header(&self) -> Option<&model::ModelHeader>{
+        return self.header.as_ref()
+    }
+
+    pub fn mesh_count(&self) -> usize{
+        return self.header.as_ref().map(|h| h.mesh_count(&self.bytes)).unwrap_or(0)
+    }
+
+    pub fn bone_count(&self) -> usize{
+        return self.header.as_ref().map(|h| h.bone_count(&self.bytes)).unwrap_or(0)
+    }
+
+    /// the mesh list, ids and the vertices of every mesh
+    pub fn meshes(&self) -> &[model::MeshInfo]{
+        return &self.meshes
+    }
+
+    //the mesh every vertex of a triangle belongs to, None for triangles outside the mesh list
+    fn triangle_meshes(&self, triangles: &[gfx::Triangle]) -> Vec<Option<usize>>{
+        let mut owner : HashMap<usize, usize> = HashMap::new();
+        for (i, mesh) in self.meshes.iter().enumerate(){
+            for v in mesh.vertices.iter(){
+                owner.entry(*v as usize).or_insert(i);
+            }
+        }
+        return triangles.iter().map(|t|{
+            let first = owner.get(&t.vertices[0]).copied();
+            first.filter(|m| t.vertices.iter().all(|v| owner.get(v) == Some(m)))
+        }).collect()
+    }
+
+    /// every vertex of the vertex list, empty when the model has none
+    pub fn vertices(&self) -> Result<Vec<model::ModelVertex>, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        return header.vertices(&self.bytes)
+    }
+
+    /// overwrites the vertex list in place, the count has to stay the same and the bounds in the list header are kept
+    pub fn set_vertices(&mut self, vertices: &[model::ModelVertex]) -> Result<(), model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let (start, count) = header.vertex_range(&self.bytes)?.unwrap_or((0, 0));
+        if vertices.len() != count{
+            return Err(model::ModelError::VertexCount{expected: count, found: vertices.len()});
+        }
+        for (i, vertex) in vertices.iter().enumerate(){
+            let offset = start + i*model::ModelVertex::LEN;
+            self.bytes[offset..offset + model::ModelVertex::LEN].copy_from_slice(&vertex.to_bytes());
+        }
+        return Ok(())
+    }
+
+    /// the raw display list commands, unknown ones included
+    pub fn display_list(&self) -> Result<gfx::DisplayList, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        return header.display_list(&self.bytes)
+    }
+
+    /// the triangles the display list draws and anything odd found on the way
+    pub fn triangles(&self) -> Result<(Vec<gfx::Triangle>, Vec<gfx::GfxIssue>), model::ModelError>{
+        let vertex_count = self.vertices()?.len();
+        return Ok(self.display_list()?.triangles(vertex_count))
+    }
+
+    /// the texture list entry a segment 2 address from the display list points into,
+    /// palette loads land on the same entry as the pixels
+    pub fn texture_index(&self, address: u32) -> Option<usize>{
+        if (address >> 24) as u8 != gfx::TEXTURE_SEGMENT{
+            return None
+        }
+        let offset = (address & 0xFF_FFFF) as usize;
+        return self.texture_entries().iter().position(|e| {
+            let start = e.offset as usize;
+            offset >= start && offset < start + e.byte_len().unwrap_or(1)
+        })
+    }
+
+    /// the texture list records, a list that runs past the end of the file is treated as empty
+    pub fn texture_entries(&self) -> Vec<model::TextureEntry>{
+        let header = match &self.header{
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        match header.textures(&self.bytes){
+            Ok((entries, _)) => entries,
+            Err(e) => {
+                eprintln!("warning: model textures skipped: {}", e);
+                Vec::new()
+            },
+        }
+    }
+
+    /// decodes one texture list entry, CI textures carry their palette right before the pixels
+    pub fn texture(&self, index: usize) -> Result<Texture, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let (entries, data_start) = header.textures(&self.bytes)?;
+        let entry = entries.get(index).ok_or(model::ModelError::NoTexture{index: index, count: entries.len()})?;
+        let bad_texture = |e: TextureError| model::ModelError::BadTexture{index: index, reason: e.to_string()};
+        let len = entry.byte_len().ok_or(bad_texture(TextureError::UnknownFormat(entry.format)))?;
+        let start = (data_start + entry.offset as usize).min(self.bytes.len());
+        let end = (start + len).min(self.bytes.len());
+        return Texture::new(entry.format, entry.width as usize, entry.height as usize, &self.bytes[start..end]).map_err(bad_texture)
+    }
+
+    /// every texture that decodes, the others are reported and left out
+    pub fn textures(&self) -> Vec<Texture>{
+        return (0..self.texture_entries().len())
+            .filter_map(|i| match self.texture(i){
+                Ok(texture) => Some(texture),
+                Err(e) => {
+                    eprintln!("warning: {}", e);
+                    None
+                },
+            })
+            .collect()
+    }
+
+    pub fn stats(&self) -> Result<model::ModelStats, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let (entries, _) = header.textures(&self.bytes)?;
+        return Ok(model::ModelStats{
+            vertices: header.vertices(&self.bytes)?.len(),
+            triangles: self.triangles()?.0.len(),
+            textures: entries.len(),
+            texture_bytes: entries.iter().filter_map(|e| e.byte_len()).sum(),
+            gfx_commands: header.display_list(&self.bytes)?.commands.len(),
+        })
+    }
+
+    /// textures too big for TMEM, vertex loads past the cache, triangles without area and
+    /// whatever else walking the display list ran into
+    pub fn lint(&self) -> Result<Vec<model::LintIssue>, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let mut issues : Vec<model::LintIssue> = Vec::new();
+        for (i, entry) in header.textures(&self.bytes)?.0.iter().enumerate(){
+            let (w, h) = (entry.width as usize, entry.height as usize);
+            let (bytes, limit) = model::tmem_usage(entry.format, w, h);
+            if bytes > limit{
+                issues.push(model::LintIssue::TextureTooLarge{index: i, width: w, height: h, bytes: bytes, limit: limit});
+            }
+        }
+        let vertices = header.vertices(&self.bytes)?;
+        let (triangles, gfx_issues) = self.triangles()?;
+        issues.extend(gfx_issues.into_iter().map(|issue| match issue{
+            gfx::GfxIssue::CacheOverflow{command, first, count} => model::LintIssue::VertexCacheOverflow{command: command, first: first, count: count},
+            other => model::LintIssue::DisplayList(other),
+        }));
+        for (i, triangle) in triangles.iter().enumerate(){
+            let corners : Vec<[i16; 3]> = triangle.vertices.iter().filter_map(|v| vertices.get(*v)).map(|v| v.position).collect();
+            if corners.len() == 3 && model::is_degenerate(corners[0], corners[1], corners[2]){
+                issues.push(model::LintIssue::DegenerateTriangle{triangle: i, vertices: triangle.vertices});
+            }
+        }
+        return Ok(issues)
+    }
+
+    /// scales every position around the origin by `scale`, then moves it by `translate`: the vertices,
+    /// the bounds of the vertex list and the bone pivots, and the collision triangles are sorted into a
+    /// new grid. coordinates past the i16 range are clamped with a warning. positions in the geo layout
+    /// and effects are not decoded and stay put
+    pub fn transform(&mut self, scale: f32, translate: [f32; 3]) -> Result<(), model::ModelError>{
+        if !scale.is_finite() || scale <= 0.0{
+            return Err(model::ModelError::BadScale(scale));
+        }
+        let header = self.header.clone().ok_or(model::ModelError::NoHeader)?;
+        let mut clamped = 0;
+        let mut point = |p: [i16; 3]| [0, 1, 2].map(|a|{
+            let v = (p[a] as f32 * scale + translate[a]).round();
+            if v < i16::MIN as f32 || v > i16::MAX as f32{
+                clamped += 1;
+            }
+            v.clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        });
+        let s16_at = |bytes: &[u8], offset: usize| model::u16_at(bytes, offset) as i16;
+        let put_s16s = |bytes: &mut [u8], offset: usize, words: &[i16]| for (i, w) in words.iter().enumerate(){
+            bytes[offset + 2*i..offset + 2*i + 2].copy_from_slice(&w.to_be_bytes());
+        };
+
+        let mut vertices = header.vertices(&self.bytes)?;
+        for v in vertices.iter_mut(){
+            v.position = point(v.position);
+        }
+        //collision triangles index the vertex list, only which cells they fall in changes
+        let collision = match header.collision(&self.bytes)?{
+            Some(mesh) if scale != 1.0 || translate != [0.0; 3] => Some(mesh.regrid(&vertices)?),
+            _ => None,
+        };
+        if header.vtx_list != 0{
+            //min, max and center, then the radius around the center, the count and the radius around the origin
+            let list = header.vtx_list as usize;
+            for corner in 0..3{
+                let moved = point([0, 1, 2].map(|a| s16_at(&self.bytes, list + 6*corner + 2*a)));
+                put_s16s(&mut self.bytes, list + 6*corner, &moved);
+            }
+            let radius = |r: i16| (r as f32 * scale).ceil().min(i16::MAX as f32) as i16;
+            let local = radius(s16_at(&self.bytes, list + 0x12));
+            let global = match translate == [0.0; 3]{
+                true => radius(s16_at(&self.bytes, list + 0x16)),
+                false => vertices.iter()
+                    .map(|v| v.position.iter().map(|c| (*c as f32).powi(2)).sum::<f32>().sqrt())
+                    .fold(0.0f32, f32::max).ceil().min(i16::MAX as f32) as i16,
+            };
+            put_s16s(&mut self.bytes, list + 0x12, &[local]);
+            put_s16s(&mut self.bytes, list + 0x16, &[global]);
+        }
+        self.set_vertices(&vertices)?;
+
+        let bone_count = header.skeleton(&self.bytes)?.bones.len();
+        for i in 0..bone_count{
+            let entry = header.bone_list as usize + 8 + i*0x10;
+            for (a, offset) in translate.iter().enumerate(){
+                let at = entry + 4*a;
+                let value = f32::from_bits(model::u32_at(&self.bytes, at)) * scale;
+                //adding 0.0 would turn -0.0 into 0.0
+                let value = if *offset != 0.0 { value + offset } else { value };
+                self.bytes[at..at + 4].copy_from_slice(&value.to_bits().to_be_bytes());
+            }
+        }
+        if let Some(mesh) = collision{
+            let bytes = model::splice_section(&self.bytes, &header, header.collision_list as usize, &mesh.to_bytes());
+            *self = Model::parse(&bytes)?;
+        }
+        if clamped != 0{
+            eprintln!("warning: {} coordinates clamped to the i16 range, the model is too big for that transform", clamped);
+        }
+        return Ok(())
+    }
+
+    /// swaps texture `index` for an 8 bit rgba image, encoded in the format the texture already has.
+    /// CI images have to fit the existing palette size, they are not quantized. an image that needs
+    /// more bytes than the old one grows its slot in place, the textures after it move up and the
+    /// display list's image loads of them move along. vertices and triangles are not touched
+    pub fn replace_texture(&mut self, index: usize, rgba: &[u8], w: usize, h: usize) -> Result<(), model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let (entries, data_start) = header.textures(&self.bytes)?;
+        let entry = entries.get(index).ok_or(model::ModelError::NoTexture{index: index, count: entries.len()})?.clone();
+        let bad_texture = |reason: String| model::ModelError::BadTexture{index: index, reason: reason};
+        let old_len = entry.byte_len().ok_or(bad_texture(TextureError::UnknownFormat(entry.format).to_string()))?;
+        let list = header.texture_list as usize;
+        let data_len = model::u32_at(model::section(&self.bytes, "texture list", list, 4)?, 0) as usize;
+        model::section(&self.bytes, "texture data", data_start, data_len)?;
+        if entry.offset as usize + old_len > data_len{
+            return Err(bad_texture(format!("its 0x{:X} bytes at 0x{:X} run past the 0x{:X} bytes of texture data", old_len, entry.offset, data_len)));
+        }
+        if rgba.len() != 4*w*h{
+            return Err(bad_texture(TextureError::SizeMismatch{expected: 4*w*h, found: rgba.len()}.to_string()));
+        }
+        if w > 0xFF || h > 0xFF{
+            return Err(model::ModelError::TooMany{what: "texels on a texture side", count: w.max(h), limit: 0xFF});
+        }
+        let (bytes, limit) = model::tmem_usage(entry.format, w, h);
+        if bytes > limit{
+            return Err(model::ModelError::TextureTooLarge{width: w, height: h, bytes: bytes, limit: limit});
+        }
+        let palette_size = match entry.format{
+            ImgFmt::CI4 => Some(16),
+            ImgFmt::CI8 => Some(256),
+            _ => None,
+        };
+        if let Some(limit) = palette_size{
+            //palette entries are rgba16, so colors that only differ below that count once
+            let rgba16 = Texture::rgba32_to_rgba16(&Texture::collapse_alpha(entry.format, rgba, EncodeOptions::default().alpha_threshold));
+            let mut colors : Vec<&[u8]> = rgba16.chunks_exact(2).collect();
+            colors.sort();
+            colors.dedup();
+            if colors.len() > limit{
+                return Err(model::ModelError::TooManyColors{index: index, colors: colors.len(), limit: limit});
+            }
+        }
+        let data = Texture::from_rgba32(entry.format, w, h, rgba).map_err(|e| bad_texture(e.to_string()))?.to_native_bytes();
+        if (w, h) != (entry.width as usize, entry.height as usize){
+            eprintln!("warning: texture {} was {}x{}, the display list still sets up tiles for that size and the uvs are not rescaled", index, entry.width, entry.height);
+        }
+
+        let entry_offset = list + 8 + index*model::TextureEntry::LEN;
+        let start = data_start + entry.offset as usize;
+        let mut new_entry = entry.clone();
+        new_entry.width = w as u8;
+        new_entry.height = h as u8;
+        if data.len() <= old_len{
+            self.bytes[start..start + old_len].fill(0);
+            self.bytes[start..start + data.len()].copy_from_slice(&data);
+            self.bytes[entry_offset..entry_offset + model::TextureEntry::LEN].copy_from_slice(&new_entry.to_bytes());
+            return Ok(())
+        }
+
+        //grown: the slot is widened where it is and everything stored after it moves up
+        let old_end = entry.offset + old_len as u32;
+        if let Some(other) = entries.iter().position(|e| e.offset > entry.offset && e.offset < old_end){
+            return Err(bad_texture(format!("texture {} starts inside its bytes, they can't be moved apart", other)));
+        }
+        let grow = (data.len() - old_len + 7) & !7;
+        eprintln!("warning: texture {} grew by 0x{:X} bytes, the textures after it move up", index, grow);
+        let mut section = self.bytes[list..data_start + data_len].to_vec();
+        let mut slot = data;
+        slot.resize(old_len + grow, 0);
+        section.splice(start - list..start - list + old_len, slot);
+        section[0..4].copy_from_slice(&((data_len + grow) as u32).to_be_bytes());
+        for (i, other) in entries.iter().enumerate(){
+            let patched = match (i == index, other.offset >= old_end){
+                (true, _) => new_entry.clone(),
+                (false, true) => model::TextureEntry{offset: other.offset + grow as u32, ..other.clone()},
+                (false, false) => continue,
+            };
+            let at = 8 + i*model::TextureEntry::LEN;
+            section[at..at + model::TextureEntry::LEN].copy_from_slice(&patched.to_bytes());
+        }
+        let bytes = model::splice_section(&self.bytes, header, list, &section);
+        let mut model = Model::parse(&bytes)?;
+
+        let segment = (gfx::TEXTURE_SEGMENT as u32) << 24;
+        let mut display_list = model.display_list()?;
+        display_list.retarget_images(segment + old_end..segment + data_len as u32, segment + old_end + grow as u32);
+        if let Some(gfx_list) = model.header.as_ref().map(|h| h.gfx_list as usize).filter(|o| *o != 0){
+            let commands = display_list.to_bytes();
+            model.bytes[gfx_list + 8..gfx_list + 8 + commands.len()].copy_from_slice(&commands);
+        }
+        *self = model;
+        return Ok(())
+    }
+
+    //writes dir/<base_name>.tex<i>.png for every texture that decodes, returns the file names by texture index
+    fn write_texture_pngs(&self, dir: &Path, base_name: &str) -> Vec<Option<String>>{
+        return (0..self.texture_entries().len()).map(|i|{
+            let texture = match self.texture(i){
+                Ok(texture) => texture,
+                Err(e) => { eprintln!("warning: {} not written: {}", base_name, e); return None },
+            };
+            let file_name = format!("{}.tex{}.png", base_name, i);
+            match texture.to_rgba32(){
+                Ok(rgba) => { write_png_rgba32(&dir.join(&file_name), texture.w, texture.h, &rgba); Some(file_name) },
+                Err(e) => { eprintln!("warning: {} texture {} not written: {}", base_name, i, e); None },
+            }
+        }).collect()
+    }
+
+    pub fn write_with(&self, path: &Path, options: &ModelWriteOptions){
+        let mut bin_file = File::create(path).unwrap();
+        bin_file.write_all(&self.bytes).unwrap();
+        //0123.model.bin -> 0123.tex0.png, 0123.tex1.png ... and 0123.model.yaml
+        let base_name = Path::new(path.file_stem().unwrap()).file_stem().unwrap().to_str().unwrap();
+        let texture_files = match options.textures{
+            true => self.write_texture_pngs(path.parent().unwrap(), base_name),
+            false => Vec::new(),
+        };
+        if options.descriptor{
+            if let Some(text) = self.descriptor(path.file_name().unwrap().to_str().unwrap(), &texture_files){
+                fs::write(path.with_extension("yaml"), text).unwrap();
+            }
+        }
+    }
+
+    /// writes out_dir/<name>.obj, <name>.mtl and the textures as <name>.tex<i>.png.
+    /// vertex colors go after the positions as `v x y z r g b`, which Blender and MeshLab read
+    pub fn to_obj(&self, out_dir: &Path, name: &str) -> Result<(), model::ModelError>{
+        let vertices = self.vertices()?;
+        let (triangles, issues) = self.triangles()?;
+        for issue in issues.iter(){
+            eprintln!("warning: {}: {}", name, issue);
+        }
+        DirBuilder::new().recursive(true).create(out_dir).unwrap();
+        let png_files = self.write_texture_pngs(out_dir, name);
+        let entries = self.texture_entries();
+
+        let mut mtl = String::from("newmtl untextured\nKd 1 1 1\n");
+        for (i, file) in png_files.iter().enumerate(){
+            if let Some(file) = file{
+                mtl += &format!("\nnewmtl tex{}\nKd 1 1 1\nmap_Kd {}\n", i, file);
+            }
+        }
+
+        let mut obj = format!("mtllib {}.mtl\no {}\n", name, name);
+        for v in vertices.iter(){
+            let c = |i: usize| v.color[i] as f32 / 255.0;
+            obj += &format!("v {} {} {} {} {} {}\n", v.position[0], v.position[1], v.position[2], c(0), c(1), c(2));
+        }
+        //one uv per vertex and texture, uvs are texels so they depend on the texture size
+        let mut uvs : Vec<(usize, usize)> = Vec::new();
+        let mut uv_index : HashMap<(usize, usize), usize> = HashMap::new();
+        let mut faces = String::new();
+        let mut material = String::new();
+        let mut group = String::new();
+        //a group per mesh list entry, triangles outside the list go in the model's own group
+        let owners = self.triangle_meshes(&triangles);
+        let mut order : Vec<usize> = (0..triangles.len()).collect();
+        order.sort_by_key(|i| owners[*i].map(|m| m + 1).unwrap_or(0));
+        for i in order{
+            let tri = &triangles[i];
+            let next_group = owners[i].map(|m| format!("mesh_{}", self.meshes[m].id)).unwrap_or(String::from(name));
+            if next_group != group{
+                faces += &format!("g {}\n", next_group);
+                group = next_group;
+                material = String::new();
+            }
+            let texture = match tri.textured{
+                true => tri.texture.and_then(|address| self.texture_index(address)).filter(|i| png_files[*i].is_some()),
+                false => None,
+            };
+            let next_material = texture.map(|i| format!("tex{}", i)).unwrap_or(String::from("untextured"));
+            if next_material != material{
+                faces += &format!("usemtl {}\n", next_material);
+                material = next_material;
+            }
+            let corners : Vec<String> = tri.vertices.iter().map(|v| match texture{
+                Some(t) => {
+                    let uv = *uv_index.entry((*v, t)).or_insert_with(|| { uvs.push((*v, t)); uvs.len() - 1 });
+                    format!("{}/{}", v + 1, uv + 1)
+                },
+                None => format!("{}", v + 1),
+            }).collect();
+            faces += &format!("f {}\n", corners.join(" "));
+        }
+        for (v, t) in uvs.iter(){
+            let [s, t_] = vertices[*v].uv_f32();
+            let (w, h) = (entries[*t].width.max(1) as f32, entries[*t].height.max(1) as f32);
+            obj += &format!("vt {} {}\n", s/w, 1.0 - t_/h);
+        }
+        obj += &faces;
+
+        fs::write(out_dir.join(format!("{}.obj", name)), obj).unwrap();
+        fs::write(out_dir.join(format!("{}.mtl", name)), mtl).unwrap();
+        return Ok(())
+    }
+
+    /// the collision mesh level models carry, None for models without one
+    pub fn collision(&self) -> Option<model::CollisionMesh>{
+        let header = self.header.as_ref()?;
+        match header.collision(&self.bytes){
+            Ok(collision) => collision,
+            Err(e) => {
+                eprintln!("warning: model collision skipped: {}", e);
+                None
+            },
+        }
+    }
+
+    /// replaces the collision list, sections after it move if its size changes
+    pub fn set_collision(&mut self, collision: &model::CollisionMesh) -> Result<(), model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        if header.collision_list == 0{
+            return Err(model::ModelError::OffsetOutOfRange{section: "collision list", offset: 0, len: self.bytes.len()});
+        }
+        let vertex_count = header.vertices(&self.bytes)?.len();
+        if let Some(v) = collision.triangles.iter().flat_map(|t| t.vertices).find(|v| *v as usize >= vertex_count){
+            return Err(model::ModelError::OutOfRange{what: "collision vertex", index: v as usize, value: vertex_count as f32});
+        }
+        let bytes = model::splice_section(&self.bytes, header, header.collision_list as usize, &collision.to_bytes());
+        *self = Model::parse(&bytes)?;
+        return Ok(())
+    }
+
+    /// writes the collision mesh as out_dir/<name>.collision.obj with a group and a colored
+    /// material per distinct flags value
+    pub fn collision_to_obj(&self, out_dir: &Path, name: &str) -> Result<(), model::ModelError>{
+        let collision = match self.collision(){
+            Some(collision) => collision,
+            None => return Ok(()),
+        };
+        let vertices = self.vertices()?;
+        let mut flags : Vec<u32> = collision.triangles.iter().map(|t| t.flags).collect();
+        flags.sort();
+        flags.dedup();
+
+        let mut mtl = String::new();
+        for f in flags.iter(){
+            //any stable color will do, spread the bits around so close values still look different
+            let hash = f.wrapping_mul(0x9E37_79B9);
+            let c = |shift: u32| (hash >> shift & 0xFF) as f32 / 255.0 * 0.8 + 0.2;
+            mtl += &format!("newmtl flags_{:08X}\nKd {} {} {}\n\n", f, c(24), c(16), c(8));
+        }
+        let mut obj = format!("mtllib {}.collision.mtl\no {}_collision\n", name, name);
+        for v in vertices.iter(){
+            obj += &format!("v {} {} {}\n", v.position[0], v.position[1], v.position[2]);
+        }
+        for f in flags.iter(){
+            obj += &format!("g flags_{:08X}\nusemtl flags_{:08X}\n", f, f);
+            for tri in collision.triangles.iter().filter(|t| t.flags == *f){
+                obj += &format!("f {} {} {}\n", tri.vertices[0] + 1, tri.vertices[1] + 1, tri.vertices[2] + 1);
+            }
+        }
+        DirBuilder::new().recursive(true).create(out_dir).unwrap();
+        fs::write(out_dir.join(format!("{}.collision.obj", name)), obj).unwrap();
+        fs::write(out_dir.join(format!("{}.collision.mtl", name)), mtl).unwrap();
+        return Ok(())
+    }
+
+    /// bone list entries as a skeleton for the animation exporter, empty without a bone list
+    pub fn skeleton(&self) -> Result<anim::ModelSkeleton, model::ModelError>{
+        let header = self.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        return header.skeleton(&self.bytes)
+    }
+
+    /// the model as a .glb: one primitive per texture with the pngs embedded, vertex colors on
+    /// unlit geometry, normals on lit geometry and the bones as nodes. which bone moves which
+    /// vertices is decided by the geo layout, that isn't decoded so there is no skin
+    pub fn to_gltf(&self) -> Result<Vec<u8>, model::ModelError>{
+        let vertices = self.vertices()?;
+        let (triangles, issues) = self.triangles()?;
+        for issue in issues.iter(){
+            eprintln!("warning: {}", issue);
+        }
+        let mut gltf = Gltf::new();
+        self.skeleton()?.add_nodes(&mut gltf);
+
+        //a material per texture that decodes, materials[i] is texture i's
+        let entries = self.texture_entries();
+        let mut materials : Vec<Option<usize>> = Vec::new();
+        for i in 0..entries.len(){
+            let rgba = self.texture(i).ok().and_then(|t| t.to_rgba32().ok().map(|rgba| (t.w, t.h, rgba)));
+            materials.push(rgba.map(|(w, h, rgba)|{
+                let view = gltf.add_view(&png_rgba32(w, h, &rgba));
+                let image = gltf.add("images", format!("{{\"bufferView\": {}, \"mimeType\": \"image/png\"}}", view));
+                let texture = gltf.add("textures", format!("{{\"source\": {}}}", image));
+                gltf.add("materials", format!("{{\"name\": \"tex{}\", \"pbrMetallicRoughness\": {{\"baseColorTexture\": {{\"index\": {}}}, \"metallicFactor\": 0}}}}", i, texture))
+            }));
+        }
+        let untextured = gltf.add("materials", String::from("{\"name\": \"untextured\", \"pbrMetallicRoughness\": {\"metallicFactor\": 0}}"));
+
+        //G_LIGHTING, lit vertices store a normal where the color would be
+        const LIGHTING : u32 = 0x0002_0000;
+        let owners = self.triangle_meshes(&triangles);
+        //mesh, texture and lighting a run of triangles shares
+        type GroupKey = (Option<usize>, Option<usize>, bool);
+        let mut groups : Vec<(GroupKey, Vec<usize>)> = Vec::new();
+        for (tri, owner) in triangles.iter().zip(owners){
+            let texture = tri.texture.filter(|_| tri.textured).and_then(|a| self.texture_index(a)).filter(|i| materials[*i].is_some());
+            let key = (owner, texture, tri.geometry_mode & LIGHTING != 0);
+            match groups.iter_mut().find(|(k, _)| *k == key){
+                Some((_, corners)) => corners.extend_from_slice(&tri.vertices),
+                None => groups.push((key, tri.vertices.to_vec())),
+            }
+        }
+
+        //a glTF mesh and node per mesh list entry
+        groups.sort_by_key(|((owner, _, _), _)| owner.map(|m| m + 1).unwrap_or(0));
+        let mut primitives : Vec<(Option<usize>, String)> = Vec::new();
+        for ((owner, texture, lit), corners) in groups.iter(){
+            //the vertices this primitive uses, renumbered from 0
+            let mut used : Vec<usize> = corners.clone();
+            used.sort();
+            used.dedup();
+            let indices : Vec<u32> = corners.iter().map(|v| used.binary_search(v).unwrap() as u32).collect();
+            let positions : Vec<f32> = used.iter().flat_map(|v| vertices[*v].position.map(|p| p as f32)).collect();
+            let mut attributes = format!("\"POSITION\": {}", gltf.add_floats(&positions, "VEC3", true));
+            match lit{
+                true => {
+                    let normals : Vec<f32> = used.iter().flat_map(|v|{
+                        let n = vertices[*v].normal();
+                        let len = (n[0]*n[0] + n[1]*n[1] + n[2]*n[2]).sqrt().max(f32::EPSILON);
+                        n.map(|c| c/len)
+                    }).collect();
+                    attributes += &format!(", \"NORMAL\": {}", gltf.add_floats(&normals, "VEC3", false));
+                },
+                false => {
+                    let colors : Vec<f32> = used.iter().flat_map(|v| vertices[*v].color.map(|c| c as f32 / 255.0)).collect();
+                    attributes += &format!(", \"COLOR_0\": {}", gltf.add_floats(&colors, "VEC4", false));
+                },
+            }
+            if let Some(t) = texture{
+                let (w, h) = (entries[*t].width.max(1) as f32, entries[*t].height.max(1) as f32);
+                let uvs : Vec<f32> = used.iter().flat_map(|v|{ let [s, t] = vertices[*v].uv_f32(); [s/w, t/h] }).collect();
+                attributes += &format!(", \"TEXCOORD_0\": {}", gltf.add_floats(&uvs, "VEC2", false));
+            }
+            let material = texture.and_then(|t| materials[t]).unwrap_or(untextured);
+            primitives.push((*owner, format!("{{\"attributes\": {{{}}}, \"indices\": {}, \"material\": {}}}", attributes, gltf.add_indices(&indices), material)));
+        }
+        let mut owners : Vec<Option<usize>> = primitives.iter().map(|(owner, _)| *owner).collect();
+        owners.dedup();
+        for owner in owners{
+            let json : Vec<String> = primitives.iter().filter(|(o, _)| *o == owner).map(|(_, p)| p.clone()).collect();
+            let name = owner.map(|m| format!("mesh_{}", self.meshes[m].id)).unwrap_or(String::from("model"));
+            let mesh = gltf.add("meshes", format!("{{\"name\": \"{}\", \"primitives\": [{}]}}", name, json.join(", ")));
+            let node = gltf.add("nodes", format!("{{\"name\": \"{}\", \"mesh\": {}}}", name, mesh));
+            gltf.scene_nodes.push(node);
+        }
+        return Ok(gltf.to_glb())
+    }
+
+    /// a model from an edited .obj drawn with one material. the vertex list, display list and
+    /// texture list are rebuilt, the bone list and animated textures come from `template` and the
+    /// geo layout becomes a single draw of the whole display list. sections that index the old
+    /// vertices (collision, mesh list, effects) are dropped
+    pub fn from_obj(obj: &Path, template: &Model, options: &ObjImportOptions) -> Result<Model, model::ModelError>{
+        let template_header = template.header.as_ref().ok_or(model::ModelError::NoHeader)?;
+        let text = fs::read_to_string(obj).map_err(|e| model::ModelError::Obj{line: 0, reason: e.to_string()})?;
+        let mesh = model::ObjMesh::parse(&text)?;
+        if mesh.materials.len() > 1{
+            return Err(model::ModelError::TooManyMaterials(mesh.materials.clone()));
+        }
+
+        //the material's image, encoded like the template's first texture unless told otherwise
+        let image = match (&mesh.mtllib, mesh.materials.first()){
+            (Some(mtllib), Some(material)) => {
+                let mtl_path = obj.parent().unwrap_or(Path::new(".")).join(mtllib);
+                let mtl = fs::read_to_string(&mtl_path).map_err(|e| model::ModelError::Obj{line: 0, reason: format!("{}: {}", mtl_path.display(), e)})?;
+                model::mtl_texture(&mtl, material).map(|file| mtl_path.parent().unwrap().join(file))
+            },
+            _ => None,
+        };
+        let mut texture_list : Vec<u8> = Vec::new();
+        let mut entry : Option<model::TextureEntry> = None;
+        if let Some(image_path) = image{
+            let (w, h, rgba) = read_png_rgba32(&image_path).map_err(|e| model::ModelError::BadTexture{index: 0, reason: format!("{}: {}", image_path.display(), e)})?;
+            let format = options.texture_format
+                .or(template.texture_entries().first().map(|e| e.format).filter(|f| model::image_codes(*f).is_some()))
+                .unwrap_or(ImgFmt::RGBA16);
+            if w > 0xFF || h > 0xFF{
+                return Err(model::ModelError::TooMany{what: "texels on a texture side", count: w.max(h), limit: 0xFF});
+            }
+            let (bytes, limit) = model::tmem_usage(format, w, h);
+            if bytes > limit{
+                return Err(model::ModelError::TextureTooLarge{width: w, height: h, bytes: bytes, limit: limit});
+            }
+            let texture = Texture::from_rgba32_with(format, w, h, &rgba, &options.encode).map_err(|e| model::ModelError::BadTexture{index: 0, reason: e.to_string()})?;
+            let data = texture.to_native_bytes();
+            let new_entry = model::TextureEntry{offset: 0, format: format, unk06: 0, width: w as u8, height: h as u8, unk0a: [0; 6]};
+            texture_list.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            texture_list.extend_from_slice(&[0, 1, 0, 0]);
+            texture_list.extend(new_entry.to_bytes());
+            texture_list.extend(data);
+            entry = Some(new_entry);
+        }
+        let geometry = model::build_geometry(&mesh, entry.as_ref())?;
+
+        let mut header = template_header.clone();
+        let mut out = vec![0u8; model::HEADER_LEN];
+        let align = |out: &mut Vec<u8>| while !out.len().is_multiple_of(8) { out.push(0) };
+        let place = |out: &mut Vec<u8>, section: &[u8]| -> u32{
+            align(out);
+            let offset = out.len() as u32;
+            out.extend_from_slice(section);
+            offset
+        };
+        //geo command 3 draws the display list from command 0 to its G_ENDDL
+        header.geo_layout = place(&mut out, &[0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        header.texture_list = match texture_list.is_empty(){
+            true => 0,
+            false => place(&mut out, &texture_list) as u16,
+        };
+        header.gfx_list = place(&mut out, &geometry.gfx_section);
+        header.vtx_list = place(&mut out, &geometry.vtx_section);
+        header.bone_list = 0;
+        header.anim_texture_list = 0;
+        for (name, offset, len) in model::section_spans(template_header, template.bytes.len()){
+            let section = &template.bytes[offset..offset + len];
+            match name{
+                "bone list" => header.bone_list = place(&mut out, section),
+                "animated textures" => header.anim_texture_list = place(&mut out, section),
+                "geo layout" | "texture list" | "display list" | "vertex list" => {},
+                _ => eprintln!("warning: {} of the template is not carried over", name),
+            }
+        }
+        header.collision_list = 0;
+        header.mesh_list = 0;
+        header.effects_setup = 0;
+        header.unk14 = 0;
+        header.unk28 = 0;
+        header.tri_count = geometry.triangle_count as u16;
+        header.vtx_count = geometry.vertex_count as u16;
+        align(&mut out);
+        out[..model::HEADER_LEN].copy_from_slice(&header.to_bytes());
+        return Model::parse(&out)
+    }
+}
+
+pub struct ModelWriteOptions{
+    pub textures: bool, //also write every embedded texture as a png next to the .bin
+    pub descriptor: bool, //write the .model.yaml describing the bin
+}
+
+impl Default for ModelWriteOptions{
+    fn default() -> ModelWriteOptions{
+        ModelWriteOptions{textures: false, descriptor: true}
+    }
+}
+
+/// knobs for `Model::from_obj`
+#[derive(Clone, Copy, Default)]
+pub struct ObjImportOptions{
+    pub texture_format: Option<ImgFmt>, //None keeps the template's texture format
+    pub encode: EncodeOptions,
+}
+
+impl ObjImportOptions{
+    pub fn texture_format(mut self, format: ImgFmt) -> ObjImportOptions{
+        self.texture_format = Some(format);
+        return self
+    }
+
+    pub fn encode(mut self, encode: EncodeOptions) -> ObjImportOptions{
+        self.encode = encode;
+        return self
+    }
+}
+
+impl ModelWriteOptions{
+    pub fn textures(mut self, enable: bool) -> ModelWriteOptions{
+        self.textures = enable;
+        return self
+    }
+
+    pub fn descriptor(mut self, enable: bool) -> ModelWriteOptions{
+        self.descriptor = enable;
+        return self
+    }
+}
+
+impl Asset for Model{
+    fn to_bytes(&self)->Vec<u8>{
+        return self.bytes.clone();
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Model;
+    }
+
+    fn write(&self, path: &Path){
+        self.write_with(path, &ModelWriteOptions::default());
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum PaletteFormat{
+    Raw,    //.pal, rgba16 words as stored in the file
+    Swatch, //.pal.png, one pixel per entry
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DitherMode{
+    None,
+    Ordered,
+    FloydSteinberg,
+}
+
+/// knobs for `Texture::from_rgba32_with`, the defaults keep unmodified assets byte exact
+#[derive(Clone, Copy)]
+pub struct EncodeOptions{
+    pub dither: DitherMode,
+    pub alpha_threshold: u8, //lowest alpha kept opaque by the 1 bit alpha formats
+}
+
+impl Default for EncodeOptions{
+    fn default() -> EncodeOptions{
+        EncodeOptions{dither: DitherMode::None, alpha_threshold: 0x80}
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError{
+    UnknownFormat(ImgFmt),
+    SizeMismatch{expected: usize, found: usize},
+    MissingPalette(ImgFmt),
+    TruncatedData{expected: usize, available: usize},
+}
+
+impl fmt::Display for TextureError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            TextureError::UnknownFormat(format) =>
+                write!(f, "cannot convert {:?} textures", format),
+            TextureError::SizeMismatch{expected, found} =>
+                write!(f, "expected 0x{:X} bytes of pixel data, got 0x{:X}", expected, found),
+            TextureError::MissingPalette(format) =>
+                write!(f, "{:?} texture has no palette", format),
+            TextureError::TruncatedData{expected, available} =>
+                write!(f, "texture needs 0x{:X} bytes, only 0x{:X} available", expected, available),
+        }
+    }
+}
+
+/// what `Texture::convert` had to throw away
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct ConversionLoss{
+    pub colors_reduced: bool, //fewer distinct colors after conversion
+    pub alpha_collapsed: bool,
+    pub pixels_changed: usize,
+}
+
+impl ConversionLoss{
+    pub fn is_lossless(&self) -> bool{
+        self.pixels_changed == 0
+    }
+}
+
+/// how rgb is reduced to a single intensity value for the I and IA formats
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum IntensityMode{
+    Luma,
+    Average,
+}
+
+pub struct Texture {
+    texture_type: ImgFmt,
+    w : usize,
+    h : usize,
+
+    palette : Option<Vec<u8>>,
+    pixel_data : Vec<u8>,
+}
+
+impl Texture {
+    pub fn new(texture_type: ImgFmt, w : usize, h : usize, bin : &[u8])->Result<Texture, TextureError>{
+        let (pal_size, pxl_size) : (usize, usize) = match texture_type{
+            ImgFmt::CI4 => (0x20, 4),
+            ImgFmt::CI8 => (0x200, 8),
+            ImgFmt::I4 | ImgFmt::IA4 => (0, 4),
+            ImgFmt::I8 | ImgFmt::IA8 => (0, 8),
+            ImgFmt::RGBA16 => (0, 16),
+            ImgFmt::RGBA32 => (0, 32),
+            ImgFmt::Unknown(_) => return Err(TextureError::UnknownFormat(texture_type)),
+        };
+        if bin.len() < pal_size + w*h*pxl_size/8{
+            return Err(TextureError::TruncatedData{expected: pal_size + w*h*pxl_size/8, available: bin.len()});
+        }
+
+        let palette : Option<Vec<u8>> = match texture_type{
+            ImgFmt::CI4 => Some(bin[0.. 0x20].to_vec()),
+            ImgFmt::CI8 => Some(bin[0.. 0x200].to_vec()),
+            _=> None,
+        };
+        
+        let pixel_data = match texture_type {
+            ImgFmt::CI4 => &bin[0x20..],
+            ImgFmt::CI8 => &bin[0x200..],
+            _ => bin,
+        };
+
+        return Ok(Texture{
+            texture_type : texture_type, 
+            w : w,
+            h : h,
+            palette : palette,
+            pixel_data : pixel_data.to_vec(),
+        })
+    }
+
+    /// encodes 8 bit rgba pixels into `texture_type`, quantizing CI palettes when needed
+    pub fn from_rgba32(texture_type: ImgFmt, w : usize, h : usize, rgba : &[u8])->Result<Texture, TextureError>{
+        return Texture::from_rgba32_with(texture_type, w, h, rgba, &EncodeOptions::default())
+    }
+
+    pub fn from_rgba32_with(texture_type: ImgFmt, w : usize, h : usize, rgba : &[u8], options : &EncodeOptions)->Result<Texture, TextureError>{
+        if rgba.len() != 4*w*h{
+            return Err(TextureError::SizeMismatch{expected: 4*w*h, found: rgba.len()});
+        }
+        let snapped = Texture::collapse_alpha(texture_type, rgba, options.alpha_threshold);
+        let rgba : &[u8] = &snapped;
+        let (palette, pixel_data) = match texture_type{
+            ImgFmt::CI4 => {let (ci, pal) = Texture::rgba32_to_ci4_dithered(rgba, w, options.dither); (Some(pal), ci)},
+            ImgFmt::CI8 => {let (ci, pal) = Texture::rgba32_to_ci8_dithered(rgba, w, options.dither); (Some(pal), ci)},
+            ImgFmt::I4 => (None, Texture::rgba32_to_i4(rgba, IntensityMode::Luma)),
+            ImgFmt::I8 => (None, Texture::rgba32_to_i8(rgba, IntensityMode::Luma)),
+            ImgFmt::IA4 => (None, Texture::rgba32_to_ia4(rgba, IntensityMode::Luma)),
+            ImgFmt::IA8 => (None, Texture::rgba32_to_ia8(rgba, IntensityMode::Luma)),
+            ImgFmt::RGBA16 => (None, Texture::rgba32_to_rgba16_dithered(rgba, w, options.dither)),
+            ImgFmt::RGBA32 => (None, rgba.to_vec()),
+            ImgFmt::Unknown(_) => return Err(TextureError::UnknownFormat(texture_type)),
+        };
+        return Ok(Texture{texture_type: texture_type, w: w, h: h, palette: palette, pixel_data: pixel_data})
+    }
+
+    /// sets alpha to 0x00 or 0xFF around `threshold`, returns the pixels and how many were semi transparent
+    pub fn snap_alpha(rgba32 : &[u8], threshold : u8)->(Vec<u8>, usize){
+        let mut snapped = 0;
+        let out = rgba32.chunks_exact(4)
+            .flat_map(|px|{
+                if px[3] != 0x00 && px[3] != 0xFF{
+                    snapped += 1;
+                }
+                [px[0], px[1], px[2], if px[3] >= threshold {0xFF} else {0x00}]
+            })
+            .collect();
+        return (out, snapped)
+    }
+
+    //snaps alpha for the formats that only keep 1 bit of it, warning when that loses anything
+    fn collapse_alpha(texture_type: ImgFmt, rgba32 : &[u8], threshold : u8)->Vec<u8>{
+        match texture_type{
+            ImgFmt::RGBA16 | ImgFmt::CI4 | ImgFmt::CI8 | ImgFmt::IA4 => {},
+            _ => return rgba32.to_vec(),
+        }
+        let (out, snapped) = Texture::snap_alpha(rgba32, threshold);
+        if snapped != 0{
+            eprintln!("warning: {} semi transparent pixels snapped to 1 bit {:?} alpha at threshold 0x{:02X}", snapped, texture_type, threshold);
+        }
+        return out
+    }
+
+    /// decodes and re-encodes into `target`, quantizing the palette for CI targets
+    pub fn convert(&self, target: ImgFmt) -> Result<(Texture, ConversionLoss), TextureError>{
+        let rgba = self.to_rgba32()?;
+        let converted = Texture::from_rgba32(target, self.w, self.h, &rgba)?;
+        let decoded = converted.to_rgba32()?;
+
+        let distinct = |px: &[u8]|{
+            let mut colors : Vec<&[u8]> = px.chunks_exact(4).collect();
+            colors.sort();
+            colors.dedup();
+            colors.len()
+        };
+        let loss = ConversionLoss{
+            colors_reduced: distinct(&decoded) < distinct(&rgba),
+            alpha_collapsed: rgba.chunks_exact(4).zip(decoded.chunks_exact(4)).any(|(a, b)| a[3] != b[3]),
+            pixels_changed: rgba.chunks_exact(4).zip(decoded.chunks_exact(4)).filter(|(a, b)| a != b).count(),
+        };
+        return Ok((converted, loss))
+    }
+
+    pub fn format(&self) -> ImgFmt{
+        self.texture_type
+    }
+
+    pub fn width(&self) -> usize{
+        self.w
+    }
+
+    pub fn height(&self) -> usize{
+        self.h
+    }
+
+    /// palette followed by pixels, the layout `new` reads
+    pub fn to_native_bytes(&self) -> Vec<u8>{
+        let mut out = self.palette.clone().unwrap_or_default();
+        out.extend_from_slice(&self.pixel_data);
+        return out
+    }
+
+    pub fn to_rgba32(&self) -> Result<Vec<u8>, TextureError>{
+        match self.texture_type{
+            ImgFmt::CI4 => 
+            {   
+                match &self.palette{
+                    Some(pal) if pal.len() >= 0x20 => Ok(Texture::ci4_to_rgba32(&self.pixel_data, pal)),
+                    _ => Err(TextureError::MissingPalette(self.texture_type)),
+                }
+            },
+            ImgFmt::CI8 => 
+            {   
+                match &self.palette{
+                    Some(pal) if pal.len() >= 0x200 => Ok(Texture::ci8_to_rgba32(&self.pixel_data, pal)),
+                    _ => Err(TextureError::MissingPalette(self.texture_type)),
+                }
+            }
+            ImgFmt::RGBA16 => Ok(Texture::rgba16_to_rgba32(&self.pixel_data)),
+            ImgFmt::RGBA32 => Ok(self.pixel_data.clone()),
+            ImgFmt::I4 => Ok(Texture::i4_to_rgba32(&self.pixel_data)),
+            ImgFmt::I8 => Ok(Texture::i8_to_rgba32(&self.pixel_data)),
+            ImgFmt::IA4 => Ok(Texture::ia4_to_rgba32(&self.pixel_data)),
+            ImgFmt::IA8 => Ok(Texture::ia8_to_rgba32(&self.pixel_data)),
+            ImgFmt::Unknown(_) => Err(TextureError::UnknownFormat(self.texture_type)),
+        }
+    }
+
+    pub fn rgba16_to_rgba32(rgba16 : &[u8])->Vec<u8>{
+        return rgba16.chunks_exact(2)
+            .flat_map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                return [r32, g32, b32, a32]
+            })
+            .collect()
+    }
+
+    /// packs 5-5-5-1, alpha is set for values >= 128
+    pub fn rgba32_to_rgba16(rgba32 : &[u8])->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .flat_map(|a|{
+                let r16 = (a[0] >> 3) as u16;
+                let g16 = (a[1] >> 3) as u16;
+                let b16 = (a[2] >> 3) as u16;
+                let a16 = (a[3] >= 0x80) as u16;
+                ((r16 << 11) | (g16 << 6) | (b16 << 1) | a16).to_be_bytes()
+            })
+            .collect()
+    }
+
+    /// `w` is the image width, needed to find the neighbours of a pixel
+    pub fn rgba32_to_rgba16_dithered(rgba32 : &[u8], w : usize, mode : DitherMode)->Vec<u8>{
+        if mode == DitherMode::None{
+            return Texture::rgba32_to_rgba16(rgba32)
+        }
+        let words = Texture::dither(rgba32, w, mode, 8, |c|{
+            let word = Texture::rgba32_to_rgba16(&[c[0] as u8, c[1] as u8, c[2] as u8, c[3] as u8]);
+            (Texture::rgba16_channels32(&word), u16::from_be_bytes([word[0], word[1]]))
+        });
+        return words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    pub fn rgba32_to_ci4_dithered(rgba32 : &[u8], w : usize, mode : DitherMode)->(Vec<u8>, Vec<u8>){
+        let (indices, palette) = Texture::quantize_dithered(rgba32, w, 16, mode);
+        return (Texture::pack_4bit(&indices), palette)
+    }
+
+    pub fn rgba32_to_ci8_dithered(rgba32 : &[u8], w : usize, mode : DitherMode)->(Vec<u8>, Vec<u8>){
+        return Texture::quantize_dithered(rgba32, w, 256, mode)
+    }
+
+    //picks the palette as usual, then maps pixels onto it with dithering
+    fn quantize_dithered(rgba32 : &[u8], w : usize, max_colors : usize, mode : DitherMode)->(Vec<u8>, Vec<u8>){
+        let (indices, palette) = Texture::quantize(rgba32, max_colors);
+        if mode == DitherMode::None{
+            return (indices, palette)
+        }
+        let used = indices.iter().map(|i| *i as usize + 1).max().unwrap_or(0);
+        let pal_words : Vec<u16> = palette[..2*used].chunks_exact(2).map(|a| u16::from_be_bytes([a[0], a[1]])).collect();
+        let indices = Texture::dither(rgba32, w, mode, 16, |c|{
+            let word = Texture::rgba32_to_rgba16(&[c[0] as u8, c[1] as u8, c[2] as u8, c[3] as u8]);
+            let i = Texture::nearest_indices(&[u16::from_be_bytes([word[0], word[1]])], &pal_words)[0];
+            (Texture::rgba16_channels32(&pal_words[i as usize].to_be_bytes()), i as u16)
+        });
+        return (indices.iter().map(|i| *i as u8).collect(), palette)
+    }
+
+    fn rgba16_channels32(word : &[u8])->[i32; 4]{
+        let px = Texture::rgba16_to_rgba32(word);
+        return [px[0] as i32, px[1] as i32, px[2] as i32, px[3] as i32]
+    }
+
+    //runs `snap` over every pixel, spreading what it rounded away over the neighbours (floyd steinberg)
+    //or offsetting the input by a 4x4 bayer matrix scaled to `spread` (ordered)
+    fn dither<F: Fn([i32; 4]) -> ([i32; 4], u16)>(rgba32 : &[u8], w : usize, mode : DitherMode, spread : i32, snap : F)->Vec<u16>{
+        const BAYER_4X4 : [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        let px_cnt = rgba32.len()/4;
+        let w = std::cmp::max(w, 1);
+        let mut err = vec![[0i32; 3]; px_cnt];
+        let mut out = Vec::with_capacity(px_cnt);
+        for i in 0..px_cnt{
+            let (x, y) = (i % w, i / w);
+            let src = &rgba32[4*i..4*i + 4];
+            let mut c = [src[0] as i32, src[1] as i32, src[2] as i32, src[3] as i32];
+            for ch in 0..3{
+                c[ch] += match mode{
+                    //+4 makes up for the rgba16 conversion truncating instead of rounding
+                    DitherMode::Ordered => (2*BAYER_4X4[y % 4][x % 4] - 15)*spread/32 + 4,
+                    DitherMode::FloydSteinberg => err[i][ch],
+                    DitherMode::None => 0,
+                };
+                c[ch] = c[ch].clamp(0, 255);
+            }
+            let (snapped, val) = snap(c);
+            out.push(val);
+
+            if mode == DitherMode::FloydSteinberg{
+                let neighbours = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+                for (dx, dy, weight) in neighbours.iter(){
+                    let (nx, ny) = (x as isize + dx, y + dy);
+                    if nx < 0 || nx as usize >= w || ny*w + nx as usize >= px_cnt{
+                        continue;
+                    }
+                    for ch in 0..3{
+                        err[ny*w + nx as usize][ch] += (c[ch] - snapped[ch])*weight/16;
+                    }
+                }
+            }
+        }
+        return out
+    }
+
+    /// returns (packed 4 bit indices, 0x20 byte rgba16 palette), quantizing to 16 colors if needed
+    pub fn rgba32_to_ci4(rgba32 : &[u8])->(Vec<u8>, Vec<u8>){
+        let (indices, palette) = Texture::quantize(rgba32, 16);
+        return (Texture::pack_4bit(&indices), palette)
+    }
+
+    //two 4 bit values per byte, high nibble first
+    fn pack_4bit(vals : &[u8])->Vec<u8>{
+        return vals.chunks(2)
+            .map(|a|{(a[0] << 4) | a.get(1).unwrap_or(&0)})
+            .collect()
+    }
+
+    /// returns (one index per pixel, 0x200 byte rgba16 palette), quantizing to 256 colors if needed
+    pub fn rgba32_to_ci8(rgba32 : &[u8])->(Vec<u8>, Vec<u8>){
+        return Texture::quantize(rgba32, 256)
+    }
+
+    //palette of at most `max_colors` rgba16 entries (padded to max_colors), plus one index per pixel
+    //colors are kept exact when they already fit, otherwise reduced with median cut
+    fn quantize(rgba32 : &[u8], max_colors: usize)->(Vec<u8>, Vec<u8>){
+        let words : Vec<u16> = Texture::rgba32_to_rgba16(rgba32)
+            .chunks_exact(2)
+            .map(|a|{u16::from_be_bytes([a[0], a[1]])})
+            .collect();
+
+        //unique colors in order of first appearance, with pixel counts
+        let mut colors : Vec<(u16, usize)> = Vec::new();
+        for w in words.iter(){
+            match colors.iter_mut().find(|(c, _)| c == w){
+                Some((_, cnt)) => *cnt += 1,
+                None => colors.push((*w, 1)),
+            }
+        }
+
+        let palette : Vec<u16> = match colors.len() <= max_colors{
+            true => colors.iter().map(|(c, _)| *c).collect(),
+            false => Texture::median_cut(&colors, max_colors),
+        };
+
+        let mut pal_bytes : Vec<u8> = palette.iter().flat_map(|p| p.to_be_bytes()).collect();
+        pal_bytes.resize(2*max_colors, 0);
+        return (Texture::nearest_indices(&words, &palette), pal_bytes)
+    }
+
+    /// index of the closest entry of an existing rgba16 palette for every pixel
+    pub fn map_to_palette(rgba32 : &[u8], palette : &[u8])->Vec<u8>{
+        let words : Vec<u16> = Texture::rgba32_to_rgba16(rgba32)
+            .chunks_exact(2)
+            .map(|a|{u16::from_be_bytes([a[0], a[1]])})
+            .collect();
+        let palette : Vec<u16> = palette.chunks_exact(2).map(|a|{u16::from_be_bytes([a[0], a[1]])}).collect();
+        return Texture::nearest_indices(&words, &palette)
+    }
+
+    fn nearest_indices(words: &[u16], palette: &[u16])->Vec<u8>{
+        return words.iter()
+            .map(|w|{
+                let (i, _) = palette.iter().enumerate()
+                    .min_by_key(|(_, p)| Texture::rgba16_distance(**p, *w))
+                    .unwrap();
+                i as u8
+            })
+            .collect()
+    }
+
+    pub fn palette(&self)->Option<&[u8]>{
+        return self.palette.as_deref()
+    }
+
+    /// writes an rgba16 palette as raw big endian words or as a 16 pixel wide swatch png
+    pub fn write_palette(palette: &[u8], path: &Path, format: PaletteFormat){
+        match format{
+            PaletteFormat::Raw => {
+                let mut pal_f = File::create(path).unwrap();
+                pal_f.write_all(palette).unwrap();
+            },
+            PaletteFormat::Swatch => {
+                let entries = palette.len()/2;
+                let w = std::cmp::min(entries, 16);
+                let mut rgba = Texture::rgba16_to_rgba32(palette);
+                rgba.resize(4*w*entries.div_ceil(w), 0);
+                write_png_rgba32(path, w, rgba.len()/(4*w), &rgba);
+            },
+        }
+    }
+
+    /// reads a palette written by `write_palette`, swatch pngs are recognized by extension
+    pub fn read_palette(path: &Path)->Result<Vec<u8>, String>{
+        match path.extension().and_then(|e| e.to_str()){
+            Some("png") => {
+                let (_, _, rgba) = read_png_rgba32(path)?;
+                Ok(Texture::rgba32_to_rgba16(&rgba))
+            },
+            _ => {
+                let pal = fs::read(path).map_err(|e| format!("could not open palette: {}", e))?;
+                match pal.len() % 2{
+                    0 => Ok(pal),
+                    _ => Err(String::from("raw palette has an odd number of bytes")),
+                }
+            },
+        }
+    }
+
+    fn rgba16_channels(c: u16)->[i32; 4]{
+        [((c >> 11) & 0x1F) as i32, ((c >> 6) & 0x1F) as i32, ((c >> 1) & 0x1F) as i32, ((c & 1) * 0x1F) as i32]
+    }
+
+    fn rgba16_distance(a: u16, b: u16)->i32{
+        let (a, b) = (Texture::rgba16_channels(a), Texture::rgba16_channels(b));
+        //never trade transparency for a closer color
+        let alpha_penalty = if a[3] != b[3] { 1 << 16 } else { 0 };
+        (0..3).map(|i| (a[i] - b[i])*(a[i] - b[i])).sum::<i32>() + alpha_penalty
+    }
+
+    fn median_cut(colors: &[(u16, usize)], max_colors: usize)->Vec<u16>{
+        let mut boxes : Vec<Vec<(u16, usize)>> = vec![colors.to_vec()];
+        while boxes.len() < max_colors{
+            //split the box with the widest channel range
+            let widest = boxes.iter().enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(i, b)|{
+                    let (axis, range) = (0..4).map(|ch|{
+                        let vals = b.iter().map(|(c, _)| Texture::rgba16_channels(*c)[ch]);
+                        (ch, vals.clone().max().unwrap() - vals.min().unwrap())
+                    })
+                    .max_by_key(|(_, r)| *r)
+                    .unwrap();
+                    (i, axis, range)
+                })
+                .max_by_key(|(_, _, range)| *range);
+            let (i, axis, _) = match widest{
+                Some(w) => w,
+                None => break,
+            };
+
+            let mut b = boxes.swap_remove(i);
+            b.sort_by_key(|(c, _)| Texture::rgba16_channels(*c)[axis]);
+            let total : usize = b.iter().map(|(_, n)| n).sum();
+            let mut acc = 0;
+            let mut split = b.iter().position(|(_, n)|{acc += n; 2*acc >= total}).unwrap() + 1;
+            if split >= b.len() { split = b.len() - 1; }
+            let upper = b.split_off(split);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        return boxes.iter()
+            .map(|b|{
+                let total : usize = b.iter().map(|(_, n)| n).sum();
+                let mut sum = [0usize; 4];
+                for (c, n) in b.iter(){
+                    let ch = Texture::rgba16_channels(*c);
+                    for i in 0..4 { sum[i] += ch[i] as usize * n; }
+                }
+                let avg : Vec<u16> = sum.iter().map(|s| ((s + total/2) / total) as u16).collect();
+                let alpha = (avg[3] >= 0x10) as u16;
+                (avg[0] << 11) | (avg[1] << 6) | (avg[2] << 1) | alpha
+            })
+            .collect()
+    }
+
+    pub fn ci4_to_rgba32(ci4 : &[u8], palatte: &[u8])->Vec<u8>{
+        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
+            .map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                [r32, g32, b32, a32]
+            })
+            .collect();
+
+        return ci4
+            .iter()
+            .flat_map(|a|{[a >> 4, a & 0xF]}) //cvt to ci8
+            .flat_map(|indx|{pal[indx as usize]})
+            .collect()
+    }
+    pub fn ci8_to_rgba32(ci8 : &[u8], palatte: &[u8])->Vec<u8>{
+        let pal : Vec<[u8; 4]> = palatte.chunks_exact(2)
+            .map(|a|{
+                let val = u16::from_be_bytes([a[0], a[1]]);
+                let r16 = ((val >> 11) & 0x1f) as u8;
+                let g16 = ((val >> 6) & 0x1f) as u8;
+                let b16 = ((val >> 1) & 0x1f) as u8;
+                let a16 = (val & 0x1) as u8;
+
+                let r32 = (r16 << 3) | (r16 >> 3);
+                let g32 = (g16 << 3) | (g16 >> 3);
+                let b32 = (b16 << 3) | (b16 >> 3);
+                let a32 = (((a16 << 7) as i8) >> 7) as u8;
+
+                [r32, g32, b32, a32]
+            })
+            .collect();
+
+        return ci8
+            .iter()
+            .flat_map(|indx|{pal[*indx as usize]})
+            .collect()
+    }
+
+    fn intensity(px : &[u8], mode: IntensityMode)->u8{
+        let (r, g, b) = (px[0] as u32, px[1] as u32, px[2] as u32);
+        return match mode{
+            IntensityMode::Luma => ((299*r + 587*g + 114*b + 500) / 1000) as u8,
+            IntensityMode::Average => ((r + g + b + 1) / 3) as u8,
+        }
+    }
+
+    //scale an 8 bit value down to `bits`, rounding to the nearest level
+    fn reduce_bits(val: u8, bits: u32)->u8{
+        let max = (1u32 << bits) - 1;
+        return ((val as u32 * max + 127) / 255) as u8
+    }
+
+    pub fn rgba32_to_i4(rgba32 : &[u8], mode: IntensityMode)->Vec<u8>{
+        let vals : Vec<u8> = rgba32.chunks_exact(4)
+            .map(|px|{Texture::reduce_bits(Texture::intensity(px, mode), 4)})
+            .collect();
+        return Texture::pack_4bit(&vals)
+    }
+
+    pub fn rgba32_to_i8(rgba32 : &[u8], mode: IntensityMode)->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{Texture::intensity(px, mode)})
+            .collect()
+    }
+
+    /// 3 bit intensity + 1 bit alpha per pixel, two pixels per byte
+    pub fn rgba32_to_ia4(rgba32 : &[u8], mode: IntensityMode)->Vec<u8>{
+        let vals : Vec<u8> = rgba32.chunks_exact(4)
+            .map(|px|{(Texture::reduce_bits(Texture::intensity(px, mode), 3) << 1) | (px[3] >= 0x80) as u8})
+            .collect();
+        return Texture::pack_4bit(&vals)
+    }
+
+    /// 4 bit intensity + 4 bit alpha per pixel
+    pub fn rgba32_to_ia8(rgba32 : &[u8], mode: IntensityMode)->Vec<u8>{
+        return rgba32.chunks_exact(4)
+            .map(|px|{(Texture::reduce_bits(Texture::intensity(px, mode), 4) << 4) | Texture::reduce_bits(px[3], 4)})
+            .collect()
+    }
+
+    pub fn i4_to_rgba32(i_4 : &[u8])->Vec<u8>{
+        return i_4.iter()
+            .flat_map(|a|{
+                let val1 = (a & 0xF0) | (a >> 4);
+                let val2 = (a << 4) | (a & 0xF);
+                [val1, val1, val1, 0xFF, val2, val2, val2, 0xFF]
+            })
+            .collect()
+    }
+
+    pub fn i8_to_rgba32(i_8 : &[u8])->Vec<u8>{
+        return i_8.iter()
+            .flat_map(|a|{
+                let val = *a;
+                [val, val, val, 0xFF]
+            })
+            .collect()
+    }
+
+    pub fn ia4_to_rgba32(ia4 : &[u8])->Vec<u8>{
+        return ia4
+            .iter()
+            .flat_map(|a|{
+                let i1 = (a & 0xE0) | (a >> 3) | (a >> 6);
+                let a1 = (((a << 3) as i8) >> 7) as u8;
+                let i2 = (a >> 1) & 0x7;
+                let i2 = (i2 << 5) | (i2 << 2) | (i2 >> 1);
+                let a2 = (((a << 7) as i8) >> 7) as u8;
+                [i1, i1, i1, a1, i2, i2, i2, a2]
+            })
+            .collect()
+    }
+
+    pub fn ia8_to_rgba32(ia8 : &[u8])->Vec<u8>{
+        return ia8
+            .iter()
+            .flat_map(|a|{
+                let val = (*a & 0xF0) | (*a >> 4);
+                let alpha = (*a << 4) | (*a & 0xF);
+                [val, val, val, alpha]
+            })
+            .collect()
+    }
+}
+
+struct SpriteChunk {
+    x : isize,
+    y : isize,
+    w : usize,
+    h : usize,
+    pub pixel_data : Vec<u8>,
+}
+
+impl SpriteChunk {
+    pub fn new(bin : &[u8], file_offset : &mut usize, format : &ImgFmt, frame_indx : usize, chunk_indx : usize)->Result<SpriteChunk, SpriteError>{
+        let truncated = |expected: usize, at: usize| SpriteError::Truncated{frame: frame_indx, chunk: Some(chunk_indx), expected: expected, available: bin.len().saturating_sub(at)};
+        if *file_offset + 8 > bin.len(){
+            return Err(truncated(8, *file_offset));
+        }
+        let chunk_bin = &bin[*file_offset..];
+        let x = i16::from_be_bytes([chunk_bin[0], chunk_bin[1]]) as isize;
+        let y = i16::from_be_bytes([chunk_bin[2], chunk_bin[3]]) as isize;
+        let w = u16::from_be_bytes([chunk_bin[4], chunk_bin[5]]) as usize;
+        let h = u16::from_be_bytes([chunk_bin[6], chunk_bin[7]]) as usize;
+        // println!("\t\t{:02X?}", &chunk_bin[..8]);
+        *file_offset += 8;
+        *file_offset = (*file_offset + (8 - 1)) & !(8 - 1); //align
+        let pxl_size : usize = match format{
+            ImgFmt::I4 | ImgFmt::IA4 | ImgFmt::CI4 => 4,
+            ImgFmt::I8 | ImgFmt::IA8 | ImgFmt::CI8 => 8,
+            ImgFmt::RGBA16 => 16,
+            ImgFmt::RGBA32 => 32,
+            _=> 0,
+        };
+        let data_size : usize = w*h*pxl_size/8;
+        if *file_offset + data_size > bin.len(){
+            return Err(truncated(data_size, *file_offset));
+        }
+
+        let data : Vec<u8> = bin[*file_offset .. *file_offset + data_size].to_vec();
+        *file_offset += data_size;
+
+        Ok(SpriteChunk{
+            x : x, 
+            y : y, 
+            w : w, 
+            h : h,
+            pixel_data : data, 
+        })
+    }
+
+    pub fn to_rgba32(&self, format : &ImgFmt, palette : &[u8])->Result<Vec<u8>, TextureError>{
+        return match format {
+            ImgFmt::CI4 | ImgFmt::CI8 => {
+                let palette = match palette.len(){
+                    0 => None,
+                    _ => Some(palette.to_vec()),
+                };
+                Texture{texture_type: *format, w: self.w, h: self.h, palette: palette, pixel_data: self.pixel_data.clone()}.to_rgba32()
+            },
+            ImgFmt::I4     => Ok(Texture::i4_to_rgba32(&self.pixel_data)),
+            ImgFmt::I8     => Ok(Texture::i8_to_rgba32(&self.pixel_data)),
+            ImgFmt::RGBA16 => Ok(Texture::rgba16_to_rgba32(&self.pixel_data)),
+            ImgFmt::RGBA32 => Ok(self.pixel_data.clone()),
+            ImgFmt::IA4    => Ok(Texture::ia4_to_rgba32(&self.pixel_data)),
+            ImgFmt::IA8    => Ok(Texture::ia8_to_rgba32(&self.pixel_data)),
+            ImgFmt::Unknown(_) => Err(TextureError::UnknownFormat(*format)),
+        }
+    }
+
+    pub fn header_bytes(&self)->Vec<u8>{
+        let mut out = (self.x as i16).to_be_bytes().to_vec();
+        out.extend_from_slice(&(self.y as i16).to_be_bytes());
+        out.extend_from_slice(&(self.w as u16).to_be_bytes());
+        out.extend_from_slice(&(self.h as u16).to_be_bytes());
+        return out;
+    }
+}
+
+//pads with 0 up to the next multiple of 8 in the file
+fn align_8(out: &mut Vec<u8>){
+    out.resize((out.len() + (8 - 1)) & !(8 - 1), 0);
+}
+
+/// the 0x14 byte header in front of every sprite frame
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct SpriteFrameHeader{
+    pub x: i16,
+    pub y: i16,
+    pub w: u16,
+    pub h: u16,
+    pub chunk_count: u16,
+    pub unk_a: u16,
+    pub unk_c: u16,
+    pub unk_e: u16,
+    pub unk_10: u16,
+    pub unk_12: u16,
+}
+
+impl SpriteFrameHeader{
+    pub fn from_bytes(bin: &[u8]) -> SpriteFrameHeader{
+        let word = |i: usize| u16::from_be_bytes([bin[i], bin[i + 1]]);
+        return SpriteFrameHeader{
+            x: word(0x0) as i16,
+            y: word(0x2) as i16,
+            w: word(0x4),
+            h: word(0x6),
+            chunk_count: word(0x8),
+            unk_a: word(0xA),
+            unk_c: word(0xC),
+            unk_e: word(0xE),
+            unk_10: word(0x10),
+            unk_12: word(0x12),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        return [self.x as u16, self.y as u16, self.w, self.h, self.chunk_count, self.unk_a, self.unk_c, self.unk_e, self.unk_10, self.unk_12]
+            .iter()
+            .flat_map(|w| w.to_be_bytes())
+            .collect()
+    }
+
+    pub fn unk(&self) -> [u16; 5]{
+        return [self.unk_a, self.unk_c, self.unk_e, self.unk_10, self.unk_12]
+    }
+}
+
+pub struct SpriteFrame {
+    w : usize,
+    h : usize,
+    pub header: SpriteFrameHeader,
+    pub chk_hdrs: Vec<Vec<u8>>,
+    palette : Option<Vec<u8>>,
+    pixel_data : Vec<u8>,
+    chunks : Vec<SpriteChunk>, //native pixel data, what to_bytes writes
+    offset : Option<usize>, //relative to the end of the frame offset table
+    raw : Option<Vec<u8>>, //bytes after the header up to the next frame as parsed, written as is while the frame is unedited
+}
+
+impl SpriteFrame {
+    pub fn new(bin : &[u8], file_offset : usize, format : &ImgFmt, frame_indx : usize)->Result<SpriteFrame, SpriteError>{
+        let truncated = |expected: usize, at: usize| SpriteError::Truncated{frame: frame_indx, chunk: None, expected: expected, available: bin.len().saturating_sub(at)};
+        if file_offset + 0x14 > bin.len(){
+            return Err(truncated(0x14, file_offset));
+        }
+        let header = SpriteFrameHeader::from_bytes(&bin[file_offset..file_offset+0x14]);
+        // println!("\t{:?}", &header);
+        let w = header.w as usize;
+        let h = header.h as usize;
+        let mut pxl_data : Vec<Vec<[u8;4]>> = vec![vec![[0; 4]; w]; h];
+        
+        let chunk_cnt = header.chunk_count;
+        let mut palette :Vec<u8> = Vec::new();
+
+        let mut offset = file_offset + 0x14;
+        let mut chunks : Vec<SpriteChunk> = Vec::new();
+        let mut chk_hdrs : Vec<Vec<u8>> = Vec::new();
+
+        //CI formats have their palette, 8 byte aligned, ahead of the chunks
+        let palette_size = match format{
+            ImgFmt::CI4 => 0x20,
+            ImgFmt::CI8 => 0x200,
+            _ => 0,
+        };
+        if palette_size != 0{
+            offset = (offset + (8 - 1)) & !(8 - 1) ; //align to 0x8
+            if offset + palette_size > bin.len(){
+                return Err(truncated(palette_size, offset));
+            }
+            palette  = bin[offset.. offset + palette_size].to_vec();
+            offset += palette_size;
+        }
+        //SpriteChunk::new sizes the pixel data for every known format
+        if !matches!(format, ImgFmt::Unknown(_)){
+            for i in 0..chunk_cnt{
+                chunks.push(SpriteChunk::new(bin, &mut offset, format, frame_indx, i as usize)?);
+                chk_hdrs.push(chunks.last().unwrap().header_bytes());
+            }
+        }
+
+        for chnk in chunks.iter().filter(|c| c.w != 0){
+            let raw_data = chnk.to_rgba32(format, &palette)?;
+
+            if(chunk_cnt) == 1{
+                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
+
+                for (j,row) in row_data.iter().enumerate(){
+                    for (i, pxl) in row.chunks_exact(4).enumerate(){
+                        let fx :isize = i as isize;
+                        let fy :isize = j as isize;
+                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
+                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
+                        }
+                    }
+                }
+            }
+            else{
+                let row_data : Vec<&[u8]> = raw_data.chunks_exact(4*chnk.w).collect();
+                for (j,row) in row_data.iter().enumerate(){
+                    for (i, pxl) in row.chunks_exact(4).enumerate(){
+                        let fx :isize = chnk.x + i as isize;
+                        let fy :isize = chnk.y + j as isize;
+                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
+                            pxl_data[fy as usize][fx as usize] = pxl.try_into().unwrap();
+                        }
+                    }
+                }
+            }
+        }
+
+        let pal = match format{
+            ImgFmt::CI4 | ImgFmt::CI8 => Some(palette),
+            _ => None,
+        };
+
+        let raw = bin[file_offset + 0x14 .. offset].to_vec();
+        Ok(SpriteFrame{w: w,h: h, header: header, chk_hdrs:chk_hdrs, palette : pal, pixel_data: pxl_data.into_iter().flatten().flatten().collect(), chunks: chunks, offset: None, raw: Some(raw)})
+    }
+
+    /// single chunk frame encoded from 8 bit rgba pixels
+    pub fn from_rgba32(format: ImgFmt, w: usize, h: usize, rgba: &[u8]) -> Result<SpriteFrame, SpriteError>{
+        return SpriteFrame::from_rgba32_chunks(format, w, h, rgba, &[ChunkDescriptor{x: 0, y: 0, w: w, h: h}])
+    }
+
+    /// slices the frame back into the given chunk rectangles before encoding them.
+    /// like `new`, a lone chunk is taken from the frame origin regardless of its x/y
+    pub fn from_rgba32_chunks(format: ImgFmt, w: usize, h: usize, rgba: &[u8], rects: &[ChunkDescriptor]) -> Result<SpriteFrame, SpriteError>{
+        return SpriteFrame::from_rgba32_chunks_with(format, w, h, rgba, rects, &EncodeOptions::default())
+    }
+
+    pub fn from_rgba32_chunks_with(format: ImgFmt, w: usize, h: usize, rgba: &[u8], rects: &[ChunkDescriptor], options: &EncodeOptions) -> Result<SpriteFrame, SpriteError>{
+        //snapped once for the whole frame so the warning isn't repeated per chunk
+        let snapped = Texture::collapse_alpha(format, rgba, options.alpha_threshold);
+        let rgba : &[u8] = &snapped;
+        let chunk_pxls = SpriteFrame::slice_chunks(w, h, rgba, 4, rects);
+
+        //every chunk of a CI frame shares one palette
+        let (natives, palette) : (Vec<Vec<u8>>, Option<Vec<u8>>) = match format{
+            ImgFmt::CI4 | ImgFmt::CI8 => {
+                let max_colors = if format == ImgFmt::CI4 {16} else {256};
+                let (indices, pal) = Texture::quantize(&chunk_pxls.concat(), max_colors);
+                let mut rest : &[u8] = &indices;
+                let natives = rects.iter()
+                    .map(|r|{
+                        let (these, next) = rest.split_at(r.w*r.h);
+                        rest = next;
+                        match format{
+                            ImgFmt::CI4 => Texture::pack_4bit(these),
+                            _ => these.to_vec(),
+                        }
+                    })
+                    .collect();
+                (natives, Some(pal))
+            },
+            _ => {
+                let natives = rects.iter().zip(chunk_pxls.iter())
+                    .map(|(r, px)|{Texture::from_rgba32_with(format, r.w, r.h, px, options).map(|t| t.pixel_data)})
+                    .collect::<Result<Vec<Vec<u8>>, TextureError>>()?;
+                (natives, None)
+            },
+        };
+
+        let chunks : Vec<SpriteChunk> = rects.iter().zip(natives)
+            .map(|(r, native)|{SpriteChunk{x: r.x, y: r.y, w: r.w, h: r.h, pixel_data: native}})
+            .collect();
+        let chk_hdrs = chunks.iter().map(|c| c.header_bytes()).collect();
+        Ok(SpriteFrame{w: w, h: h, header: SpriteFrameHeader::default(), chk_hdrs: chk_hdrs, palette: palette, pixel_data: rgba.to_vec(), chunks: chunks, offset: None, raw: None})
+    }
+
+    /// CI frame built from palette indices as they are, no quantization.
+    /// `palette` is rgba16 and gets padded to the size the format expects
+    pub fn from_indexed_chunks(format: ImgFmt, w: usize, h: usize, indices: &[u8], palette: &[u8], rects: &[ChunkDescriptor]) -> Result<SpriteFrame, SpriteError>{
+        let pal_size = match format{
+            ImgFmt::CI4 => 0x20,
+            ImgFmt::CI8 => 0x200,
+            _ => return Err(SpriteError::UnsupportedFormat(format)),
+        };
+        let mut palette = palette.to_vec();
+        palette.resize(pal_size, 0);
+
+        let chunks : Vec<SpriteChunk> = rects.iter().zip(SpriteFrame::slice_chunks(w, h, indices, 1, rects))
+            .map(|(r, these)|{
+                let native = match format{
+                    ImgFmt::CI4 => Texture::pack_4bit(&these),
+                    _ => these,
+                };
+                SpriteChunk{x: r.x, y: r.y, w: r.w, h: r.h, pixel_data: native}
+            })
+            .collect();
+        let rgba = indices.iter()
+            .flat_map(|i|{let k = 2*(*i as usize); Texture::rgba16_to_rgba32(&palette[k..k+2])})
+            .collect();
+        let chk_hdrs = chunks.iter().map(|c| c.header_bytes()).collect();
+        Ok(SpriteFrame{w: w, h: h, header: SpriteFrameHeader::default(), chk_hdrs: chk_hdrs, palette: Some(palette), pixel_data: rgba, chunks: chunks, offset: None, raw: None})
+    }
+
+    //cuts `px_size` byte pixels out of the frame for each chunk, like `new` a lone chunk sits at the origin
+    fn slice_chunks(w: usize, h: usize, data: &[u8], px_size: usize, rects: &[ChunkDescriptor]) -> Vec<Vec<u8>>{
+        return rects.iter()
+            .map(|r|{
+                let (ox, oy) = if rects.len() == 1 {(0, 0)} else {(r.x, r.y)};
+                let mut pxls : Vec<u8> = Vec::with_capacity(px_size*r.w*r.h);
+                for j in 0..r.h as isize{
+                    for i in 0..r.w as isize{
+                        let (fx, fy) = (ox + i, oy + j);
+                        if (0 <= fx) && (fx < (w as isize)) && (0 <= fy) && (fy < (h as isize)){
+                            let k = px_size*(fy as usize*w + fx as usize);
+                            pxls.extend_from_slice(&data[k..k+px_size]);
+                        }
+                        else{
+                            pxls.resize(pxls.len() + px_size, 0);
+                        }
+                    }
+                }
+                pxls
+            })
+            .collect()
+    }
+
+    pub fn width(&self) -> usize{
+        return self.w
+    }
+
+    pub fn height(&self) -> usize{
+        return self.h
+    }
+
+    /// decoded pixels of the whole frame, row major
+    pub fn rgba32(&self) -> &[u8]{
+        return &self.pixel_data
+    }
+
+    pub fn offset(&self) -> Option<usize>{
+        return self.offset
+    }
+
+    pub fn palette(&self) -> Option<&[u8]>{
+        return self.palette.as_deref()
+    }
+
+    pub fn export_palette(&self, path: &Path, format: PaletteFormat) -> Result<(), SpriteError>{
+        match &self.palette{
+            Some(palette) => Texture::write_palette(palette, path, format),
+            None => return Err(SpriteError::NoPalette),
+        }
+        return Ok(())
+    }
+
+    /// palette index of every pixel in the frame, None for non CI frames
+    pub fn indices(&self) -> Option<Vec<u8>>{
+        let pal_cnt = self.palette.as_ref()?.len()/2;
+        let mut out = vec![0; self.w*self.h];
+        for chnk in self.chunks.iter(){
+            let chnk_indices : Vec<u8> = match pal_cnt{
+                16 => chnk.pixel_data.iter().flat_map(|a|{[a >> 4, a & 0xF]}).collect(),
+                _ => chnk.pixel_data.clone(),
+            };
+            let (ox, oy) = if self.chunks.len() == 1 {(0, 0)} else {(chnk.x, chnk.y)};
+            for (j, row) in chnk_indices.chunks_exact(chnk.w).take(chnk.h).enumerate(){
+                for (i, indx) in row.iter().enumerate(){
+                    let (fx, fy) = (ox + i as isize, oy + j as isize);
+                    if (0 <= fx) && (fx < (self.w as isize)) && (0 <= fy) && (fy < (self.h as isize)){
+                        out[fy as usize*self.w + fx as usize] = *indx;
+                    }
+                }
+            }
+        }
+        return Some(out)
+    }
+
+    //`keep_raw` writes an unedited frame back as it was parsed, gaps and padding included
+    fn write_bytes(&self, out: &mut Vec<u8>, keep_raw: bool){
+        let header = SpriteFrameHeader{w: self.w as u16, h: self.h as u16, chunk_count: self.chunks.len() as u16, ..self.header};
+        out.append(&mut header.to_bytes());
+        if let (true, Some(raw)) = (keep_raw, &self.raw){
+            out.extend_from_slice(raw);
+            return;
+        }
+
+        if let Some(pal) = &self.palette{
+            align_8(out);
+            out.extend_from_slice(pal);
+        }
+        for chunk in self.chunks.iter(){
+            out.append(&mut chunk.header_bytes());
+            align_8(out);
+            out.extend_from_slice(&chunk.pixel_data);
+        }
+    }
+
+    //same size, chunks and pixels, the descriptor header words are not compared.
+    //frames quantized from rgba get a palette of their own so only the colors have to match
+    fn same_image(&self, other: &SpriteFrame, quantized: bool) -> bool{
+        let rects = |f: &SpriteFrame| f.chunks.iter().map(|c| (c.x, c.y, c.w, c.h)).collect::<Vec<_>>();
+        let colors_match = quantized || (self.indices() == other.indices() && self.palette == other.palette);
+        return (self.w, self.h) == (other.w, other.h) && rects(self) == rects(other) && self.pixel_data == other.pixel_data && colors_match
+    }
+
+    /// whether encoding this parsed frame from its own pixels gives back its raw bytes, with zeros after them.
+    /// chunk pixels outside the frame and nonzero padding are what usually gets lost.
+    /// `start` is where the frame header sat in the file, None for a headerless sprite
+    fn reencodes_exactly(&self, format: ImgFmt, start: Option<usize>, options: &EncodeOptions) -> Result<bool, SpriteError>{
+        let raw = match &self.raw{
+            Some(raw) => raw,
+            None => return Ok(true),
+        };
+        let rects : Vec<ChunkDescriptor> = self.chunks.iter().map(|c| ChunkDescriptor{x: c.x, y: c.y, w: c.w, h: c.h}).collect();
+        let again = match (self.indices(), &self.palette){
+            (Some(indices), Some(palette)) => SpriteFrame::from_indexed_chunks(format, self.w, self.h, &indices, palette, &rects)?,
+            _ => SpriteFrame::from_rgba32_chunks_with(format, self.w, self.h, &self.pixel_data, &rects, options)?,
+        };
+        let encoded = match start{
+            Some(start) => {
+                let mut out = vec![0; start];
+                again.write_bytes(&mut out, false);
+                out.split_off(start + 0x14)
+            },
+            None => again.chunks.first().map(|c| c.pixel_data.clone()).unwrap_or_default(),
+        };
+        return Ok(raw.starts_with(&encoded) && raw[encoded.len()..].iter().all(|b| *b == 0))
+    }
+}
+
+pub struct Sprite{
+    format: ImgFmt,
+    pub frame: Vec<SpriteFrame>,
+    header: Vec<u8>, //everything before the frame offset table
+    bytes: Vec<u8>,
+    size: usize, //original length including padding
+    reproduction: Reproduction,
+    alpha_threshold: u8, //used when rebuilding from pngs
+}
+
+impl Sprite{
+    pub fn format(&self) -> ImgFmt{
+        return self.format
+    }
+
+    /// everything before the frame offset table
+    pub fn header(&self) -> &[u8]{
+        return &self.header
+    }
+
+    /// sprites that fail to parse are kept as raw bytes with an unknown format
+    pub fn from_bytes(in_bytes: &[u8])->Sprite{
+        return match Sprite::parse(in_bytes){
+            Ok(sprite) => sprite,
+            Err(e) => {
+                eprintln!("warning: {}, keeping sprite as binary", e);
+                let format = match in_bytes.len(){
+                    0..=3 => 0,
+                    _ => u16::from_be_bytes([in_bytes[2], in_bytes[3]]),
+                };
+                Sprite{format: ImgFmt::Unknown(format), frame: Vec::new(), header: Vec::new(), bytes: in_bytes.to_vec(), size: in_bytes.len(), reproduction: Reproduction::Exact, alpha_threshold: 0x80}
+            },
+        }
+    }
+
+    pub fn parse(in_bytes: &[u8])->Result<Sprite, SpriteError>{
+        if in_bytes.len() < 0x10{
+            return Err(SpriteError::TruncatedHeader{expected: 0x10, available: in_bytes.len()});
+        }
+        let frame_cnt = u16::from_be_bytes([in_bytes[0], in_bytes[1]]);
+        let format = u16::from_be_bytes([in_bytes[2], in_bytes[3]]);
+        let frmt = match format{
+            0x0001 => ImgFmt::CI4,
+            0x0004 => ImgFmt::CI8,
+            0x0020 => ImgFmt::I4,
+            0x0040 => ImgFmt::I8,
+            0x0080 => ImgFmt::IA4,
+            0x0100 => ImgFmt::IA8,
+            0x0400 => ImgFmt::RGBA16,
+            0x0800 => ImgFmt::RGBA32,
+            _ => ImgFmt::Unknown(format),
+        };
+        if let ImgFmt::Unknown(_) = frmt{
+            return Ok(Sprite{format: frmt, frame: Vec::new(), header: Vec::new(), bytes: in_bytes.to_vec(), size: in_bytes.len(), reproduction: Reproduction::Exact, alpha_threshold: 0x80})
+        }
+
+        //headerless single chunk, there is nowhere to keep a palette
+        if frame_cnt > 0x100{
+            if let ImgFmt::CI4 | ImgFmt::CI8 = frmt{
+                return Err(SpriteError::UnsupportedFormat(frmt));
+            }
+            let mut offset = 8_usize;
+            let chunk = SpriteChunk::new(in_bytes, &mut offset, &frmt, 0, 0)?;
+            let pixel_data = chunk.to_rgba32(&frmt, &[])?;
+            //the raw bytes of a headerless sprite start at its pixels
+            let frame = SpriteFrame{w:chunk.w, h:chunk.h, header: SpriteFrameHeader::default(), chk_hdrs: vec![in_bytes[8..16].to_vec()], palette: None, pixel_data: pixel_data, chunks: vec![chunk], offset: None, raw: Some(in_bytes[16..].to_vec())};
+            return Ok(Sprite{format: frmt, frame: vec![frame], header: in_bytes[..8].to_vec(), bytes: in_bytes.to_vec(), size: in_bytes.len(), reproduction: Reproduction::Exact, alpha_threshold: 0x80});
+        }
+        if 0x10 + 4*frame_cnt as usize > in_bytes.len(){
+            return Err(SpriteError::TruncatedHeader{expected: 0x10 + 4*frame_cnt as usize, available: in_bytes.len()});
+        }
+        // println!("{:02X?}", &in_bytes[..0x10]);
+        let mut frames : Vec<SpriteFrame>= in_bytes[0x10..]
+                .chunks_exact(0x4)
+                .take(frame_cnt as usize)
+                .enumerate()
+                .map(|(i, a)|{
+                    let offset = u32::from_be_bytes(a.try_into().unwrap());
+                    let mut frame = SpriteFrame::new(in_bytes, 0x10 + offset as usize + 4*frame_cnt as usize, &frmt, i)?;
+                    frame.offset = Some(offset as usize);
+                    Ok(frame)
+                })
+                .collect::<Result<Vec<SpriteFrame>, SpriteError>>()?; 
+
+        //whatever sits between a frame and the next one, or the end of the file, stays with the frame
+        let data_start = 0x10 + 4*frame_cnt as usize;
+        let starts : Vec<usize> = frames.iter().map(|f| data_start + f.offset.unwrap()).collect();
+        for (frame, start) in frames.iter_mut().zip(starts.iter()){
+            let next = starts.iter().cloned().filter(|s| s > start).min().unwrap_or(in_bytes.len());
+            let raw = frame.raw.as_mut().unwrap();
+            let parsed_end = start + 0x14 + raw.len();
+            if parsed_end <= next{
+                raw.extend_from_slice(&in_bytes[parsed_end..next]);
+            }
+        }
+        return Ok(Sprite{format: frmt, frame: frames, header: in_bytes[..0x10].to_vec(), bytes: in_bytes.to_vec(), size: in_bytes.len(), reproduction: Reproduction::Exact, alpha_threshold: 0x80});
+    }
+
+    /// rebuilds a sprite from a .sprite.yaml/.sprite.toml descriptor and the frame pngs it lists
+    pub fn read(path: &Path) -> Result<Sprite, SpriteError>{
+        let descriptor = SpriteDescriptor::read(path)?;
+        let desc_dir = path.parent().unwrap();
+
+        let sheet = match &descriptor.sheet{
+            Some(sheet) => Some(read_png_rgba32(&desc_dir.join(&sheet.file))
+                .map_err(|e| SpriteError::BadFramePng{index: 0, file: sheet.file.clone(), reason: e})?),
+            None => None,
+        };
+
+        let max_colors = match descriptor.format{
+            ImgFmt::CI4 => 16,
+            ImgFmt::CI8 => 256,
+            _ => 0,
+        };
+
+        //the bin the descriptor was extracted with, unedited frames are copied from it so bytes the pngs can't hold survive
+        let original = descriptor.bin.as_ref()
+            .and_then(|bin| fs::read(desc_dir.join(bin)).ok())
+            .and_then(|bytes| Sprite::parse(&bytes).ok())
+            .filter(|o| o.format == descriptor.format && o.header.len() == descriptor.header.len());
+        let old_start = |o: &Sprite, f: &SpriteFrame| match o.header.len(){
+            8 => None,
+            _ => Some(0x10 + 4*o.frame.len() + f.offset.unwrap_or(0)),
+        };
+
+        let encode = EncodeOptions{alpha_threshold: descriptor.alpha_threshold, ..EncodeOptions::default()};
+        let mut frames : Vec<SpriteFrame> = Vec::new();
+        for (i, frame_desc) in descriptor.frames.iter().enumerate(){
+            let rects = match frame_desc.chunks.is_empty(){
+                true => vec![ChunkDescriptor{x: 0, y: 0, w: frame_desc.w, h: frame_desc.h}],
+                false => frame_desc.chunks.clone(),
+            };
+
+            //indexed pngs that fit the palette are taken as is
+            let indexed = match (max_colors, &sheet){
+                (0, _) | (_, Some(_)) => None,
+                _ => read_png_indexed(&desc_dir.join(&frame_desc.file))
+                    .map_err(|e| SpriteError::BadFramePng{index: i, file: frame_desc.file.clone(), reason: e})?
+                    .filter(|(_, _, indices, palette)| palette.len() <= 2*max_colors && indices.iter().all(|x| (*x as usize) < max_colors)),
+            };
+            let (w, h, rgba) = match (&indexed, &sheet, &descriptor.sheet){
+                (Some((w, h, _, _)), _, _) => (*w, *h, Vec::new()),
+                (None, Some((sheet_w, sheet_h, sheet_rgba)), Some(layout)) => {
+                    let (cell_x, cell_y) = (layout.cell_w*(i % layout.columns), layout.cell_h*(i / layout.columns));
+                    if cell_x + frame_desc.w > *sheet_w || cell_y + frame_desc.h > *sheet_h{
+                        return Err(SpriteError::BadFramePng{index: i, file: layout.file.clone(), reason: format!("sheet is too small to hold frame {}", i)});
+                    }
+                    let rgba : Vec<u8> = (cell_y..cell_y + frame_desc.h)
+                        .flat_map(|row|{
+                            let start = 4*(row*sheet_w + cell_x);
+                            &sheet_rgba[start..start + 4*frame_desc.w]
+                        })
+                        .cloned()
+                        .collect();
+                    (frame_desc.w, frame_desc.h, rgba)
+                },
+                _ => read_png_rgba32(&desc_dir.join(&frame_desc.file))
+                    .map_err(|e| SpriteError::BadFramePng{index: i, file: frame_desc.file.clone(), reason: e})?,
+            };
+            if (w, h) != (frame_desc.w, frame_desc.h){
+                return Err(SpriteError::FrameSizeMismatch{index: i, file: frame_desc.file.clone(), expected: (frame_desc.w, frame_desc.h), found: (w, h)});
+            }
+            //flipping is its own inverse
+            let rgba = descriptor.orientation.apply(&rgba, 4*w);
+            let indexed = indexed.map(|(w, h, indices, palette)| (w, h, descriptor.orientation.apply(&indices, w), palette));
+            let ext_palette = match &frame_desc.palette{
+                None => None,
+                Some(pal_file) => {
+                    let bad_palette = |reason: String| SpriteError::BadPalette{index: i, file: pal_file.clone(), reason: reason};
+                    let palette = Texture::read_palette(&desc_dir.join(pal_file)).map_err(bad_palette)?;
+                    if max_colors == 0{
+                        return Err(bad_palette(format!("{:?} sprites have no palette", descriptor.format)));
+                    }
+                    if palette.len() > 2*max_colors{
+                        return Err(bad_palette(format!("{} colors do not fit a {:?} palette", palette.len()/2, descriptor.format)));
+                    }
+                    Some(palette)
+                },
+            };
+            let quantized = max_colors != 0 && indexed.is_none();
+            let quantized_rgba = quantized && ext_palette.is_none();
+            let frame = match (indexed, ext_palette){
+                (Some((_, _, indices, _)), Some(palette)) => SpriteFrame::from_indexed_chunks(descriptor.format, w, h, &indices, &palette, &rects)?,
+                (Some((_, _, indices, palette)), None) => SpriteFrame::from_indexed_chunks(descriptor.format, w, h, &indices, &palette, &rects)?,
+                (None, Some(palette)) => {
+                    let (rgba, _) = Texture::snap_alpha(&rgba, descriptor.alpha_threshold);
+                    SpriteFrame::from_indexed_chunks(descriptor.format, w, h, &Texture::map_to_palette(&rgba, &palette), &palette, &rects)?
+                },
+                (None, None) => SpriteFrame::from_rgba32_chunks_with(descriptor.format, w, h, &rgba, &rects, &encode)?,
+            };
+            let mut frame = match original.as_ref().and_then(|o| o.frame.get(i).map(|f| (o, f))){
+                Some((_, old)) if old.same_image(&frame, quantized) => SpriteFrame{
+                    header: old.header,
+                    chk_hdrs: old.chk_hdrs.clone(),
+                    palette: old.palette.clone(),
+                    pixel_data: old.pixel_data.clone(),
+                    chunks: old.chunks.iter().map(|c| SpriteChunk{x: c.x, y: c.y, w: c.w, h: c.h, pixel_data: c.pixel_data.clone()}).collect(),
+                    offset: old.offset,
+                    raw: old.raw.clone(),
+                    ..frame
+                },
+                Some((o, old)) => {
+                    descriptor.reproduction.require_exact(old.reencodes_exactly(descriptor.format, old_start(o, old), &encode)?, &format!("frame {} losing bytes its png can't hold", i))?;
+                    descriptor.reproduction.require_exact(!quantized_rgba, &format!("frame {} quantized from an rgba png", i))?;
+                    frame
+                },
+                None => frame, //nothing extracted to compare against
+            };
+            frame.header = frame_desc.header; //w, h and chunk count are filled in on write
+            frame.offset = frame_desc.offset;
+            frames.push(frame);
+        }
+        let out = Sprite{format: descriptor.format, frame: frames, header: descriptor.header, bytes: Vec::new(), size: descriptor.size, reproduction: descriptor.reproduction, alpha_threshold: descriptor.alpha_threshold};
+        //layout problems surface here instead of in to_bytes
+        out.try_to_bytes()?;
+        Ok(out)
+    }
+
+    /// swaps the palette of every CI frame, or only frame `frame_index`, the pixel indices are kept as is
+    pub fn remap_palette(&mut self, frame_index: Option<usize>, new_palette: &[u8]) -> Result<(), SpriteError>{
+        let pal_size = match self.format{
+            ImgFmt::CI4 => 0x20,
+            ImgFmt::CI8 => 0x200,
+            _ => return Err(SpriteError::UnsupportedFormat(self.format)),
+        };
+        if new_palette.len() != pal_size{
+            return Err(SpriteError::PaletteSize{expected: pal_size, found: new_palette.len()});
+        }
+        let frame_cnt = self.frame.len();
+        let targets : Vec<&mut SpriteFrame> = match frame_index{
+            Some(i) if i >= frame_cnt => return Err(SpriteError::NoFrame{index: i, count: frame_cnt}),
+            Some(i) => vec![&mut self.frame[i]],
+            None => self.frame.iter_mut().collect(),
+        };
+        for frame in targets{
+            let indices = frame.indices().ok_or(SpriteError::NoPalette)?;
+            frame.pixel_data = Texture::ci8_to_rgba32(&indices, new_palette);
+            frame.palette = Some(new_palette.to_vec());
+            frame.raw = None;
+        }
+        return Ok(())
+    }
+
+    /// re-encodes frame `index` from 8 bit rgba keeping its header, offset and chunk layout, the other frames are left alone
+    pub fn replace_frame(&mut self, index: usize, rgba: &[u8], w: usize, h: usize) -> Result<(), SpriteError>{
+        if let ImgFmt::Unknown(_) = self.format{
+            return Err(SpriteError::UnsupportedFormat(self.format));
+        }
+        if index >= self.frame.len(){
+            return Err(SpriteError::NoFrame{index: index, count: self.frame.len()});
+        }
+        let old = &self.frame[index];
+        if (w, h) != (old.w, old.h){
+            return Err(SpriteError::ReplacementSize{index: index, expected: (old.w, old.h), found: (w, h)});
+        }
+        let rects : Vec<ChunkDescriptor> = old.chunks.iter()
+            .map(|c|{ChunkDescriptor{x: c.x, y: c.y, w: c.w, h: c.h}})
+            .collect();
+        let encode = EncodeOptions{alpha_threshold: self.alpha_threshold, ..EncodeOptions::default()};
+        let mut frame = SpriteFrame::from_rgba32_chunks_with(self.format, w, h, rgba, &rects, &encode)?;
+        frame.header = old.header;
+        frame.offset = old.offset;
+        self.frame[index] = frame;
+        return Ok(())
+    }
+
+    /// structural checks on the sprite binary that extraction would otherwise silently work around
+    pub fn validate(&self) -> Vec<SpriteIssue>{
+        //sprites rebuilt from a descriptor have no original bytes
+        let bin = match self.bytes.is_empty(){
+            true => self.to_bytes(),
+            false => self.bytes.clone(),
+        };
+        let mut issues : Vec<SpriteIssue> = Vec::new();
+        if bin.len() < 0x10{
+            issues.push(SpriteIssue::TruncatedHeader{expected: 0x10, available: bin.len()});
+            return issues;
+        }
+        let frame_cnt = u16::from_be_bytes([bin[0], bin[1]]) as usize;
+        let code = u16::from_be_bytes([bin[2], bin[3]]);
+        let (pal_size, pxl_size) : (usize, usize) = match code{
+            0x0001 => (0x20, 4),
+            0x0004 => (0x200, 8),
+            0x0020 | 0x0080 => (0, 4),
+            0x0040 | 0x0100 => (0, 8),
+            0x0400 => (0, 16),
+            0x0800 => (0, 32),
+            _ => {
+                issues.push(SpriteIssue::UnknownFormat(code));
+                return issues;
+            },
+        };
+
+        //walks the chunks the way SpriteFrame::new does, returning the offset after the last one
+        let check_chunks = |issues: &mut Vec<SpriteIssue>, frame: usize, mut offset: usize, chunk_cnt: usize, frame_w: usize, frame_h: usize|{
+            let mut covered = 0;
+            for chunk in 0..chunk_cnt{
+                if offset + 8 > bin.len(){
+                    issues.push(SpriteIssue::ChunkOutOfRange{frame: frame, chunk: chunk, expected: 8, available: bin.len().saturating_sub(offset)});
+                    return;
+                }
+                let x = i16::from_be_bytes([bin[offset], bin[offset + 1]]) as isize;
+                let y = i16::from_be_bytes([bin[offset + 2], bin[offset + 3]]) as isize;
+                let w = u16::from_be_bytes([bin[offset + 4], bin[offset + 5]]) as usize;
+                let h = u16::from_be_bytes([bin[offset + 6], bin[offset + 7]]) as usize;
+                offset = (offset + 8 + (8 - 1)) & !(8 - 1);
+                let data_size = w*h*pxl_size/8;
+                if offset + data_size > bin.len(){
+                    issues.push(SpriteIssue::ChunkOutOfRange{frame: frame, chunk: chunk, expected: data_size, available: bin.len().saturating_sub(offset)});
+                    return;
+                }
+                offset += data_size;
+                covered += w*h;
+                //a lone chunk is drawn at the frame origin regardless of its x/y
+                let (x, y) = if chunk_cnt == 1 {(0, 0)} else {(x, y)};
+                if x < 0 || y < 0 || x as usize + w > frame_w || y as usize + h > frame_h{
+                    issues.push(SpriteIssue::ChunkOutsideFrame{frame: frame, chunk: chunk, x: x, y: y, w: w, h: h});
+                }
+            }
+            if covered != frame_w*frame_h{
+                issues.push(SpriteIssue::Coverage{frame: frame, frame_pixels: frame_w*frame_h, chunk_pixels: covered});
+            }
+        };
+
+        if frame_cnt > 0x100{
+            if pal_size != 0{
+                issues.push(SpriteIssue::UnknownFormat(code));
+                return issues;
+            }
+            let (w, h) = (u16::from_be_bytes([bin[12], bin[13]]) as usize, u16::from_be_bytes([bin[14], bin[15]]) as usize);
+            check_chunks(&mut issues, 0, 8, 1, w, h);
+            return issues;
+        }
+        if 0x10 + 4*frame_cnt > bin.len(){
+            issues.push(SpriteIssue::TruncatedHeader{expected: 0x10 + 4*frame_cnt, available: bin.len()});
+            return issues;
+        }
+        let data_start = 0x10 + 4*frame_cnt;
+        for frame in 0..frame_cnt{
+            let entry = &bin[0x10 + 4*frame..0x14 + 4*frame];
+            let offset = data_start + u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize;
+            if offset + 0x14 > bin.len(){
+                issues.push(SpriteIssue::FrameOutOfRange{frame: frame, offset: offset});
+                continue;
+            }
+            let header = SpriteFrameHeader::from_bytes(&bin[offset..offset + 0x14]);
+            let mut chunk_start = offset + 0x14;
+            if pal_size != 0{
+                chunk_start = ((chunk_start + (8 - 1)) & !(8 - 1)) + pal_size;
+                if chunk_start > bin.len(){
+                    issues.push(SpriteIssue::FrameOutOfRange{frame: frame, offset: offset});
+                    continue;
+                }
+            }
+            check_chunks(&mut issues, frame, chunk_start, header.chunk_count as usize, header.w as usize, header.h as usize);
+        }
+        return issues
+    }
+
+    fn format_code(format: ImgFmt) -> u16{
+        match format{
+            ImgFmt::CI4    => 0x0001,
+            ImgFmt::CI8    => 0x0004,
+            ImgFmt::I4     => 0x0020,
+            ImgFmt::I8     => 0x0040,
+            ImgFmt::IA4    => 0x0080,
+            ImgFmt::IA8    => 0x0100,
+            ImgFmt::RGBA16 => 0x0400,
+            ImgFmt::RGBA32 => 0x0800,
+            ImgFmt::Unknown(code) => code,
+        }
+    }
+
+    //clears frames left over from a previous extraction, leaves anything else in the folder alone
+    fn remove_frame_pngs(frame_dir: &Path){
+        for entry in fs::read_dir(frame_dir).unwrap(){
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap_or("");
+            let mut parts = name.split('.');
+            let is_frame = match (parts.next(), parts.next(), parts.next(), parts.next()){
+                (Some(indx), Some(_), Some("png"), None) |
+                (Some(indx), Some("pal"), None, None) => 
+                    indx == "sheet" || (!indx.is_empty() && indx.chars().all(|c| c.is_ascii_hexdigit())),
+                _ => false,
+            };
+            if is_frame && path.is_file(){
+                fs::remove_file(&path).unwrap();
+            }
+        }
+    }
+
+    //frame pngs are named relative to the folder containing the descriptor
+    fn descriptor(&self, base_name: &str, bin_name: Option<&str>, options: &SpriteWriteOptions) -> SpriteDescriptor{
+        let fmt_str = format!("{:?}", self.format).to_lowercase();
+        let sheet = match options.layout{
+            FrameLayout::Separate => None,
+            FrameLayout::Sheet{..} if self.frame.is_empty() => None,
+            FrameLayout::Sheet{columns} => {
+                let columns = match columns{
+                    0 => (1..).find(|c| c*c >= self.frame.len()).unwrap(),
+                    c => c,
+                };
+                Some(SheetDescriptor{
+                    file: format!("{}/sheet.{}.png", base_name, fmt_str),
+                    columns: columns,
+                    cell_w: self.frame.iter().map(|f| f.w).max().unwrap_or(0),
+                    cell_h: self.frame.iter().map(|f| f.h).max().unwrap_or(0),
+                })
+            },
+        };
+        let frames = self.frame.iter().enumerate()
+            .map(|(i, frame)|{
+                let chunks = frame.chunks.iter()
+                    .map(|c|{ChunkDescriptor{x: c.x, y: c.y, w: c.w, h: c.h}})
+                    .collect();
+                let file = match &sheet{
+                    Some(sheet) => sheet.file.clone(),
+                    None => format!("{}/{:02X}.{}.png", base_name, i, fmt_str),
+                };
+                let palette = match (options.palette, &frame.palette){
+                    (Some(PaletteFormat::Raw), Some(_)) => Some(format!("{}/{:02X}.pal", base_name, i)),
+                    (Some(PaletteFormat::Swatch), Some(_)) => Some(format!("{}/{:02X}.pal.png", base_name, i)),
+                    _ => None,
+                };
+                FrameDescriptor{file: file, w: frame.w, h: frame.h, header: frame.header, offset: frame.offset, chunks: chunks, palette: palette}
+            })
+            .collect();
+        SpriteDescriptor{format: self.format, bin: bin_name.map(String::from), header: self.header.clone(), size: self.size, sheet: sheet, orientation: options.orientation, frames: frames, reproduction: self.reproduction, alpha_threshold: self.alpha_threshold}
+    }
+
+    //all frames composited into one png, each in the top left corner of its cell
+    fn sheet_rgba32(&self, sheet: &SheetDescriptor, orientation: Orientation) -> (usize, usize, Vec<u8>){
+        let rows = self.frame.len().div_ceil(sheet.columns);
+        let (w, h) = (sheet.columns*sheet.cell_w, rows*sheet.cell_h);
+        let mut out = vec![0; 4*w*h];
+        for (i, frame) in self.frame.iter().enumerate(){
+            let (cell_x, cell_y) = (sheet.cell_w*(i % sheet.columns), sheet.cell_h*(i / sheet.columns));
+            let data = orientation.apply(&frame.pixel_data, 4*frame.w);
+            for (j, row) in data.chunks_exact(4*frame.w).enumerate(){
+                let start = 4*((cell_y + j)*w + cell_x);
+                out[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+        return (w, h, out)
+    }
+
+    pub fn write_with(&self, path: &Path, options: &SpriteWriteOptions){
+        //sprites that failed to parse only exist as a bin
+        let decoded = !matches!(self.format, ImgFmt::Unknown(_));
+        if options.mode != SpriteWriteMode::DecodedOnly || !decoded{
+            let mut bin_file = File::create(path).unwrap();
+            bin_file.write_all(&self.to_bytes()).unwrap();
+        }
+        if options.mode == SpriteWriteMode::BinOnly{
+            return;
+        }
+
+        //write descriptor yaml and folder containing frame pngs
+        let base_name = Path::new(path.file_stem().unwrap());
+        let new_base = Path::new(base_name.file_stem().unwrap());
+        let base_name = Path::new(new_base.file_stem().unwrap());
+        let base_path = path.parent().unwrap().join(base_name);
+        //construct prefers the toml, so a descriptor left in the other format would shadow this one
+        for format in [DescriptorFormat::Yaml, DescriptorFormat::Toml]{
+            let stale = base_path.with_extension(format.extension());
+            if format != options.descriptor && stale.is_file(){
+                fs::remove_file(&stale).unwrap();
+            }
+        }
+        let desc_path = base_path.with_extension(options.descriptor.extension());
+        let bin_name = match options.mode{
+            SpriteWriteMode::DecodedOnly => None,
+            _ => path.file_name().and_then(|n| n.to_str()),
+        };
+        let descriptor = self.descriptor(base_name.to_str().unwrap(), bin_name, options);
+        descriptor.write(&desc_path);
+        
+        DirBuilder::new().recursive(true).create(base_path.clone()).unwrap();
+        Sprite::remove_frame_pngs(&base_path);
+        for(frame, frame_file) in self.frame.iter().zip(descriptor.frames.iter()){
+            if let (Some(pal_file), Some(format)) = (&frame_file.palette, options.palette){
+                frame.export_palette(&path.parent().unwrap().join(pal_file), format).unwrap();
+            }
+        }
+        if let Some(sheet) = &descriptor.sheet{
+            let (w, h, data) = self.sheet_rgba32(sheet, options.orientation);
+            write_png_rgba32(&path.parent().unwrap().join(&sheet.file), w, h, &data);
+            return;
+        }
+        for(frame, frame_file) in self.frame.iter().zip(descriptor.frames.iter()){
+            let i_path = path.parent().unwrap().join(&frame_file.file);
+            match (frame.indices(), &frame.palette){
+                (Some(indices), Some(palette)) => write_png_indexed(&i_path, frame.w, frame.h, &options.orientation.apply(&indices, frame.w), palette),
+                _ => write_png_rgba32(&i_path, frame.w, frame.h, &options.orientation.apply(&frame.pixel_data, 4*frame.w)),
+            }
+        }
+    }
+}
+
+/// problems found by `Sprite::validate`, none of them stop extraction
+#[derive(PartialEq, Debug, Clone)]
+pub enum SpriteIssue{
+    UnknownFormat(u16),
+    TruncatedHeader{expected: usize, available: usize},
+    FrameOutOfRange{frame: usize, offset: usize},
+    ChunkOutOfRange{frame: usize, chunk: usize, expected: usize, available: usize},
+    ChunkOutsideFrame{frame: usize, chunk: usize, x: isize, y: isize, w: usize, h: usize},
+    Coverage{frame: usize, frame_pixels: usize, chunk_pixels: usize}, //chunks leave gaps or overlap
+}
+
+impl fmt::Display for SpriteIssue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            SpriteIssue::UnknownFormat(code) =>
+                write!(f, "unknown format 0x{:04X}", code),
+            SpriteIssue::TruncatedHeader{expected, available} =>
+                write!(f, "sprite header needs 0x{:X} bytes, only 0x{:X} available", expected, available),
+            SpriteIssue::FrameOutOfRange{frame, offset} =>
+                write!(f, "frame {} at 0x{:X} runs past the end of the sprite", frame, offset),
+            SpriteIssue::ChunkOutOfRange{frame, chunk, expected, available} =>
+                write!(f, "frame {} chunk {} needs 0x{:X} bytes, only 0x{:X} available", frame, chunk, expected, available),
+            SpriteIssue::ChunkOutsideFrame{frame, chunk, x, y, w, h} =>
+                write!(f, "frame {} chunk {} ({}, {}) {}x{} lies outside the frame", frame, chunk, x, y, w, h),
+            SpriteIssue::Coverage{frame, frame_pixels, chunk_pixels} =>
+                write!(f, "frame {} has {} pixels but its chunks cover {}", frame, frame_pixels, chunk_pixels),
+        }
+    }
+}
+
+/// how frames are laid out on disk
+#[derive(Clone, Copy)]
+pub enum FrameLayout{
+    Separate,
+    Sheet{columns: usize}, //0 picks a roughly square grid
+}
+
+/// row order of the pngs, frames are flipped as a whole so chunk layout is unaffected
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Orientation{
+    Native,       //rows in the order the game uploads them, top row first on screen
+    FlipVertical,
+}
+
+impl Orientation{
+    fn from_str(s: &str) -> Result<Orientation, String>{
+        match s{
+            "native" => Ok(Orientation::Native),
+            "flip_vertical" => Ok(Orientation::FlipVertical),
+            _ => Err(format!("unknown orientation {:?}, expected native or flip_vertical", s)),
+        }
+    }
+
+    fn apply(&self, data: &[u8], row_len: usize) -> Vec<u8>{
+        match (self, row_len){
+            (Orientation::FlipVertical, 1..) => data.rchunks_exact(row_len).flat_map(|a|{a.to_vec()}).collect(),
+            _ => data.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for Orientation{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Orientation::Native => write!(f, "native"),
+            Orientation::FlipVertical => write!(f, "flip_vertical"),
+        }
+    }
+}
+
+/// which representations `Sprite::write_with` puts on disk, the descriptor alone is enough to rebuild the bin
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum SpriteWriteMode{
+    BinOnly,
+    DecodedOnly, //descriptor and pngs
+    Both,
+}
+
+/// file the sprite descriptor is written as, both read back the same
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DescriptorFormat{
+    Yaml,
+    Toml,
+}
+
+impl DescriptorFormat{
+    fn extension(&self) -> &'static str{
+        match self{
+            DescriptorFormat::Yaml => "sprite.yaml",
+            DescriptorFormat::Toml => "sprite.toml",
+        }
+    }
+}
+
+pub struct SpriteWriteOptions{
+    pub layout: FrameLayout,
+    pub palette: Option<PaletteFormat>, //also write each CI frame's palette next to its png
+    pub orientation: Orientation,
+    pub mode: SpriteWriteMode,
+    pub descriptor: DescriptorFormat,
+}
+
+impl Default for SpriteWriteOptions{
+    fn default() -> SpriteWriteOptions{
+        SpriteWriteOptions{layout: FrameLayout::Separate, palette: None, orientation: Orientation::Native, mode: SpriteWriteMode::Both, descriptor: DescriptorFormat::Yaml}
+    }
+}
+
+impl SpriteWriteOptions{
+    pub fn sheet(mut self, columns: usize) -> SpriteWriteOptions{
+        self.layout = FrameLayout::Sheet{columns: columns};
+        return self
+    }
+
+    pub fn palette(mut self, format: PaletteFormat) -> SpriteWriteOptions{
+        self.palette = Some(format);
+        return self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> SpriteWriteOptions{
+        self.orientation = orientation;
+        return self
+    }
+
+    pub fn mode(mut self, mode: SpriteWriteMode) -> SpriteWriteOptions{
+        self.mode = mode;
+        return self
+    }
+
+    pub fn descriptor(mut self, format: DescriptorFormat) -> SpriteWriteOptions{
+        self.descriptor = format;
+        return self
+    }
+}
+
+#[derive(Debug)]
+pub enum SpriteError{
+    UnsupportedFormat(ImgFmt),
+    NoPalette,
+    BadPalette{index: usize, file: String, reason: String},
+    PaletteSize{expected: usize, found: usize},
+    NoFrame{index: usize, count: usize},
+    ReplacementSize{index: usize, expected: (usize, usize), found: (usize, usize)},
+    TruncatedHeader{expected: usize, available: usize},
+    Truncated{frame: usize, chunk: Option<usize>, expected: usize, available: usize},
+    BadFramePng{index: usize, file: String, reason: String},
+    FrameSizeMismatch{index: usize, file: String, expected: (usize, usize), found: (usize, usize)},
+    Texture(TextureError),
+    Descriptor(ReadError),
+    NotReproducible(NotReproducible),
+}
+
+impl From<TextureError> for SpriteError{
+    fn from(e: TextureError) -> SpriteError{
+        SpriteError::Texture(e)
+    }
+}
+
+impl From<ReadError> for SpriteError{
+    fn from(e: ReadError) -> SpriteError{
+        SpriteError::Descriptor(e)
+    }
+}
+
+impl From<NotReproducible> for SpriteError{
+    fn from(e: NotReproducible) -> SpriteError{
+        SpriteError::NotReproducible(e)
+    }
+}
+
+impl fmt::Display for SpriteError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            SpriteError::UnsupportedFormat(format) =>
+                write!(f, "{:?} is not supported for this sprite", format),
+            SpriteError::NoPalette =>
+                write!(f, "only CI frames have a palette"),
+            SpriteError::PaletteSize{expected, found} =>
+                write!(f, "expected a 0x{:X} byte palette, got 0x{:X} bytes", expected, found),
+            SpriteError::NoFrame{index, count} =>
+                write!(f, "frame {} does not exist, sprite has {} frames", index, count),
+            SpriteError::ReplacementSize{index, expected, found} =>
+                write!(f, "frame {} is {}x{}, replacement is {}x{}", index, expected.0, expected.1, found.0, found.1),
+            SpriteError::TruncatedHeader{expected, available} =>
+                write!(f, "sprite header needs 0x{:X} bytes, only 0x{:X} available", expected, available),
+            SpriteError::Truncated{frame, chunk: None, expected, available} =>
+                write!(f, "frame {} needs 0x{:X} more bytes, only 0x{:X} available", frame, expected, available),
+            SpriteError::Truncated{frame, chunk: Some(chunk), expected, available} =>
+                write!(f, "frame {} chunk {} needs 0x{:X} more bytes, only 0x{:X} available", frame, chunk, expected, available),
+            SpriteError::BadPalette{index, file, reason} =>
+                write!(f, "frame {} palette ({}): {}", index, file, reason),
+            SpriteError::BadFramePng{index, file, reason} =>
+                write!(f, "frame {} ({}): {}", index, file, reason),
+            SpriteError::FrameSizeMismatch{index, file, expected, found} =>
+                write!(f, "frame {} ({}): expected {}x{} pixels, png is {}x{}", index, file, expected.0, expected.1, found.0, found.1),
+            SpriteError::Texture(e) =>
+                write!(f, "{}", e),
+            SpriteError::Descriptor(e) =>
+                write!(f, "{}", e),
+            SpriteError::NotReproducible(e) =>
+                write!(f, "{}", e),
+        }
+    }
+}
+
+//a png in memory, for formats that embed their images
+fn png_rgba32(w: usize, h: usize, data: &[u8]) -> Vec<u8>{
+    let mut out : Vec<u8> = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, w as u32, h as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+    }
+    return out
+}
+
+fn write_png_rgba32(path: &Path, w: usize, h: usize, data: &[u8]){
+    let texture_f = File::create(path).unwrap();
+    let w_buf = &mut BufWriter::new(texture_f);
+
+    let mut encoder = png::Encoder::new(w_buf, w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(data).unwrap(); // Save
+}
+
+//palette is written as PLTE + tRNS so the original indices survive
+fn write_png_indexed(path: &Path, w: usize, h: usize, indices: &[u8], palette: &[u8]){
+    let pal_rgba = Texture::rgba16_to_rgba32(palette);
+    let plte : Vec<u8> = pal_rgba.chunks_exact(4).flat_map(|c| c[..3].to_vec()).collect();
+    let trns : Vec<u8> = pal_rgba.chunks_exact(4).map(|c| c[3]).collect();
+
+    let texture_f = File::create(path).unwrap();
+    let w_buf = &mut BufWriter::new(texture_f);
+
+    let mut encoder = png::Encoder::new(w_buf, w as u32, h as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(plte);
+    encoder.set_trns(trns);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(indices).unwrap(); // Save
+}
+
+//w, h, one index per pixel and the rgba16 palette
+type IndexedPng = (usize, usize, Vec<u8>, Vec<u8>);
+
+//returns the indexed image, or None if the png is not indexed
+fn read_png_indexed(path: &Path) -> Result<Option<IndexedPng>, String>{
+    let png_f = File::open(path).map_err(|e| format!("could not open png: {}", e))?;
+    let decoder = png::Decoder::new(png_f);
+    let mut reader = decoder.read_info().map_err(|e| format!("could not decode png: {}", e))?;
+    if reader.info().color_type != png::ColorType::Indexed{
+        return Ok(None);
+    }
+    let plte = reader.info().palette.as_ref().map(|p| p.to_vec()).unwrap_or_default();
+    let trns = reader.info().trns.as_ref().map(|t| t.to_vec()).unwrap_or_default();
+    let pal_rgba : Vec<u8> = plte.chunks_exact(3).enumerate()
+        .flat_map(|(i, c)|{[c[0], c[1], c[2], *trns.get(i).unwrap_or(&0xFF)]})
+        .collect();
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| format!("could not decode png: {}", e))?;
+    let (w, h) = (info.width as usize, info.height as usize);
+    let bits = info.bit_depth as usize;
+    let indices : Vec<u8> = buf[..info.buffer_size()].chunks_exact(info.line_size)
+        .flat_map(|row|{
+            (0..w).map(move |x|{
+                let bit = x*bits;
+                (row[bit / 8] >> (8 - bits - bit % 8)) & ((1u16 << bits) - 1) as u8
+            })
+        })
+        .take(w*h)
+        .collect();
+    Ok(Some((w, h, indices, Texture::rgba32_to_rgba16(&pal_rgba))))
+}
+
+//returns w, h and 8 bit rgba pixels of any non-animated png
+fn read_png_rgba32(path: &Path) -> Result<(usize, usize, Vec<u8>), String>{
+    let png_f = File::open(path).map_err(|e| format!("could not open png: {}", e))?;
+    let mut decoder = png::Decoder::new(png_f);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| format!("could not decode png: {}", e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| format!("could not decode png: {}", e))?;
+    let buf = &buf[..info.buffer_size()];
+
+    let rgba : Vec<u8> = match info.color_type{
+        png::ColorType::Rgba => buf.to_vec(),
+        png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p|{[p[0], p[1], p[2], 0xFF]}).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p|{[p[0], p[0], p[0], p[1]]}).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|p|{[*p, *p, *p, 0xFF]}).collect(),
+        png::ColorType::Indexed => return Err(String::from("unexpanded indexed png")),
+    };
+    Ok((info.width as usize, info.height as usize, rgba))
+}
+
+#[derive(Clone, Copy)]
+pub struct ChunkDescriptor{
+    pub x: isize,
+    pub y: isize,
+    pub w: usize,
+    pub h: usize,
+}
+
+struct FrameDescriptor{
+    file: String,
+    w: usize,
+    h: usize,
+    header: SpriteFrameHeader,
+    offset: Option<usize>,
+    chunks: Vec<ChunkDescriptor>, //empty means a single chunk covering the frame
+    palette: Option<String>, //replaces the palette stored in the png
+}
+
+//frame i sits in the top left of cell (i % columns, i / columns)
+struct SheetDescriptor{
+    file: String,
+    columns: usize,
+    cell_w: usize,
+    cell_h: usize,
+}
+
+/// sprite.yaml/sprite.toml contents, shared by both serializers
+struct SpriteDescriptor{
+    format: ImgFmt,
+    bin: Option<String>, //the extracted bin next to the descriptor, unedited frames are copied from it
+    header: Vec<u8>, //empty when the sprite was never extracted
+    size: usize,
+    sheet: Option<SheetDescriptor>,
+    orientation: Orientation,
+    frames: Vec<FrameDescriptor>,
+    reproduction: Reproduction,
+    alpha_threshold: u8,
+}
+
+//formats a byte list as a yaml/toml flow sequence
+//first place `found` differs from `expected`, file names are not compared since they only point at
+//files written next to the yaml
+fn yaml_mismatch(expected: &Yaml, found: &Yaml, path: &str) -> Option<String>{
+    let show = |y: &Yaml| match y{
+        Yaml::Integer(i) => format!("0x{:X}", i),
+        Yaml::String(s) => s.clone(),
+        Yaml::BadValue => String::from("missing"),
+        other => format!("{:?}", other),
+    };
+    match (expected, found){
+        (Yaml::Hash(a), Yaml::Hash(_)) => a.iter()
+            .filter(|(k, _)| !matches!(k.as_str(), Some("file") | Some("bin")))
+            .find_map(|(k, v)|{
+                let key = k.as_str().unwrap_or("?");
+                let next = if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+                yaml_mismatch(v, &found[key], &next)
+            }),
+        (Yaml::Array(a), Yaml::Array(b)) if a.len() == b.len() => a.iter().zip(b.iter()).enumerate()
+            .find_map(|(i, (x, y))| yaml_mismatch(x, y, &format!("{}[{}]", path, i))),
+        (Yaml::Array(a), Yaml::Array(b)) => Some(format!("{} has {} entries, the bin has {}", path, b.len(), a.len())),
+        (a, b) if a == b => None,
+        (a, b) => Some(format!("{} is {} but the bin has {}", path, show(b), show(a))),
+    }
+}
+
+fn hex_list(bytes: &[u8]) -> String{
+    let vals : Vec<String> = bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+    return format!("[{}]", vals.join(", "))
+}
+
+impl SpriteDescriptor{
+    pub fn read(path: &Path) -> Result<SpriteDescriptor, ReadError>{
+        let file = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|e| ReadError::Io{file: file.clone(), reason: e.to_string()})?;
+        match path.extension().and_then(|e| e.to_str()){
+            Some("toml") => SpriteDescriptor::from_toml(&text, &file),
+            _ => SpriteDescriptor::from_yaml(&text, &file),
+        }
+    }
+
+    pub fn write(&self, path: &Path){
+        match path.extension().and_then(|e| e.to_str()){
+            Some("toml") => self.write_toml(path),
+            _ => self.write_yaml(path),
+        }
+    }
+
+    fn from_yaml(text: &str, file: &str) -> Result<SpriteDescriptor, ReadError>{
+        let field_err = |section: &'static str, reason: String| ReadError::Field{file: file.to_string(), section: section, index: None, reason: reason};
+        let docs = YamlLoader::load_from_str(text).map_err(|e| ReadError::Yaml{file: file.to_string(), reason: e.to_string()})?;
+        let doc = docs.first().ok_or(ReadError::Yaml{file: file.to_string(), reason: String::from("empty document")})?;
+        match doc["type"].as_str(){
+            Some("Sprite") => {},
+            t => return Err(field_err("type", format!("expected Sprite, found {}", t.unwrap_or("nothing")))),
+        }
+        let format = doc["format"].as_str().ok_or(field_err("format", String::from("missing")))?
+            .parse::<ImgFmt>().map_err(|e| field_err("format", e))?;
+        let bytes = |y: &Yaml| -> Vec<u8> {
+            y.as_vec().map(|v| v.iter().map(|b| b.as_i64().unwrap() as u8).collect()).unwrap_or_default()
+        };
+        let frames = doc["frames"].as_vec().unwrap().iter()
+            .map(|y|{
+                let chunks = y["chunks"].as_vec().map(|v| v.iter()
+                    .map(|c|{
+                        ChunkDescriptor{
+                            x: c["x"].as_i64().unwrap() as isize,
+                            y: c["y"].as_i64().unwrap() as isize,
+                            w: c["w"].as_i64().unwrap() as usize,
+                            h: c["h"].as_i64().unwrap() as usize,
+                        }
+                    })
+                    .collect())
+                    .unwrap_or_default();
+                FrameDescriptor{
+                    file: String::from(y["file"].as_str().unwrap()),
+                    w: y["w"].as_i64().unwrap() as usize,
+                    h: y["h"].as_i64().unwrap() as usize,
+                    header: frame_header_from(
+                        y["x"].as_i64().unwrap_or(0),
+                        y["y"].as_i64().unwrap_or(0),
+                        &bytes(&y["unk"]),
+                        [&y["unk_a"], &y["unk_c"], &y["unk_e"], &y["unk_10"], &y["unk_12"]].map(|u| u.as_i64()),
+                    ),
+                    offset: y["offset"].as_i64().map(|o| o as usize),
+                    chunks: chunks,
+                    palette: y["palette"].as_str().map(String::from),
+                }
+            })
+            .collect();
+        let reproduction = Reproduction::from_yaml(&doc["reproduction"]).map_err(|e| field_err("reproduction", e))?;
+        let size = doc["size"].as_i64().unwrap_or(0) as usize;
+        let sheet = match doc["sheet"].is_badvalue(){
+            true => None,
+            false => Some(SheetDescriptor{
+                file: String::from(doc["sheet"]["file"].as_str().unwrap()),
+                columns: doc["sheet"]["columns"].as_i64().unwrap() as usize,
+                cell_w: doc["sheet"]["cell_w"].as_i64().unwrap() as usize,
+                cell_h: doc["sheet"]["cell_h"].as_i64().unwrap() as usize,
+            }),
+        };
+        let orientation = match doc["orientation"].as_str(){
+            Some(o) => Orientation::from_str(o).map_err(|e| field_err("orientation", e))?,
+            None => Orientation::Native,
+        };
+        let alpha_threshold = doc["alpha_threshold"].as_i64().unwrap_or(0x80) as u8;
+        Ok(SpriteDescriptor{format: format, bin: doc["bin"].as_str().map(String::from), header: bytes(&doc["header"]), size: size, sheet: sheet, orientation: orientation, frames: frames, reproduction: reproduction, alpha_threshold: alpha_threshold})
+    }
+
+    fn from_toml(text: &str, file: &str) -> Result<SpriteDescriptor, ReadError>{
+        let field_err = |section: &'static str, reason: String| ReadError::Field{file: file.to_string(), section: section, index: None, reason: reason};
+        let doc = text.parse::<toml::Value>().map_err(|e| ReadError::Toml{file: file.to_string(), reason: e.to_string()})?;
+        match doc.get("type").and_then(|t| t.as_str()){
+            Some("Sprite") => {},
+            t => return Err(field_err("type", format!("expected Sprite, found {}", t.unwrap_or("nothing")))),
+        }
+        let format = doc.get("format").and_then(|f| f.as_str()).ok_or(field_err("format", String::from("missing")))?
+            .parse::<ImgFmt>().map_err(|e| field_err("format", e))?;
+        let bytes = |t: Option<&toml::Value>| -> Vec<u8> {
+            t.and_then(|t| t.as_array())
+                .map(|v| v.iter().map(|b| b.as_integer().unwrap() as u8).collect())
+                .unwrap_or_default()
+        };
+        let int = |t: &toml::Value, key: &str| -> Option<i64> { t.get(key).and_then(|v| v.as_integer()) };
+        let frames = doc["frames"].as_array().unwrap().iter()
+            .map(|t|{
+                let chunks = t.get("chunks").and_then(|c| c.as_array()).map(|v| v.iter()
+                    .map(|c|{
+                        ChunkDescriptor{
+                            x: int(c, "x").unwrap() as isize,
+                            y: int(c, "y").unwrap() as isize,
+                            w: int(c, "w").unwrap() as usize,
+                            h: int(c, "h").unwrap() as usize,
+                        }
+                    })
+                    .collect())
+                    .unwrap_or_default();
+                FrameDescriptor{
+                    file: String::from(t["file"].as_str().unwrap()),
+                    w: t["w"].as_integer().unwrap() as usize,
+                    h: t["h"].as_integer().unwrap() as usize,
+                    header: frame_header_from(
+                        int(t, "x").unwrap_or(0),
+                        int(t, "y").unwrap_or(0),
+                        &bytes(t.get("unk")),
+                        ["unk_a", "unk_c", "unk_e", "unk_10", "unk_12"].map(|k| int(t, k)),
+                    ),
+                    offset: int(t, "offset").map(|o| o as usize),
+                    chunks: chunks,
+                    palette: t.get("palette").and_then(|p| p.as_str()).map(String::from),
+                }
+            })
+            .collect();
+        let header = bytes(doc.get("header"));
+        let size = int(&doc, "size").unwrap_or(0) as usize;
+        let sheet = doc.get("sheet").map(|t|{
+            SheetDescriptor{
+                file: String::from(t["file"].as_str().unwrap()),
+                columns: int(t, "columns").unwrap() as usize,
+                cell_w: int(t, "cell_w").unwrap() as usize,
+                cell_h: int(t, "cell_h").unwrap() as usize,
+            }
+        });
+        let reproduction = match doc.get("reproduction").and_then(|r| r.as_str()){
+            Some(r) => Reproduction::from_yaml(&Yaml::String(String::from(r))).map_err(|e| field_err("reproduction", e))?,
+            None => Reproduction::Exact,
+        };
+        let orientation = match doc.get("orientation").and_then(|o| o.as_str()){
+            Some(o) => Orientation::from_str(o).map_err(|e| field_err("orientation", e))?,
+            None => Orientation::Native,
+        };
+        let alpha_threshold = int(&doc, "alpha_threshold").unwrap_or(0x80) as u8;
+        let bin = doc.get("bin").and_then(|b| b.as_str()).map(String::from);
+        Ok(SpriteDescriptor{format: format, bin: bin, header: header, size: size, sheet: sheet, orientation: orientation, frames: frames, reproduction: reproduction, alpha_threshold: alpha_threshold})
+    }
+
+    fn write_yaml(&self, path: &Path){
+        let mut desc_f = File::create(path).unwrap();
+        writeln!(desc_f, "type: Sprite").unwrap();
+        writeln!(desc_f, "reproduction: {}", self.reproduction).unwrap();
+        writeln!(desc_f, "format: {}", self.format).unwrap();
+        if let Some(bin) = &self.bin{
+            writeln!(desc_f, "bin: {:?}", bin).unwrap();
+        }
+        writeln!(desc_f, "header: {}", hex_list(&self.header)).unwrap();
+        writeln!(desc_f, "size: 0x{:X}", self.size).unwrap();
+        writeln!(desc_f, "orientation: {}", self.orientation).unwrap();
+        writeln!(desc_f, "alpha_threshold: 0x{:02X}", self.alpha_threshold).unwrap();
+        if let Some(sheet) = &self.sheet{
+            writeln!(desc_f, "sheet: {{file: {:?}, columns: {}, cell_w: {}, cell_h: {}}}", sheet.file, sheet.columns, sheet.cell_w, sheet.cell_h).unwrap();
+        }
+        if self.frames.is_empty(){
+            writeln!(desc_f, "frames: []").unwrap();
+            return;
+        }
+        writeln!(desc_f, "frames:").unwrap();
+        for frame in self.frames.iter(){
+            let hdr = &frame.header;
+            write!(desc_f, "  - {{file: {:?}, w: {}, h: {}, x: {}, y: {}, unk_a: 0x{:X}, unk_c: 0x{:X}, unk_e: 0x{:X}, unk_10: 0x{:X}, unk_12: 0x{:X}",
+                frame.file, frame.w, frame.h, hdr.x, hdr.y, hdr.unk_a, hdr.unk_c, hdr.unk_e, hdr.unk_10, hdr.unk_12).unwrap();
+            if let Some(offset) = frame.offset{
+                write!(desc_f, ", offset: 0x{:X}", offset).unwrap();
+            }
+            if let Some(palette) = &frame.palette{
+                write!(desc_f, ", palette: {:?}", palette).unwrap();
+            }
+            let chunks : Vec<String> = frame.chunks.iter()
+                .map(|c| format!("{{x: {}, y: {}, w: {}, h: {}}}", c.x, c.y, c.w, c.h))
+                .collect();
+            writeln!(desc_f, ", chunks: [{}]}}", chunks.join(", ")).unwrap();
+        }
+    }
+
+    fn write_toml(&self, path: &Path){
+        let mut desc_f = File::create(path).unwrap();
+        writeln!(desc_f, "type = \"Sprite\"").unwrap();
+        writeln!(desc_f, "reproduction = \"{}\"", self.reproduction).unwrap();
+        writeln!(desc_f, "format = \"{}\"", self.format).unwrap();
+        if let Some(bin) = &self.bin{
+            writeln!(desc_f, "bin = {:?}", bin).unwrap();
+        }
+        writeln!(desc_f, "header = {}", hex_list(&self.header)).unwrap();
+        writeln!(desc_f, "size = 0x{:X}", self.size).unwrap();
+        writeln!(desc_f, "orientation = \"{}\"", self.orientation).unwrap();
+        writeln!(desc_f, "alpha_threshold = 0x{:02X}", self.alpha_threshold).unwrap();
+        if let Some(sheet) = &self.sheet{
+            writeln!(desc_f, "sheet = {{file = {:?}, columns = {}, cell_w = {}, cell_h = {}}}", sheet.file, sheet.columns, sheet.cell_w, sheet.cell_h).unwrap();
+        }
+        writeln!(desc_f, "frames = [").unwrap();
+        for frame in self.frames.iter(){
+            let hdr = &frame.header;
+            write!(desc_f, "    {{file = {:?}, w = {}, h = {}, x = {}, y = {}, unk_a = 0x{:X}, unk_c = 0x{:X}, unk_e = 0x{:X}, unk_10 = 0x{:X}, unk_12 = 0x{:X}",
+                frame.file, frame.w, frame.h, hdr.x, hdr.y, hdr.unk_a, hdr.unk_c, hdr.unk_e, hdr.unk_10, hdr.unk_12).unwrap();
+            if let Some(offset) = frame.offset{
+                write!(desc_f, ", offset = 0x{:X}", offset).unwrap();
+            }
+            if let Some(palette) = &frame.palette{
+                write!(desc_f, ", palette = {:?}", palette).unwrap();
+            }
+            let chunks : Vec<String> = frame.chunks.iter()
+                .map(|c| format!("{{x = {}, y = {}, w = {}, h = {}}}", c.x, c.y, c.w, c.h))
+                .collect();
+            writeln!(desc_f, ", chunks = [{}]}},", chunks.join(", ")).unwrap();
+        }
+        writeln!(desc_f, "]").unwrap();
+    }
+}
+
+//older descriptors stored the unknown header words as a 10 byte `unk` list, named words win when both are present
+fn frame_header_from(x: i64, y: i64, unk_bytes: &[u8], unk: [Option<i64>; 5]) -> SpriteFrameHeader{
+    let mut bin = vec![0; 0xA];
+    bin.extend_from_slice(unk_bytes);
+    bin.resize(0x14, 0);
+    let legacy = SpriteFrameHeader::from_bytes(&bin).unk();
+    let word = |i: usize| unk[i].map(|v| v as u16).unwrap_or(legacy[i]);
+    SpriteFrameHeader{x: x as i16, y: y as i16, unk_a: word(0), unk_c: word(1), unk_e: word(2), unk_10: word(3), unk_12: word(4), ..SpriteFrameHeader::default()}
+}
+
+impl Sprite{
+    /// the sprite bin, fails in exact mode when the frames can't be laid out as described
+    pub fn try_to_bytes(&self) -> Result<Vec<u8>, SpriteError>{
+        return self.layout(self.reproduction)
+    }
+
+    fn layout(&self, reproduction: Reproduction) -> Result<Vec<u8>, SpriteError>{
+        if let ImgFmt::Unknown(_) = self.format{
+            return Ok(self.bytes.clone());
+        }
+
+        //headerless single chunk sprite
+        if self.header.len() == 8{
+            let frame = &self.frame[0];
+            let chunk = &frame.chunks[0];
+            let mut out = self.header.clone();
+            out.append(&mut chunk.header_bytes());
+            match (reproduction, &frame.raw){
+                (Reproduction::Exact, Some(raw)) => out.extend_from_slice(raw),
+                _ => out.extend_from_slice(&chunk.pixel_data),
+            }
+            return Ok(out);
+        }
+
+        let frame_cnt = self.frame.len();
+        let mut out : Vec<u8> = (frame_cnt as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(&Sprite::format_code(self.format).to_be_bytes());
+        match self.header.len(){
+            0x10 => out.extend_from_slice(&self.header[4..]),
+            _ => out.resize(0x10, 0),
+        }
+        let table_start = out.len();
+        out.resize(table_start + 4*frame_cnt, 0);
+        let data_start = out.len();
+
+        for (i, frame) in self.frame.iter().enumerate(){
+            let mut start = out.len();
+            match frame.offset{
+                Some(offset) if data_start + offset >= start => start = data_start + offset,
+                Some(_) => {
+                    reproduction.require_exact(false, &format!("frame {} overlapping the previous one", i))?;
+                    start = (start + (8 - 1)) & !(8 - 1);
+                },
+                None => start = (start + (8 - 1)) & !(8 - 1),
+            }
+            out.resize(start, 0);
+            out[table_start + 4*i .. table_start + 4*(i+1)].copy_from_slice(&((start - data_start) as u32).to_be_bytes());
+            frame.write_bytes(&mut out, reproduction == Reproduction::Exact);
+        }
+
+        if out.len() < self.size && !reproduction.normalize("sprite padding"){
+            out.resize(self.size, 0);
+        }
+        return Ok(out);
+    }
+}
+
+impl Asset for Sprite{
+    fn to_bytes(&self)->Vec<u8>{
+        //only edits made after reading can still fail, normalized layouts always succeed
+        match self.try_to_bytes(){
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("warning: {}, writing the sprite normalized", e);
+                self.layout(Reproduction::Normalized).unwrap_or_default()
+            },
+        }
+    }
+
+    fn get_type(&self)->AssetType{
+        return AssetType::Sprite(self.format);
+    }
+
+    fn write(&self, path: &Path){
+        self.write_with(path, &SpriteWriteOptions::default());
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //header, then (cmd, string) pairs for the bottom and top sections
+    fn dialog_bytes(bottom: &[(u8, &[u8])], top: &[(u8, &[u8])]) -> Vec<u8>{
+        let mut out = magic::DIALOG.to_vec();
+        for section in [bottom, top]{
+            out.push(section.len() as u8);
+            for (cmd, string) in section.iter(){
+                out.push(*cmd);
+                out.push(string.len() as u8);
+                out.extend_from_slice(string);
+            }
+        }
+        return out
+    }
+
+    //writes the dialog to the scratch file `name.ext` and reads it back
+    fn dialog_round_trip(name: &str, bytes: &[u8], ext: &str, table: &charset::CharTable) -> Vec<u8>{
+        let path = std::env::temp_dir().join(format!("bk_asset_tool_{}_{}.{}", std::process::id(), name, ext));
+        Dialog::parse(bytes).unwrap().write_with(&path, table);
+        let out = Dialog::read_with(&path, table).unwrap_or_else(|e| panic!("{}", e));
+        fs::remove_file(&path).unwrap();
+        return out.to_bytes()
+    }
+
+    //an empty folder below the temp dir, removed by the test once it is done
+    fn scratch_dir(name: &str) -> std::path::PathBuf{
+        let dir = std::env::temp_dir().join(format!("bk_asset_tool_{}_{}", std::process::id(), name));
+        if dir.exists(){
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        return dir
+    }
+
+    //a sprite bin with one frame of `w` x `h` per entry, the pixels only use a few colors so CI4 keeps them all
+    fn sprite_bytes(format: ImgFmt, sizes: &[(usize, usize)]) -> Vec<u8>{
+        let frames = sizes.iter().enumerate()
+            .map(|(f, (w, h))|{
+                let rgba : Vec<u8> = (0..w*h)
+                    .flat_map(|i|{
+                        let v = ((i*7 + f*3) % 12 * 0x11) as u8;
+                        [v, 0xFF - v, v/2, if i % 5 == 0 {0} else {0xFF}]
+                    })
+                    .collect();
+                SpriteFrame::from_rgba32(format, *w, *h, &rgba).unwrap()
+            })
+            .collect();
+        let sprite = Sprite{format: format, frame: frames, header: Vec::new(), bytes: Vec::new(), size: 0, reproduction: Reproduction::Exact, alpha_threshold: 0x80};
+        return sprite.to_bytes()
+    }
+
+    #[test]
+    fn sprite_descriptors_read_the_same_as_yaml_and_toml(){
+        let dir = scratch_dir("descriptor_formats");
+        for format in [ImgFmt::CI4, ImgFmt::RGBA16, ImgFmt::IA8]{
+            let bin = dir.join(format!("0001.sprite.{:?}.bin", format).to_lowercase());
+            let bytes = sprite_bytes(format, &[(8, 4), (6, 3)]);
+            let sprite = Sprite::parse(&bytes).unwrap();
+            sprite.write_with(&bin, &SpriteWriteOptions::default());
+            let yaml = fs::read_to_string(dir.join("0001.sprite.yaml")).unwrap();
+            sprite.write_with(&bin, &SpriteWriteOptions::default().descriptor(DescriptorFormat::Toml));
+            assert!(!dir.join("0001.sprite.yaml").exists(), "the old yaml would be read instead of the toml");
+
+            let from_toml = SpriteDescriptor::read(&dir.join("0001.sprite.toml")).unwrap();
+            from_toml.write(&dir.join("0001.sprite.yaml"));
+            assert_eq!(fs::read_to_string(dir.join("0001.sprite.yaml")).unwrap(), yaml, "{:?}", format);
+            for desc in ["0001.sprite.toml", "0001.sprite.yaml"]{
+                assert_eq!(Sprite::read(&dir.join(desc)).unwrap().to_bytes(), bytes, "{:?} {}", format, desc);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unedited_sprites_keep_gaps_and_padding(){
+        let dir = scratch_dir("sprite_gaps");
+        for format in [ImgFmt::CI4, ImgFmt::CI8, ImgFmt::I4, ImgFmt::I8, ImgFmt::IA4, ImgFmt::IA8, ImgFmt::RGBA16, ImgFmt::RGBA32]{
+            //frame 1 moved 8 bytes further with junk in between, and junk padding at the end
+            let mut bytes = sprite_bytes(format, &[(8, 4), (6, 3)]);
+            let offset = u32::from_be_bytes(bytes[0x14..0x18].try_into().unwrap()) as usize;
+            let start = 0x18 + offset;
+            bytes.splice(start..start, [0xAA; 8]);
+            bytes[0x14..0x18].copy_from_slice(&((offset + 8) as u32).to_be_bytes());
+            bytes.extend_from_slice(&[0x55; 8]);
+            let frame_1 = start + 8;
+
+            let sprite = Sprite::parse(&bytes).unwrap();
+            assert_eq!(sprite.to_bytes(), bytes, "{:?}", format);
+            let bin = dir.join("0001.sprite.bin");
+            let desc = dir.join("0001.sprite.yaml");
+            for options in [SpriteWriteOptions::default(), SpriteWriteOptions::default().sheet(0)]{
+                sprite.write_with(&bin, &options);
+                assert_eq!(Sprite::read(&desc).unwrap().to_bytes(), bytes, "{:?}", format);
+            }
+
+            sprite.write_with(&bin, &SpriteWriteOptions::default());
+            let frame = &sprite.frame[0];
+            let mut rgba = frame.pixel_data.clone();
+            rgba[0] ^= 0x80;
+            let fmt_str = format!("{:?}", format).to_lowercase();
+            write_png_rgba32(&dir.join(format!("0001/00.{}.png", fmt_str)), frame.w, frame.h, &rgba);
+            assert!(matches!(Sprite::read(&desc), Err(SpriteError::NotReproducible(_))), "{:?}", format);
+
+            let yaml = fs::read_to_string(&desc).unwrap().replace("reproduction: exact", "reproduction: normalized");
+            fs::write(&desc, yaml).unwrap();
+            let normalized = Sprite::read(&desc).unwrap().to_bytes();
+            assert_eq!(normalized[frame_1..], bytes[frame_1..bytes.len() - 8], "{:?}", format);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rgba16_words_survive_decode_and_encode(){
+        let words : Vec<u8> = (0..=0xFFFFu16).flat_map(|w| w.to_be_bytes()).collect();
+        assert_eq!(Texture::rgba32_to_rgba16(&Texture::rgba16_to_rgba32(&words)), words);
+
+        //encoding drops the low 3 bits of each channel and thresholds alpha at 0x80
+        let rgba : Vec<u8> = (0..=255u8).flat_map(|v| [v, 255 - v, v/3, v]).collect();
+        let decoded = Texture::rgba16_to_rgba32(&Texture::rgba32_to_rgba16(&rgba));
+        for (src, out) in rgba.chunks_exact(4).zip(decoded.chunks_exact(4)){
+            for ch in 0..3{
+                assert_eq!(out[ch] >> 3, src[ch] >> 3, "{:?} came back as {:?}", src, out);
+            }
+            assert_eq!(out[3], if src[3] >= 0x80 {0xFF} else {0});
+        }
+    }
+
+    //`len` rgba16 words with distinct colors, some of them transparent
+    fn ci_palette(len: usize) -> Vec<u8>{
+        return (0..len).flat_map(|i|{
+            let (r, g, b) = ((i*7 % 32) as u16, (i*13 % 32) as u16, (i / 8) as u16);
+            ((r << 11) | (g << 6) | (b << 1) | (i % 3 != 0) as u16).to_be_bytes()
+        }).collect()
+    }
+
+    #[test]
+    fn ci4_decode_encode_decode_keeps_pixels_and_palette(){
+        let palette = ci_palette(16);
+        //every index shows up in order, so the encoder rebuilds the same palette
+        let ci4 : Vec<u8> = (0..32u8).map(|i| ((2*i % 16) << 4) | ((2*i + 1) % 16)).collect();
+        let rgba = Texture::ci4_to_rgba32(&ci4, &palette);
+        let (indices, new_palette) = Texture::rgba32_to_ci4(&rgba);
+        assert_eq!((&indices, &new_palette), (&ci4, &palette));
+
+        //out of order indices may permute the palette, the pixels stay
+        let shuffled : Vec<u8> = ci4.iter().rev().map(|b| b.rotate_left(4) ^ 0x35).collect();
+        let rgba = Texture::ci4_to_rgba32(&shuffled, &palette);
+        let (indices, new_palette) = Texture::rgba32_to_ci4(&rgba);
+        assert_eq!(new_palette.len(), 0x20);
+        assert_eq!(Texture::ci4_to_rgba32(&indices, &new_palette), rgba);
+    }
+
+    #[test]
+    fn ci8_decode_encode_decode_keeps_pixels_and_palette(){
+        let palette = ci_palette(256);
+        let ci8 : Vec<u8> = (0..=255u8).chain((0..=255u8).rev()).collect();
+        let rgba = Texture::ci8_to_rgba32(&ci8, &palette);
+        let (indices, new_palette) = Texture::rgba32_to_ci8(&rgba);
+        assert_eq!((&indices, &new_palette), (&ci8, &palette));
+
+        let shuffled : Vec<u8> = ci8.iter().map(|i| i.wrapping_mul(37).wrapping_add(11)).collect();
+        let rgba = Texture::ci8_to_rgba32(&shuffled, &palette);
+        let (indices, new_palette) = Texture::rgba32_to_ci8(&rgba);
+        assert_eq!(new_palette.len(), 0x200);
+        assert_eq!(Texture::ci8_to_rgba32(&indices, &new_palette), rgba);
+    }
+
+    #[test]
+    fn intensity_formats_survive_decode_and_encode(){
+        let bytes : Vec<u8> = (0..=255u8).collect();
+        for mode in [IntensityMode::Luma, IntensityMode::Average]{
+            assert_eq!(Texture::rgba32_to_i8(&Texture::i8_to_rgba32(&bytes), mode), bytes, "{:?}", mode);
+            assert_eq!(Texture::rgba32_to_i4(&Texture::i4_to_rgba32(&bytes), mode), bytes, "{:?}", mode);
+        }
+        //an odd pixel count leaves the last low nibble empty
+        let gray = [0x44, 0x44, 0x44, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x88, 0x88, 0x88, 0xFF];
+        assert_eq!(Texture::rgba32_to_i4(&gray, IntensityMode::Luma), vec![0x4F, 0x80]);
+        //luma weighs green over blue, averaging doesn't
+        let green = [0, 0xFF, 0, 0xFF];
+        assert_eq!(Texture::rgba32_to_i8(&green, IntensityMode::Luma), vec![150]);
+        assert_eq!(Texture::rgba32_to_i8(&green, IntensityMode::Average), vec![85]);
+    }
+
+    #[test]
+    fn intensity_alpha_formats_survive_decode_and_encode(){
+        let bytes : Vec<u8> = (0..=255u8).collect();
+        for mode in [IntensityMode::Luma, IntensityMode::Average]{
+            assert_eq!(Texture::rgba32_to_ia8(&Texture::ia8_to_rgba32(&bytes), mode), bytes, "{:?}", mode);
+            assert_eq!(Texture::rgba32_to_ia4(&Texture::ia4_to_rgba32(&bytes), mode), bytes, "{:?}", mode);
+        }
+        //ia4 keeps one alpha bit, set from 0x80 up
+        let px = [0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 0x80];
+        assert_eq!(Texture::rgba32_to_ia4(&px, IntensityMode::Luma), vec![0xEF]);
+        assert_eq!(Texture::rgba32_to_ia8(&px, IntensityMode::Luma), vec![0xF7, 0xF8]);
+    }
+
+    #[test]
+    fn i8_and_ia8_sprites_decode_with_their_own_format(){
+        //values the 8 bit formats keep exactly, the 4 bit decoders would spread them over two pixels
+        let gray : Vec<u8> = (0..16u8).flat_map(|i|{ let v = i.wrapping_mul(53); [v, v, v, 0xFF] }).collect();
+        let faded : Vec<u8> = (0..16u8).flat_map(|i|{ let v = (i*7 % 16) * 0x11; [v, v, v, (15 - i) * 0x11] }).collect();
+        for (format, rgba) in [(ImgFmt::I8, gray), (ImgFmt::IA8, faded)]{
+            let frame = SpriteFrame::from_rgba32(format, 4, 4, &rgba).unwrap();
+            let sprite = Sprite{format: format, frame: vec![frame], header: Vec::new(), bytes: Vec::new(), size: 0, reproduction: Reproduction::Exact, alpha_threshold: 0x80};
+            let parsed = Sprite::parse(&sprite.to_bytes()).unwrap();
+            assert_eq!(parsed.format, format);
+            assert_eq!(parsed.frame[0].rgba32(), &rgba[..], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn dialog_round_trips_every_byte(){
+        let low : Vec<u8> = (0x01..0x80).chain([0]).collect();
+        let high : Vec<u8> = (0x80..=0xFF).chain([0]).collect();
+        let bytes = dialog_bytes(&[(0x80, &low), (0x81, b"\0")], &[(0x80, &high)]);
+        for table in [charset::DEFAULT, charset::CharTable::empty()]{
+            for string in [&low, &high]{
+                assert_eq!(string_to_vecu8(&vecu8_to_string(string, &table), &table), *string);
+            }
+            for ext in ["yaml", "json"]{
+                assert_eq!(dialog_round_trip("every_byte", &bytes, ext, &table), bytes, "{}", ext);
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_backslashes_and_control_bytes_round_trip(){
+        let string = b"say \"hi\", it's a\\b \\x41 \x01\x1F\x7F\xFD\x6A ''\0";
+        let text = vecu8_to_string(string, &charset::DEFAULT);
+        assert_eq!(text, "say \"hi\", it's a\\\\b \\\\x41 \\x01\\x1F\\x7F\\xFDj ''");
+        assert_eq!(string_to_vecu8(&text, &charset::DEFAULT), string.to_vec());
+        let bytes = dialog_bytes(&[(0x80, string)], &[(0x81, b"\"\0"), (0x82, b"\\\0")]);
+        for ext in ["yaml", "json"]{
+            assert_eq!(dialog_round_trip("escapes", &bytes, ext, &charset::DEFAULT), bytes, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn text_that_spells_a_glyph_round_trips(){
+        let mut table = charset::DEFAULT;
+        table.set(0x80, "(A)");
+        table.set(0x81, "(A");
+        table.set(0x82, "é!");
+        let strings : [&[u8]; 5] = [b"press (A)\0", b"press \x80 or (A\0", b"\x81)\0", b"\xE9!\0", b"(\x80\xE9\0"];
+        for string in strings{
+            let text = vecu8_to_string(string, &table);
+            assert_eq!(string_to_vecu8(&text, &table), string.to_vec(), "{}", text);
+        }
+        //only the byte that would start a glyph is escaped
+        assert_eq!(vecu8_to_string(b"press (A)\0", &table), "press \\x28A)");
+        let bytes = dialog_bytes(&[(0x80, strings[0]), (0x81, strings[2])], &[(0x80, strings[3])]);
+        for ext in ["yaml", "json"]{
+            assert_eq!(dialog_round_trip("glyph_text", &bytes, ext, &table), bytes, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn non_terminated_strings_keep_their_last_byte(){
+        let yaml = &YamlLoader::load_from_str("{cmd: 0x80, string: 'abc', terminated: false}").unwrap()[0];
+        assert_eq!(BKString::from_yaml(yaml, &charset::DEFAULT).unwrap().string, b"abc".to_vec());
+        let yaml = &YamlLoader::load_from_str("{cmd: 0x80, string: 'abc'}").unwrap()[0];
+        assert_eq!(BKString::from_yaml(yaml, &charset::DEFAULT).unwrap().string, b"abc\0".to_vec());
+
+        let bytes = dialog_bytes(&[(0x80, b"abc"), (0x81, b"ab\0")], &[(0x80, b"")]);
+        let dialog = Dialog::parse(&bytes).unwrap();
+        assert_eq!(dialog.bottom.iter().chain(dialog.top.iter()).filter(|t| !t.is_terminated()).count(), 2);
+        for ext in ["yaml", "json"]{
+            assert_eq!(dialog_round_trip("unterminated", &bytes, ext, &charset::DEFAULT), bytes, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn serialized_assets_detect_as_their_own_type(){
+        let mut quiz = magic::QUIZ_QUESTION.to_vec();
+        quiz.extend_from_slice(&[4, 0x80, 5, b'w', b'h', b'o', b'?', 0, 0x80, 2, b'a', 0, 0x80, 2, b'b', 0, 0x80, 2, b'c', 0]);
+        let mut grunty = quiz.clone();
+        grunty[..5].copy_from_slice(&magic::GRUNTY_QUESTION);
+        let mut model = magic::MODEL.to_vec();
+        model.resize(0x40, 0);
+        let fixtures : [(usize, Vec<u8>, AssetType); 6] = [
+            (4, dialog_bytes(&[(0x80, b"hi\0")], &[(0x81, b"bye\0")]), AssetType::Dialog),
+            (4, dialog_bytes(&[(0x00, b"a\0"), (0x80, b"b\0"), (0x80, b"c\0"), (0x80, b"d\0"), (0x80, b"e\0")], &[]), AssetType::Dialog),
+            (4, quiz, AssetType::QuizQuestion),
+            (4, grunty, AssetType::GruntyQuestion),
+            (4, vec![0, 0, 0, 6, 0x80, 0x00, 10, 20, 0, 1], AssetType::DemoInput),
+            (1, model, AssetType::Model),
+        ];
+        for (segment, bytes, kind) in fixtures.iter(){
+            let asset = from_seg_indx_and_bytes(*segment, 0, bytes);
+            assert_eq!(asset.get_type(), *kind);
+            let out = asset.to_bytes();
+            assert_eq!(out, *bytes, "{:?}", kind);
+            assert_eq!(from_seg_indx_and_bytes(*segment, 0, &out).get_type(), *kind);
+            assert!(magic::for_type(*kind).is_none() || magic::starts_with_magic(&out, *kind), "{:?} lost its magic", kind);
+        }
+    }
+
+    #[test]
+    fn sprite_descriptor_reports_bad_format_and_orientation(){
+        let cases = [
+            ("yaml", "type: Sprite\nformat: CI9\nframes: []\n", "format"),
+            ("yaml", "type: Sprite\nformat: CI4\norientation: sideways\nframes: []\n", "orientation"),
+            ("yaml", "type: Sprite\nframes: []\n", "format"),
+            ("toml", "type = \"Sprite\"\nformat = \"CI9\"\nframes = []\n", "format"),
+            ("toml", "type = \"Sprite\"\nformat = \"I8\"\norientation = \"sideways\"\nframes = []\n", "orientation"),
+        ];
+        for (i, (ext, text, expected)) in cases.iter().enumerate(){
+            let path = std::env::temp_dir().join(format!("bk_asset_tool_{}_descriptor_{}.sprite.{}", std::process::id(), i, ext));
+            fs::write(&path, text).unwrap();
+            let result = Sprite::read(&path);
+            fs::remove_file(&path).unwrap();
+            match result{
+                Err(SpriteError::Descriptor(ReadError::Field{section, ..})) => assert_eq!(section, *expected, "case {}", i),
+                Err(e) => panic!("case {}: {}", i, e),
+                Ok(_) => panic!("case {} was read", i),
+            }
+        }
+    }
+
+    #[test]
+    fn exact_edits_keep_the_untouched_bytes(){
+        let header = [0x01, 0x03, 0x01];
+        let with_header = |bytes: Vec<u8>| -> Vec<u8> { header.iter().chain(bytes[3..].iter()).cloned().collect() };
+        let bytes = with_header(dialog_bytes(&[(0x80, b"hello\0"), (0x05, b"\x01\xFD\x6A")], &[(0x81, b"bye\0")]));
+        let path = std::env::temp_dir().join(format!("bk_asset_tool_{}_exact_edit.yaml", std::process::id()));
+        Dialog::parse(&bytes).unwrap().write(&path);
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("reproduction: exact"));
+        fs::write(&path, text.replace("'hello'", "'howdy there'")).unwrap();
+        let edited = Dialog::read(&path).unwrap().to_bytes();
+        fs::write(&path, text.replace("reproduction: exact", "reproduction: sloppy")).unwrap();
+        let bad_mode = Dialog::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(edited, with_header(dialog_bytes(&[(0x80, b"howdy there\0"), (0x05, b"\x01\xFD\x6A")], &[(0x81, b"bye\0")])));
+        assert!(matches!(bad_mode, Err(ReadError::Field{section: "reproduction", ..})));
+    }
+
+    #[test]
+    fn exact_level_setups_refuse_a_resized_bin(){
+        let dir = std::env::temp_dir().join(format!("bk_asset_tool_{}_lvl_setup", std::process::id()));
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        let bin_path = dir.join("0001.lvl_setup.bin");
+        let yaml_path = dir.join("0001.lvl_setup.yaml");
+        LevelSetup::from_bytes(&[1, 2, 3, 4, 0, 0, 0, 0]).write(&bin_path);
+        assert_eq!(LevelSetup::read(&yaml_path).unwrap().to_bytes(), vec![1, 2, 3, 4, 0, 0, 0, 0]);
+
+        fs::write(&bin_path, [1, 2, 3, 4, 5]).unwrap();
+        let exact = LevelSetup::read(&yaml_path);
+        let text = fs::read_to_string(&yaml_path).unwrap();
+        fs::write(&yaml_path, text.replace("reproduction: exact", "reproduction: normalized")).unwrap();
+        let normalized = LevelSetup::read(&yaml_path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(exact, Err(ReadError::Field{section: "size", ..})));
+        assert_eq!(normalized.unwrap().to_bytes(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn dialog_parse_rejects_truncated_bytes(){
+        let bytes = dialog_bytes(&[(0x80, b"hello\0")], &[(0x81, b"bye\0")]);
+        assert_eq!(Dialog::parse(&bytes).unwrap().to_bytes(), bytes);
+        for len in 0..bytes.len(){
+            assert!(matches!(Dialog::parse(&bytes[..len]), Err(text::TextError::Truncated{..})), "{} bytes parsed", len);
+        }
+        //the dispatcher keeps what it can't parse
+        assert_eq!(from_seg_indx_and_bytes(4, 0, &bytes[..bytes.len() - 2]).to_bytes(), bytes[..bytes.len() - 2].to_vec());
+    }
+
+    #[test]
+    fn questions_parse_once_for_both_types(){
+        let mut bytes = magic::QUIZ_QUESTION.to_vec();
+        bytes.push(4);
+        for string in [&b"who?\0"[..], b"a\0", b"b\0", b"c\0"]{
+            bytes.extend_from_slice(&[0x80, string.len() as u8]);
+            bytes.extend_from_slice(string);
+        }
+        let asset = from_seg_indx_and_bytes(4, 0, &bytes);
+        assert_eq!(asset.get_type(), AssetType::QuizQuestion);
+        assert_eq!(asset.to_bytes(), bytes);
+        let quiz = QuizQuestion::parse(&bytes).unwrap();
+        assert!(text::TextAsset::shares_count(&quiz));
+        assert_eq!(text::TextAsset::sections(&quiz)[0].1.len(), 1);
+
+        let mut grunty = bytes.clone();
+        grunty[..5].copy_from_slice(&magic::GRUNTY_QUESTION);
+        assert_eq!(from_seg_indx_and_bytes(4, 0, &grunty).get_type(), AssetType::GruntyQuestion);
+        //a question without its options stays binary instead of aborting extraction
+        let short = &bytes[..bytes.len() - 3];
+        assert_eq!(from_seg_indx_and_bytes(4, 0, short).to_bytes(), short.to_vec());
+    }
+
+    #[test]
+    fn demo_inputs_out_of_range_are_refused(){
+        let dir = scratch_dir("demo_range");
+        let yaml = "type: DemoInput\ninputs:\n  - {x: 0, y: 0, buttons: [A], frames: 1}\n  - {x: 200, y: 0, buttons: [A], frames: 1}\n";
+        let json = "{\"type\": \"DemoInput\", \"inputs\": [{\"x\": 0, \"y\": 0, \"buttons\": [], \"frames\": 300}]}";
+        for (name, text, index, field) in [("0001.demo", yaml, 1, "x 200"), ("0001.demo.json", json, 0, "frames 300")]{
+            let path = dir.join(name);
+            fs::write(&path, text).unwrap();
+            match DemoButtonFile::read(&path){
+                Err(ReadError::Field{section: "inputs", index: Some(i), reason, ..}) => {
+                    assert_eq!(i, index, "{}", name);
+                    assert!(reason.starts_with(field), "{}: {}", name, reason);
+                },
+                _ => panic!("{} was not refused", name),
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    //two tracks, the first with tempo, loop markers, running status, a repeated block and padding
+    fn sequence_bytes() -> Vec<u8>{
+        let track_0 : Vec<u8> = vec![
+            0x00, 0xFF, 0x51, 0x07, 0xA1, 0x20,
+            0x00, 0xFF, 0x2E, 0x00, 0xFF,
+            0x00, 0x90, 0x3C, 0x40, 0x10,
+            0x10, 0x3E, 0x40, 0x10,
+            0xFE, 0x00, 0x04, 0x04,
+            0x10, 0xFF, 0x2D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12,
+            0x00, 0xFF, 0x2F,
+            0x00, 0x00,
+        ];
+        let track_1 : Vec<u8> = vec![0x00, 0xC0, 0x05, 0x00, 0xFE, 0xFE, 0x7F, 0x00, 0x00, 0xFF, 0x2F];
+        let mut offsets = [0u32; midi::TRACK_COUNT];
+        offsets[0] = midi::HEADER_LEN as u32;
+        offsets[3] = (midi::HEADER_LEN + track_0.len()) as u32;
+        let mut out = midi::SeqHeader{track_offsets: offsets, division: 480}.to_bytes();
+        out.extend(track_0);
+        out.extend(track_1);
+        return out
+    }
+
+    #[test]
+    fn sequences_rebuild_byte_identical(){
+        let dir = scratch_dir("sequences");
+        let bytes = sequence_bytes();
+        let seq = MidiSeqFile::from_bytes(&bytes);
+        assert!(seq.header().is_some());
+        assert_eq!(seq.to_bytes(), bytes);
+
+        let bin = dir.join("0001.midi.bin");
+        seq.write_with(&bin, &MidiWriteOptions::default().standard_midi(true));
+        assert!(dir.join("0001.midi.mid").exists());
+        assert_eq!(MidiSeqFile::read(&dir.join("0001.midi.yaml")).unwrap().to_bytes(), bytes);
+        assert_eq!(MidiSeqFile::read(&bin).unwrap().to_bytes(), bytes);
+
+        //a header that doesn't belong to the bytes is an error, not a panic
+        let header = seq.header().unwrap();
+        let short = &bytes[..midi::HEADER_LEN + 8];
+        assert!(midi::to_yaml(short, header).is_err());
+        assert!(midi::to_standard_midi(short, header).is_err());
+        assert!(midi::set_loop_points(short, header, 0, 0x10).is_err());
+
+        //a header pointing past the end stays opaque and still writes back as is
+        let mut broken = bytes.clone();
+        broken.truncate(midi::HEADER_LEN + 4);
+        assert!(MidiSeqFile::from_bytes(&broken).header().is_none());
+        assert_eq!(MidiSeqFile::from_bytes(&broken).to_bytes(), broken);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn set_tempo_only_touches_the_tempo_bytes(){
+        //track 0 repeats its second note through a 0xFE block after the tempo
+        let bytes = sequence_bytes();
+        let header = MidiSeqFile::from_bytes(&bytes).header().unwrap().clone();
+        let edited = midi::set_tempo(&bytes, &header, 1_000_000).unwrap();
+        assert_eq!(edited.len(), bytes.len());
+        let changed : Vec<usize> = (0..bytes.len()).filter(|i| bytes[*i] != edited[*i]).collect();
+        let tempo_at = midi::HEADER_LEN + 3;
+        assert_eq!(changed, (tempo_at..tempo_at + 3).collect::<Vec<usize>>());
+        assert_eq!(&edited[tempo_at..tempo_at + 3], &[0x0F, 0x42, 0x40]);
+        assert_eq!(midi::tempo(&edited, &header), Some(1_000_000));
+
+        //setting it back gives the original
+        assert_eq!(midi::set_tempo(&edited, &header, 0x07A120).unwrap(), bytes);
+    }
+
+    #[test]
+    fn sequences_past_the_last_tick_are_refused(){
+        let sequence = |track: Vec<u8>|{
+            let mut offsets = [0u32; midi::TRACK_COUNT];
+            offsets[0] = midi::HEADER_LEN as u32;
+            let mut out = midi::SeqHeader{track_offsets: offsets, division: 480}.to_bytes();
+            out.extend(track);
+            return out
+        };
+        //deltas of 0x0FFFFFFF, the longest a var len holds
+        let long_waits = |count: usize|{
+            let mut track = vec![0xFF, 0xFF, 0xFF, 0x7F, 0xC0, 0x05];
+            for _ in 1..count{
+                track.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F, 0x05]);
+            }
+            return track
+        };
+
+        let mut track = long_waits(17);
+        track.extend_from_slice(&[0x00, 0xFF, 0x2F]);
+        let bytes = sequence(track);
+        let seq = MidiSeqFile::from_bytes(&bytes);
+        assert!(seq.header().is_none());
+        assert_eq!(seq.to_bytes(), bytes);
+
+        //the events fit but a note held past the last tick does not
+        let mut track = long_waits(16);
+        track.extend_from_slice(&[0x00, 0x90, 0x3C, 0x40, 0xFF, 0xFF, 0xFF, 0x7F, 0x00, 0xFF, 0x2F]);
+        let bytes = sequence(track);
+        let seq = MidiSeqFile::from_bytes(&bytes);
+        let header = seq.header().unwrap();
+        assert_eq!(midi::to_standard_midi(&bytes, header), Err(midi::MidiError::TickOverflow{track: 0}));
+    }
+
+    #[test]
+    fn animations_rebuild_byte_identical(){
+        let dir = scratch_dir("animations");
+        let mut bytes : Vec<u8> = vec![0x00, 0x01, 0x00, 0x20, 0x00, 0x03, 0xAB, 0xCD];
+        bytes.extend_from_slice(&[0x00, 0x20, 0x00, 0x02, 0x00, 0x01, 0x00, 0x64, 0xC0, 0x20, 0xFF, 0xFB]);
+        bytes.extend_from_slice(&[0x0A, 0x5C, 0x00, 0x02, 0x7F, 0xFF, 0x7F, 0xFF, 0x80, 0x06, 0x80, 0x00]);
+        bytes.extend_from_slice(&[0x00, 0x38, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x12]);
+        let truncated = bytes[..0x10].to_vec();
+        for (name, bytes, parsed) in [("0001.anim", bytes, true), ("0002.anim", truncated, false)]{
+            let anim = Animation::from_bytes(&bytes);
+            assert_eq!(anim.file().is_some(), parsed, "{}", name);
+            assert_eq!(anim.to_bytes(), bytes, "{}", name);
+            let path = dir.join(name);
+            anim.write(&path);
+            assert_eq!(Animation::read(&path).unwrap().to_bytes(), bytes, "{}", name);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    //a model with rgba16 textures of the given sizes, each filled with its index + 1, and one image load per texture
+    fn texture_model_bytes(sizes: &[(u8, u8)]) -> Vec<u8>{
+        let mut data = Vec::new();
+        let mut entries = Vec::new();
+        for (i, (w, h)) in sizes.iter().enumerate(){
+            let entry = model::TextureEntry{offset: data.len() as u32, format: ImgFmt::RGBA16, unk06: 0, width: *w, height: *h, unk0a: [0; 6]};
+            entries.extend(entry.to_bytes());
+            data.resize(data.len() + 2*(*w as usize)*(*h as usize), i as u8 + 1);
+        }
+        let mut list = (data.len() as u32).to_be_bytes().to_vec();
+        list.extend_from_slice(&(sizes.len() as u16).to_be_bytes());
+        list.extend_from_slice(&[0, 0]);
+        list.extend(entries);
+        list.extend(data);
+        let gfx_list = model::HEADER_LEN + list.len();
+        let header = model::ModelHeader{
+            geo_layout: 0, texture_list: model::HEADER_LEN as u16, geo_type: 0, gfx_list: gfx_list as u32, vtx_list: 0,
+            unk14: 0, bone_list: 0, collision_list: 0, mesh_list: 0, effects_setup: 0, unk28: 0, anim_texture_list: 0,
+            tri_count: 0, vtx_count: 0, unk34: 0,
+        };
+        let mut out = header.to_bytes();
+        out.extend(list);
+        out.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        let segment = (gfx::TEXTURE_SEGMENT as u32) << 24;
+        let mut offset = 0;
+        for (w, h) in sizes.iter(){
+            out.extend_from_slice(&0xFD10_0000u32.to_be_bytes());
+            out.extend_from_slice(&(segment + offset).to_be_bytes());
+            offset += 2*(*w as u32)*(*h as u32);
+        }
+        return out
+    }
+
+    #[test]
+    fn identity_edits_leave_animation_bytes_alone(){
+        //frames 1 to 0x20, every key inside that range, flags and extreme values included
+        let mut bytes : Vec<u8> = vec![0x00, 0x01, 0x00, 0x20, 0x00, 0x02, 0xAB, 0xCD];
+        bytes.extend_from_slice(&[0x00, 0x20, 0x00, 0x03, 0x00, 0x01, 0x00, 0x64, 0xC0, 0x10, 0xFF, 0xFB, 0x40, 0x20, 0x7F, 0xFF]);
+        bytes.extend_from_slice(&[0x00, 0x56, 0x00, 0x02, 0x00, 0x01, 0x12, 0x34, 0x80, 0x06, 0x80, 0x00]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x12]);
+        let file = anim::AnimFile::from_bytes(&bytes).unwrap();
+        assert_eq!(file.to_bytes(), bytes);
+
+        let mut scaled = file.clone();
+        scaled.scale_time(1.0).unwrap();
+        assert_eq!(scaled.to_bytes(), bytes, "scale_time(1.0)");
+        let mut trimmed = file.clone();
+        trimmed.trim(file.start_frame, file.end_frame).unwrap();
+        assert_eq!(trimmed.to_bytes(), bytes, "trim to the full range");
+    }
+
+    #[test]
+    fn grown_textures_move_the_ones_after_them(){
+        let segment = (gfx::TEXTURE_SEGMENT as u32) << 24;
+        let bytes = texture_model_bytes(&[(4, 4), (4, 2)]);
+        let mut model = Model::parse(&bytes).unwrap();
+        model.replace_texture(0, &[0x80; 4*8*4], 8, 4).unwrap();
+
+        let entries = model.texture_entries();
+        assert_eq!((entries[0].offset, entries[0].width, entries[0].height), (0, 8, 4));
+        assert_eq!(entries[1].offset, 2*8*4);
+        assert_eq!(model.bytes.len(), bytes.len() + 2*4*4, "the old slot is reused, not left behind");
+        let (_, data_start) = model.header.as_ref().unwrap().textures(&model.bytes).unwrap();
+        assert_eq!(model::u32_at(&model.bytes, model::HEADER_LEN), 2*8*4 + 2*4*2);
+        assert_eq!(&model.bytes[data_start + 2*8*4..data_start + 2*8*4 + 2*4*2], &[2; 2*4*2]);
+        let loads : Vec<u32> = model.display_list().unwrap().commands.iter().map(|c| c.w1).collect();
+        assert_eq!(loads, vec![segment, segment + 2*8*4]);
+
+        //shrinking stays in place
+        model.replace_texture(1, &[0x80; 4*2*2], 2, 2).unwrap();
+        assert_eq!(model.texture_entries()[1].offset, 2*8*4);
+        assert_eq!(model.bytes.len(), bytes.len() + 2*4*4);
+
+        //an entry pointing past the texture data is refused, not sliced
+        let mut broken = bytes.clone();
+        let second = model::HEADER_LEN + 8 + model::TextureEntry::LEN;
+        broken[second..second + 4].copy_from_slice(&0x100u32.to_be_bytes());
+        let mut broken = Model::parse(&broken).unwrap();
+        assert!(matches!(broken.replace_texture(1, &[0x80; 4*4*2], 4, 2), Err(model::ModelError::BadTexture{index: 1, ..})));
+        let mut long = bytes.clone();
+        long[model::HEADER_LEN..model::HEADER_LEN + 4].copy_from_slice(&0x1000u32.to_be_bytes());
+        let mut long = Model::parse(&long).unwrap();
+        assert!(matches!(long.replace_texture(0, &[0x80; 4*8*4], 8, 4), Err(model::ModelError::Truncated{..})));
+    }
+
+    //a model with three vertices, one bone and, when asked, a collision list of 100 unit cells over them
+    fn geometry_model_bytes(collision: bool) -> Vec<u8>{
+        let positions : [[i16; 3]; 3] = [[-10, 0, -30], [10, 20, 0], [0, -20, 30]];
+        let mut vtx_list = Vec::new();
+        for word in [-10i16, -20, -30, 10, 20, 30, 0, 0, 0, 38, 3, 38]{
+            vtx_list.extend_from_slice(&word.to_be_bytes());
+        }
+        for (i, position) in positions.iter().enumerate(){
+            let i = i as i16;
+            vtx_list.extend(model::ModelVertex{position: *position, flag: 0x8000 | i as u16, uv: [-0x20*i, 0x7FFF - i], color: [0xFF, 0x80, i as u8, 0x7F]}.to_bytes());
+        }
+        let mut bone_list = vec![0, 0, 0, 0, 0, 1, 0, 0];
+        for value in [1.5f32, -0.0, 250.0]{
+            bone_list.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+        bone_list.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF]);
+        //the triangle spans the 2x2x2 cells around the origin
+        let collision_list = model::CollisionMesh{
+            min: [-1, -1, -1], max: [0, 0, 0], stride: [2, 4], unk14: 100,
+            cells: (0..8).map(|i| (i, 1)).collect(),
+            triangles: vec![model::CollisionTri{vertices: [0, 1, 2], unk6: 0, flags: 0x10}; 8],
+        }.to_bytes();
+
+        let vtx_offset = model::HEADER_LEN;
+        let collision_offset = vtx_offset + vtx_list.len();
+        let bone_offset = collision_offset + if collision { collision_list.len() } else { 0 };
+        let header = model::ModelHeader{
+            geo_layout: 0, texture_list: 0, geo_type: 0, gfx_list: 0, vtx_list: vtx_offset as u32,
+            unk14: 0, bone_list: bone_offset as u32, collision_list: if collision { collision_offset as u32 } else { 0 },
+            mesh_list: 0, effects_setup: 0, unk28: 0, anim_texture_list: 0,
+            tri_count: 1, vtx_count: 3, unk34: 0,
+        };
+        let mut out = header.to_bytes();
+        out.extend(vtx_list);
+        if collision{
+            out.extend(collision_list);
+        }
+        out.extend(bone_list);
+        return out
+    }
+
+    #[test]
+    fn unmodified_vertices_write_back_the_same_section(){
+        let bytes = geometry_model_bytes(false);
+        let mut model = Model::parse(&bytes).unwrap();
+        let (start, count) = model.header.as_ref().unwrap().vertex_range(&bytes).unwrap().unwrap();
+        let vertices = model.vertices().unwrap();
+        assert_eq!(vertices.len(), count);
+        let section : Vec<u8> = vertices.iter().flat_map(|v| v.to_bytes()).collect();
+        assert_eq!(section, &bytes[start..start + count*model::ModelVertex::LEN]);
+        model.set_vertices(&vertices).unwrap();
+        assert_eq!(model.bytes, bytes);
+        assert_eq!(model.set_vertices(&vertices[1..]), Err(model::ModelError::VertexCount{expected: 3, found: 2}));
+        assert_eq!(model.bytes, bytes);
+    }
+
+    #[test]
+    fn glb_layout_of_a_small_model(){
+        //the geometry fixture plus a display list drawing its one triangle
+        let mut bytes = geometry_model_bytes(false);
+        let gfx_list = bytes.len();
+        bytes[0x0C..0x10].copy_from_slice(&(gfx_list as u32).to_be_bytes());
+        let commands = [gfx::GfxCommand::vtx(0, 3, (gfx::VTX_SEGMENT as u32) << 24), gfx::GfxCommand::tri1([0, 1, 2]), gfx::GfxCommand::end_dl()];
+        bytes.extend_from_slice(&(commands.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        for command in commands.iter(){
+            bytes.extend(command.to_bytes());
+        }
+        let glb = Model::parse(&bytes).unwrap().to_gltf().unwrap();
+
+        let word = |at: usize| u32::from_le_bytes([glb[at], glb[at + 1], glb[at + 2], glb[at + 3]]) as usize;
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!((word(4), word(8)), (2, glb.len()));
+        let json_len = word(12);
+        assert_eq!(&glb[16..20], b"JSON");
+        let bin_at = 20 + json_len;
+        assert_eq!(&glb[bin_at + 4..bin_at + 8], b"BIN\0");
+        let bin = &glb[bin_at + 8..bin_at + 8 + word(bin_at)];
+        assert_eq!(bin_at + 8 + bin.len(), glb.len());
+
+        let json = &YamlLoader::load_from_str(std::str::from_utf8(&glb[20..bin_at]).unwrap()).unwrap()[0];
+        assert_eq!(json["buffers"][0]["byteLength"].as_i64(), Some(bin.len() as i64));
+        let primitive = &json["meshes"][0]["primitives"][0];
+        let accessor = |name: &str|{
+            let index = match name{
+                "indices" => primitive["indices"].as_i64(),
+                _ => primitive["attributes"][name].as_i64(),
+            };
+            let accessor = &json["accessors"][index.unwrap() as usize];
+            let view = &json["bufferViews"][accessor["bufferView"].as_i64().unwrap() as usize];
+            let at = view["byteOffset"].as_i64().unwrap() as usize;
+            let len = view["byteLength"].as_i64().unwrap() as usize;
+            (accessor.clone(), bin[at..at + len].to_vec())
+        };
+
+        let (positions, data) = accessor("POSITION");
+        assert_eq!((positions["type"].as_str(), positions["componentType"].as_i64(), positions["count"].as_i64()), (Some("VEC3"), Some(5126), Some(3)));
+        let floats : Vec<f32> = data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(floats, vec![-10.0, 0.0, -30.0, 10.0, 20.0, 0.0, 0.0, -20.0, 30.0]);
+        assert_eq!(positions["min"], YamlLoader::load_from_str("[-10, -20, -30]").unwrap()[0]);
+        assert_eq!(positions["max"], YamlLoader::load_from_str("[10, 20, 30]").unwrap()[0]);
+
+        let (colors, data) = accessor("COLOR_0");
+        assert_eq!((colors["type"].as_str(), colors["count"].as_i64(), data.len()), (Some("VEC4"), Some(3), 3*4*4));
+        assert_eq!(&data[0..4], &1.0f32.to_le_bytes());
+        let (indices, data) = accessor("indices");
+        assert_eq!((indices["type"].as_str(), indices["componentType"].as_i64(), indices["count"].as_i64()), (Some("SCALAR"), Some(5125), Some(3)));
+        let indices : Vec<u32> = data.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert!(primitive["attributes"]["TEXCOORD_0"].is_badvalue());
+        assert_eq!(json["nodes"].as_vec().unwrap().len(), 2, "one node for the bone, one for the mesh");
+    }
+
+    #[test]
+    fn transforms_regrid_collision(){
+        //scale 1 with no offset gives the bin back byte for byte, collision or not
+        for collision in [false, true]{
+            let bytes = geometry_model_bytes(collision);
+            let mut model = Model::parse(&bytes).unwrap();
+            model.transform(1.0, [0.0; 3]).unwrap();
+            assert_eq!(model.bytes, bytes, "collision: {}", collision);
+        }
+
+        let tri = model::CollisionTri{vertices: [0, 1, 2], unk6: 0, flags: 0x10};
+        let mut model = Model::parse(&geometry_model_bytes(true)).unwrap();
+        model.transform(2.0, [0.0; 3]).unwrap();
+        let header = model.header.clone().unwrap();
+        let positions : Vec<[i16; 3]> = header.vertices(&model.bytes).unwrap().iter().map(|v| v.position).collect();
+        assert_eq!(positions, vec![[-20, 0, -60], [20, 40, 0], [0, -40, 60]]);
+        assert_eq!(header.skeleton(&model.bytes).unwrap().bones[0].position, [3.0, -0.0, 500.0]);
+        let collision = header.collision(&model.bytes).unwrap().unwrap();
+        assert_eq!((collision.min, collision.max, collision.stride), ([-1, -1, -1], [0, 0, 0], [2, 4]));
+        assert_eq!(collision.triangles, vec![tri; 8]);
+
+        //moved past x = 100 the triangle only touches the upper column of cells
+        let mut model = Model::parse(&geometry_model_bytes(true)).unwrap();
+        model.transform(1.0, [150.0, 0.0, 0.0]).unwrap();
+        let header = model.header.clone().unwrap();
+        let collision = header.collision(&model.bytes).unwrap().unwrap();
+        assert_eq!((collision.min, collision.max, collision.stride), ([1, -1, -1], [1, 0, 0], [1, 2]));
+        assert_eq!(collision.cells, vec![(0, 1), (1, 1), (2, 1), (3, 1)]);
+        assert_eq!(collision.triangles, vec![tri; 4]);
+        assert_eq!(header.skeleton(&model.bytes).unwrap().bones[0].position, [151.5, -0.0, 250.0]);
+
+        //a grid this tool can't make sense of is refused before anything changes
+        let mut bytes = geometry_model_bytes(true);
+        let stride = model::HEADER_LEN + model::VTX_HEADER_LEN + 3*model::ModelVertex::LEN + 0xC;
+        bytes[stride..stride + 2].copy_from_slice(&3u16.to_be_bytes());
+        let mut model = Model::parse(&bytes).unwrap();
+        assert!(matches!(model.transform(2.0, [0.0; 3]), Err(model::ModelError::CollisionGrid(_))));
+        assert_eq!(model.bytes, bytes);
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// maps font bytes to the text shown for them in yamls, bytes without an entry fall back to `\xNN` escapes
+#[derive(Clone)]
+pub struct CharTable{
+    base: &'static [(u8, &'static str)],
+    entries: Vec<(u8, String)>, //loaded entries, these win over `base`
+}
+
+// accented letters of the PAL fonts, these sit at their latin-1 code points
+const LATIN_1_GLYPHS : [(u8, &str); 24] = [
+    (0xC0, "À"), (0xC2, "Â"), (0xC4, "Ä"), (0xC7, "Ç"), (0xC8, "È"), (0xC9, "É"), (0xCA, "Ê"), (0xCE, "Î"), (0xD1, "Ñ"), (0xD6, "Ö"), (0xDC, "Ü"), (0xDF, "ß"),
+    (0xE0, "à"), (0xE2, "â"), (0xE4, "ä"), (0xE7, "ç"), (0xE8, "è"), (0xE9, "é"), (0xEA, "ê"), (0xEE, "î"), (0xF1, "ñ"), (0xF6, "ö"), (0xFC, "ü"), (0xFB, "û"),
+];
+
+/// table the text assets are written and read with unless another one is given
+pub const DEFAULT : CharTable = CharTable{base: &LATIN_1_GLYPHS, entries: Vec::new()};
+
+impl Default for CharTable{
+    fn default() -> CharTable{
+        DEFAULT
+    }
+}
+
+impl CharTable{
+    pub fn empty() -> CharTable{
+        CharTable{base: &[], entries: Vec::new()}
+    }
+
+    /// reads a yaml map of byte to text, e.g. `0x80: "(A)"`, entries override the default table
+    pub fn load(path: &Path) -> Result<CharTable, String>{
+        let text = fs::read_to_string(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        let docs = YamlLoader::load_from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let map = match docs.first(){
+            Some(Yaml::Hash(map)) => map,
+            _ => return Err(format!("{}: expected a map of byte to text", path.display())),
+        };
+        let mut table = DEFAULT;
+        for (k, v) in map.iter(){
+            let byte = match k.as_i64(){
+                Some(b) if (0x20..=0xFF).contains(&b) => b as u8,
+                _ => return Err(format!("{}: {:?} is not a byte from 0x20 to 0xFF", path.display(), k)),
+            };
+            let glyph = match v.as_str(){
+                Some(g) if !g.is_empty() && !g.contains('\\') => g.to_string(),
+                _ => return Err(format!("{}: entry 0x{:02X} needs non empty text without backslashes", path.display(), byte)),
+            };
+            table.set(byte, &glyph);
+        }
+        return Ok(table)
+    }
+
+    pub fn set(&mut self, byte: u8, glyph: &str){
+        self.entries.retain(|(b, _)| *b != byte);
+        self.entries.push((byte, glyph.to_string()));
+        //longest text first so encode prefers "(AB)" over "(A"
+        self.entries.sort_by_key(|e| std::cmp::Reverse(e.1.len()));
+    }
+
+    pub fn glyph(&self, byte: u8) -> Option<&str>{
+        match self.entries.iter().find(|(b, _)| *b == byte){
+            Some((_, g)) => Some(g.as_str()),
+            None => self.base.iter().find(|(b, _)| *b == byte).map(|(_, g)| *g),
+        }
+    }
+
+    /// a byte above ascii whose glyph is `letter` with an accent, e.g. `ä` for `a`
+    pub fn accented(&self, letter: u8) -> Option<u8>{
+        if !letter.is_ascii_alphabetic(){
+            return None
+        }
+        return (0x80..=0xFF).find(|b|{
+            let mut chars = self.glyph(*b).unwrap_or("").chars();
+            match (chars.next(), chars.next()){
+                (Some(c), None) => base_letter(c) == Some(letter as char),
+                _ => false,
+            }
+        })
+    }
+
+    /// the byte whose text `text` starts with, and the length of that text
+    pub fn match_prefix(&self, text: &str) -> Option<(u8, usize)>{
+        if let Some((b, g)) = self.entries.iter().find(|(_, g)| text.starts_with(g.as_str())){
+            return Some((*b, g.len()))
+        }
+        self.base.iter()
+            .filter(|(b, _)| !self.entries.iter().any(|(e, _)| e == b))
+            .find(|(_, g)| text.starts_with(g))
+            .map(|(b, g)| (*b, g.len()))
+    }
+}
+
+//the letter an accented latin-1 letter is drawn from
+fn base_letter(glyph: char) -> Option<char>{
+    let base = match glyph{
+        'À'..='Å' => 'A', 'Ç' => 'C', 'È'..='Ë' => 'E', 'Ì'..='Ï' => 'I', 'Ñ' => 'N', 'Ò'..='Ö' => 'O', 'Ù'..='Ü' => 'U', 'Ý' => 'Y',
+        'à'..='å' => 'a', 'ç' => 'c', 'è'..='ë' => 'e', 'ì'..='ï' => 'i', 'ñ' => 'n', 'ò'..='ö' => 'o', 'ù'..='ü' => 'u', 'ý' | 'ÿ' => 'y',
+        _ => return None,
+    };
+    return Some(base)
+}
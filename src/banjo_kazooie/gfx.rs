@@ -0,0 +1,396 @@
+use std::fmt;
+use std::ops::Range;
+
+/// model display lists are F3DEX: 8 byte commands, the opcode in the top byte of the first word.
+/// addresses are segmented, segment 1 is the model's vertices, 2 its texture data and 3 the display list itself
+pub const VTX_SEGMENT : u8 = 0x01;
+pub const TEXTURE_SEGMENT : u8 = 0x02;
+pub const GFX_SEGMENT : u8 = 0x03;
+
+/// slots in the RSP vertex cache
+pub const VTX_CACHE : usize = 32;
+
+pub const G_VTX : u8 = 0x04;
+pub const G_DL : u8 = 0x06;
+pub const G_TRI2 : u8 = 0xB1;
+pub const G_CLEARGEOMETRYMODE : u8 = 0xB6;
+pub const G_SETGEOMETRYMODE : u8 = 0xB7;
+pub const G_ENDDL : u8 = 0xB8;
+pub const G_TEXTURE : u8 = 0xBB;
+pub const G_TRI1 : u8 = 0xBF;
+pub const G_LOADBLOCK : u8 = 0xF3;
+pub const G_LOADTILE : u8 = 0xF4;
+pub const G_SETTILE : u8 = 0xF5;
+pub const G_SETTIMG : u8 = 0xFD;
+pub const G_LOADTLUT : u8 = 0xF0;
+pub const G_SETTILESIZE : u8 = 0xF2;
+pub const G_SETOTHERMODE_H : u8 = 0xBA;
+pub const G_SETCOMBINE : u8 = 0xFC;
+pub const G_RDPLOADSYNC : u8 = 0xE6;
+pub const G_RDPPIPESYNC : u8 = 0xE7;
+pub const G_RDPTILESYNC : u8 = 0xE8;
+
+/// geometry mode bits
+pub const G_SHADE : u32 = 0x0000_0004;
+pub const G_SHADING_SMOOTH : u32 = 0x0000_0200;
+pub const G_CULL_BACK : u32 = 0x0000_2000;
+pub const G_LIGHTING : u32 = 0x0002_0000;
+
+/// one raw command, kept as is so unmodified lists write back unchanged
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct GfxCommand{
+    pub w0: u32,
+    pub w1: u32,
+}
+
+/// what a command does, as far as rebuilding geometry needs
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum GfxOp{
+    Vtx{first: usize, count: usize, address: u32},
+    Tri1([usize; 3]),
+    Tri2([usize; 3], [usize; 3]),
+    Dl{branch: bool, address: u32},
+    EndDl,
+    Texture{scale_s: u16, scale_t: u16, tile: u8, on: bool},
+    SetTImg{format: u8, size: u8, width: usize, address: u32},
+    SetTile{format: u8, size: u8, tmem: u16, tile: u8, palette: u8},
+    LoadBlock{tile: u8},
+    LoadTile{tile: u8},
+    SetGeometryMode(u32),
+    ClearGeometryMode(u32),
+    Other(&'static str), //known command with no effect on the triangle list
+    Unknown(u8),
+}
+
+impl GfxCommand{
+    pub fn from_bytes(bytes: &[u8]) -> GfxCommand{
+        GfxCommand{
+            w0: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            w1: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.w0.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.w1.to_be_bytes());
+        return out
+    }
+
+    fn new(opcode: u8, w0: u32, w1: u32) -> GfxCommand{
+        GfxCommand{w0: (opcode as u32) << 24 | w0 & 0xFF_FFFF, w1: w1}
+    }
+
+    /// loads `count` vertices from `address` into the cache from slot `first`
+    pub fn vtx(first: usize, count: usize, address: u32) -> GfxCommand{
+        GfxCommand::new(G_VTX, ((first*2) as u32) << 16 | (count as u32) << 10 | (0x10*count as u32 - 1), address)
+    }
+
+    pub fn tri1(a: [usize; 3]) -> GfxCommand{
+        GfxCommand::new(G_TRI1, 0, GfxCommand::tri_word(a))
+    }
+
+    pub fn tri2(a: [usize; 3], b: [usize; 3]) -> GfxCommand{
+        GfxCommand::new(G_TRI2, GfxCommand::tri_word(a), GfxCommand::tri_word(b))
+    }
+
+    fn tri_word(slots: [usize; 3]) -> u32{
+        ((slots[0]*2) as u32) << 16 | ((slots[1]*2) as u32) << 8 | (slots[2]*2) as u32
+    }
+
+    pub fn end_dl() -> GfxCommand{
+        GfxCommand::new(G_ENDDL, 0, 0)
+    }
+
+    pub fn pipe_sync() -> GfxCommand{
+        GfxCommand::new(G_RDPPIPESYNC, 0, 0)
+    }
+
+    pub fn texture(on: bool) -> GfxCommand{
+        GfxCommand::new(G_TEXTURE, on as u32, 0xFFFF_FFFF)
+    }
+
+    pub fn geometry_mode(clear: u32, set: u32) -> [GfxCommand; 2]{
+        [GfxCommand::new(G_CLEARGEOMETRYMODE, 0, clear), GfxCommand::new(G_SETGEOMETRYMODE, 0, set)]
+    }
+
+    /// color combiner set to texture * shade, or shade alone
+    pub fn combine(textured: bool) -> GfxCommand{
+        match textured{
+            true => GfxCommand::new(G_SETCOMBINE, 0x12_1824, 0xFF33_FFFF),
+            false => GfxCommand::new(G_SETCOMBINE, 0xFF_FFFF, 0xFFFE_793C),
+        }
+    }
+
+    /// the commands gsDPLoadTLUT_pal16/pal256 expand to, the palette goes to the top half of TMEM
+    pub fn load_tlut(address: u32, colors: usize) -> Vec<GfxCommand>{
+        vec![
+            GfxCommand::new(G_SETOTHERMODE_H, 14 << 8 | 2, 2 << 14), //G_TT_RGBA16
+            GfxCommand::new(G_SETTIMG, 2 << 19, address),
+            GfxCommand::new(G_RDPTILESYNC, 0, 0),
+            GfxCommand::new(G_SETTILE, 0x100, 7 << 24),
+            GfxCommand::new(G_RDPLOADSYNC, 0, 0),
+            GfxCommand::new(G_LOADTLUT, 0, 7 << 24 | ((colors - 1) as u32 & 0x3FF) << 14),
+            GfxCommand::pipe_sync(),
+        ]
+    }
+
+    /// the commands gsDPLoadTextureBlock expands to for a `width` x `height` image of
+    /// `format` (0 RGBA, 2 CI, 3 IA) and `size` (0 4 bit .. 3 32 bit) at `address`
+    pub fn load_texture_block(format: u8, size: u8, width: usize, height: usize, address: u32) -> Vec<GfxCommand>{
+        let (fmt, siz) = (format as u32, size as u32);
+        //4 and 8 bit textures are loaded as if they were 16 bit
+        let load_siz = siz.max(2);
+        let texel_bits = 4 << siz;
+        let texels = match siz{
+            0 => (width*height + 3) >> 2,
+            1 => (width*height + 1) >> 1,
+            _ => width*height,
+        };
+        let words = (width*texel_bits/64).max(1);
+        let dxt = (1usize << 11).div_ceil(words);
+        //32 bit texels are split across both halves of TMEM so a line holds 16 bits per texel
+        let line = ((width*texel_bits.min(16)/8 + 7) >> 3) as u32;
+        //power of two sizes wrap, anything else is clamped by the missing mask
+        let mask = |n: usize| if n.is_power_of_two() { n.trailing_zeros() } else { 0 };
+        let wrap = mask(height) << 14 | mask(width) << 4;
+        vec![
+            GfxCommand::new(G_SETTIMG, fmt << 21 | load_siz << 19, address),
+            GfxCommand::new(G_SETTILE, fmt << 21 | load_siz << 19, 7 << 24 | wrap),
+            GfxCommand::new(G_RDPLOADSYNC, 0, 0),
+            GfxCommand::new(G_LOADBLOCK, 0, 7 << 24 | ((texels - 1).min(0x7FF) as u32) << 12 | dxt as u32),
+            GfxCommand::pipe_sync(),
+            GfxCommand::new(G_SETTILE, fmt << 21 | siz << 19 | line << 9, wrap),
+            GfxCommand::new(G_SETTILESIZE, 0, ((width - 1) as u32) << 14 | ((height - 1) as u32) << 2),
+        ]
+    }
+
+    pub fn opcode(&self) -> u8{
+        (self.w0 >> 24) as u8
+    }
+
+    pub fn op(&self) -> GfxOp{
+        let (w0, w1) = (self.w0, self.w1);
+        //F3DEX triangle indices are stored doubled
+        let tri = |w: u32| [(w >> 16 & 0xFF) as usize / 2, (w >> 8 & 0xFF) as usize / 2, (w & 0xFF) as usize / 2];
+        match self.opcode(){
+            G_VTX => GfxOp::Vtx{first: (w0 >> 16 & 0xFF) as usize / 2, count: (w0 >> 10 & 0x3F) as usize, address: w1},
+            G_DL => GfxOp::Dl{branch: w0 >> 16 & 0xFF != 0, address: w1},
+            G_TRI1 => GfxOp::Tri1(tri(w1)),
+            G_TRI2 => GfxOp::Tri2(tri(w0), tri(w1)),
+            G_ENDDL => GfxOp::EndDl,
+            G_TEXTURE => GfxOp::Texture{scale_s: (w1 >> 16) as u16, scale_t: w1 as u16, tile: (w0 >> 8 & 0x7) as u8, on: w0 & 0xFF != 0},
+            G_SETTIMG => GfxOp::SetTImg{format: (w0 >> 21 & 0x7) as u8, size: (w0 >> 19 & 0x3) as u8, width: (w0 & 0xFFF) as usize + 1, address: w1},
+            G_SETTILE => GfxOp::SetTile{format: (w0 >> 21 & 0x7) as u8, size: (w0 >> 19 & 0x3) as u8, tmem: (w0 & 0x1FF) as u16, tile: (w1 >> 24 & 0x7) as u8, palette: (w1 >> 20 & 0xF) as u8},
+            G_LOADBLOCK => GfxOp::LoadBlock{tile: (w1 >> 24 & 0x7) as u8},
+            G_LOADTILE => GfxOp::LoadTile{tile: (w1 >> 24 & 0x7) as u8},
+            G_SETGEOMETRYMODE => GfxOp::SetGeometryMode(w1),
+            G_CLEARGEOMETRYMODE => GfxOp::ClearGeometryMode(w1),
+            0x00 => GfxOp::Other("G_SPNOOP"),
+            0x01 => GfxOp::Other("G_MTX"),
+            0x03 => GfxOp::Other("G_MOVEMEM"),
+            0xB2 => GfxOp::Other("G_MODIFYVTX"),
+            0xB3 => GfxOp::Other("G_RDPHALF_2"),
+            0xB4 => GfxOp::Other("G_RDPHALF_1"),
+            0xB9 => GfxOp::Other("G_SETOTHERMODE_L"),
+            0xBA => GfxOp::Other("G_SETOTHERMODE_H"),
+            0xBC => GfxOp::Other("G_MOVEWORD"),
+            0xBD => GfxOp::Other("G_POPMTX"),
+            0xBE => GfxOp::Other("G_CULLDL"),
+            0xC0 => GfxOp::Other("G_NOOP"),
+            0xE6 => GfxOp::Other("G_RDPLOADSYNC"),
+            0xE7 => GfxOp::Other("G_RDPPIPESYNC"),
+            0xE8 => GfxOp::Other("G_RDPTILESYNC"),
+            0xE9 => GfxOp::Other("G_RDPFULLSYNC"),
+            0xF0 => GfxOp::Other("G_LOADTLUT"),
+            0xF2 => GfxOp::Other("G_SETTILESIZE"),
+            0xF7 => GfxOp::Other("G_SETFILLCOLOR"),
+            0xF8 => GfxOp::Other("G_SETFOGCOLOR"),
+            0xF9 => GfxOp::Other("G_SETBLENDCOLOR"),
+            0xFA => GfxOp::Other("G_SETPRIMCOLOR"),
+            0xFB => GfxOp::Other("G_SETENVCOLOR"),
+            0xFC => GfxOp::Other("G_SETCOMBINE"),
+            opcode => GfxOp::Unknown(opcode),
+        }
+    }
+}
+
+/// problems found while walking a display list, the walk carries on past all of them
+#[derive(PartialEq, Debug, Clone)]
+pub enum GfxIssue{
+    UnknownOpcode{command: usize, opcode: u8},
+    BadAddress{command: usize, address: u32},
+    VertexOutOfRange{command: usize, vertex: usize, count: usize},
+    EmptySlot{command: usize, slot: usize},
+    CacheOverflow{command: usize, first: usize, count: usize},
+    NoEndDl,
+    TooDeep{command: usize},
+}
+
+impl fmt::Display for GfxIssue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            GfxIssue::UnknownOpcode{command, opcode} => write!(f, "command {}: unknown opcode 0x{:02X}", command, opcode),
+            GfxIssue::BadAddress{command, address} => write!(f, "command {}: address 0x{:08X} is not in the model", command, address),
+            GfxIssue::VertexOutOfRange{command, vertex, count} => write!(f, "command {}: loads vertex {} of {}", command, vertex, count),
+            GfxIssue::EmptySlot{command, slot} => write!(f, "command {}: draws from vertex cache slot {} before anything was loaded", command, slot),
+            GfxIssue::CacheOverflow{command, first, count} => write!(f, "command {}: loads {} vertices from slot {}, the cache holds {}", command, count, first, VTX_CACHE),
+            GfxIssue::NoEndDl => write!(f, "display list does not end with G_ENDDL"),
+            GfxIssue::TooDeep{command} => write!(f, "command {}: display list calls nest too deep", command),
+        }
+    }
+}
+
+/// a drawn triangle, `vertices` index the model's vertex list and `texture` is the segment 2
+/// address of the last texture loaded before it
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Triangle{
+    pub vertices: [usize; 3],
+    pub texture: Option<u32>,
+    pub textured: bool, //G_TEXTURE was on
+    pub geometry_mode: u32,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DisplayList{
+    pub commands: Vec<GfxCommand>,
+}
+
+//the state the RSP keeps between commands
+struct Walker<'a>{
+    commands: &'a [GfxCommand],
+    vertex_count: usize,
+    cache: [Option<usize>; VTX_CACHE],
+    timg: Option<u32>,
+    texture: Option<u32>,
+    textured: bool,
+    geometry_mode: u32,
+    triangles: Vec<Triangle>,
+    issues: Vec<GfxIssue>,
+}
+
+const MAX_DEPTH : usize = 10;
+
+impl<'a> Walker<'a>{
+    //offset into segment `segment`, None for anything else
+    fn segment_offset(address: u32, segment: u8) -> Option<usize>{
+        match (address >> 24) as u8 == segment{
+            true => Some((address & 0xFF_FFFF) as usize),
+            false => None,
+        }
+    }
+
+    fn draw(&mut self, command: usize, slots: [usize; 3]){
+        let mut vertices = [0; 3];
+        for (i, slot) in slots.iter().enumerate(){
+            match self.cache.get(*slot).copied().flatten(){
+                Some(vertex) => vertices[i] = vertex,
+                None => {
+                    self.issues.push(GfxIssue::EmptySlot{command: command, slot: *slot});
+                    return;
+                },
+            }
+        }
+        self.triangles.push(Triangle{vertices: vertices, texture: self.texture, textured: self.textured, geometry_mode: self.geometry_mode});
+    }
+
+    fn run(&mut self, start: usize, depth: usize){
+        let mut i = start;
+        while let Some(command) = self.commands.get(i){
+            match command.op(){
+                GfxOp::Vtx{first, count, address} => {
+                    if first + count > VTX_CACHE{
+                        self.issues.push(GfxIssue::CacheOverflow{command: i, first: first, count: count});
+                    }
+                    match Walker::segment_offset(address, VTX_SEGMENT){
+                        Some(offset) => for n in 0..count.min(VTX_CACHE.saturating_sub(first)){
+                            let vertex = offset/0x10 + n;
+                            if vertex >= self.vertex_count{
+                                self.issues.push(GfxIssue::VertexOutOfRange{command: i, vertex: vertex, count: self.vertex_count});
+                                self.cache[first + n] = None;
+                                continue;
+                            }
+                            self.cache[first + n] = Some(vertex);
+                        },
+                        None => self.issues.push(GfxIssue::BadAddress{command: i, address: address}),
+                    }
+                },
+                GfxOp::Tri1(a) => self.draw(i, a),
+                GfxOp::Tri2(a, b) => {
+                    self.draw(i, a);
+                    self.draw(i, b);
+                },
+                GfxOp::Dl{branch, address} => {
+                    let target = Walker::segment_offset(address, GFX_SEGMENT).filter(|offset| offset/8 < self.commands.len());
+                    match (target, depth < MAX_DEPTH){
+                        (None, _) => self.issues.push(GfxIssue::BadAddress{command: i, address: address}),
+                        (Some(_), false) => self.issues.push(GfxIssue::TooDeep{command: i}),
+                        (Some(offset), true) => {
+                            self.run(offset/8, depth + 1);
+                            if branch{
+                                return;
+                            }
+                        },
+                    }
+                },
+                GfxOp::EndDl => return,
+                GfxOp::Texture{on, ..} => self.textured = on,
+                GfxOp::SetTImg{address, ..} => self.timg = Some(address),
+                //palettes go through G_LOADTLUT, only block and tile loads bring in the image
+                GfxOp::LoadBlock{..} | GfxOp::LoadTile{..} => self.texture = self.timg,
+                GfxOp::SetGeometryMode(bits) => self.geometry_mode |= bits,
+                GfxOp::ClearGeometryMode(bits) => self.geometry_mode &= !bits,
+                GfxOp::Unknown(opcode) => self.issues.push(GfxIssue::UnknownOpcode{command: i, opcode: opcode}),
+                GfxOp::SetTile{..} | GfxOp::Other(_) => {},
+            }
+            i += 1;
+        }
+        self.issues.push(GfxIssue::NoEndDl);
+    }
+}
+
+impl DisplayList{
+    pub fn from_bytes(bytes: &[u8]) -> DisplayList{
+        DisplayList{commands: bytes.chunks_exact(8).map(GfxCommand::from_bytes).collect()}
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        return self.commands.iter().flat_map(|c| c.to_bytes()).collect()
+    }
+
+    /// points every G_SETTIMG loading from `from` at the same place relative to `to`, returns how many changed
+    pub fn retarget_images(&mut self, from: Range<u32>, to: u32) -> usize{
+        let mut changed = 0;
+        for command in self.commands.iter_mut(){
+            if command.opcode() == G_SETTIMG && from.contains(&command.w1){
+                command.w1 = to + (command.w1 - from.start);
+                changed += 1;
+            }
+        }
+        return changed
+    }
+
+    /// every command the game would run that this tool doesn't know, with its index
+    pub fn unknown_commands(&self) -> Vec<(usize, GfxCommand)>{
+        return self.commands.iter().enumerate()
+            .filter(|(_, c)| matches!(c.op(), GfxOp::Unknown(_)))
+            .map(|(i, c)| (i, *c))
+            .collect()
+    }
+
+    /// runs the list from its first command the way the RSP would, `vertex_count` is the size of the model's vertex list
+    pub fn triangles(&self, vertex_count: usize) -> (Vec<Triangle>, Vec<GfxIssue>){
+        let mut walker = Walker{
+            commands: &self.commands,
+            vertex_count: vertex_count,
+            cache: [None; VTX_CACHE],
+            timg: None,
+            texture: None,
+            textured: false,
+            geometry_mode: 0,
+            triangles: Vec::new(),
+            issues: Vec::new(),
+        };
+        walker.run(0, 0);
+        return (walker.triangles, walker.issues)
+    }
+}
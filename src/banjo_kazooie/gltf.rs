@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+/// a glTF 2.0 document put together from json snippets, all binary data shares one buffer
+pub struct Gltf{
+    buffer: Vec<u8>,
+    sections: BTreeMap<&'static str, Vec<String>>,
+    pub scene_nodes: Vec<usize>,
+}
+
+const BASE64 : &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String{
+    let mut out = String::with_capacity(4*(bytes.len() + 2)/3);
+    for chunk in bytes.chunks(3){
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8*i));
+        for i in 0..4{
+            match i <= chunk.len(){
+                true => out.push(BASE64[(n >> (18 - 6*i) & 0x3F) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    return out
+}
+
+impl Default for Gltf{
+    fn default() -> Gltf{
+        return Gltf::new()
+    }
+}
+
+impl Gltf{
+    pub fn new() -> Gltf{
+        Gltf{buffer: Vec::new(), sections: BTreeMap::new(), scene_nodes: Vec::new()}
+    }
+
+    /// appends a json object to a top level list like "nodes" or "animations", returns its index
+    pub fn add(&mut self, section: &'static str, json: String) -> usize{
+        let list = self.sections.entry(section).or_default();
+        list.push(json);
+        return list.len() - 1
+    }
+
+    pub fn count(&self, section: &'static str) -> usize{
+        return self.sections.get(section).map(|l| l.len()).unwrap_or(0)
+    }
+
+    /// raw bytes in a buffer view of their own, returns the view
+    pub fn add_view(&mut self, bytes: &[u8]) -> usize{
+        while !self.buffer.len().is_multiple_of(4){
+            self.buffer.push(0);
+        }
+        let view = format!("{{\"buffer\": 0, \"byteOffset\": {}, \"byteLength\": {}}}", self.buffer.len(), bytes.len());
+        self.buffer.extend_from_slice(bytes);
+        return self.add("bufferViews", view)
+    }
+
+    /// float accessor of `kind` (SCALAR, VEC2, VEC3 or VEC4), `bounds` adds the min and max animation inputs and positions need
+    pub fn add_floats(&mut self, values: &[f32], kind: &str, bounds: bool) -> usize{
+        let width = match kind{ "VEC2" => 2, "VEC3" => 3, "VEC4" => 4, _ => 1 };
+        let bytes : Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let view = self.add_view(&bytes);
+        let mut accessor = format!("{{\"bufferView\": {}, \"componentType\": 5126, \"count\": {}, \"type\": \"{}\"", view, values.len()/width, kind);
+        if bounds{
+            let column = |i: usize| values.iter().skip(i).step_by(width).copied();
+            let min : Vec<String> = (0..width).map(|i| column(i).fold(f32::INFINITY, f32::min).to_string()).collect();
+            let max : Vec<String> = (0..width).map(|i| column(i).fold(f32::NEG_INFINITY, f32::max).to_string()).collect();
+            accessor += &format!(", \"min\": [{}], \"max\": [{}]", min.join(", "), max.join(", "));
+        }
+        return self.add("accessors", accessor + "}")
+    }
+
+    /// u32 accessor for primitive indices
+    pub fn add_indices(&mut self, indices: &[u32]) -> usize{
+        let bytes : Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = self.add_view(&bytes);
+        return self.add("accessors", format!("{{\"bufferView\": {}, \"componentType\": 5125, \"count\": {}, \"type\": \"SCALAR\"}}", view, indices.len()))
+    }
+
+    fn json(&self, buffer_uri: Option<String>) -> String{
+        let mut out = String::from("{\"asset\": {\"version\": \"2.0\", \"generator\": \"bk_asset_tool\"}");
+        let roots : Vec<String> = self.scene_nodes.iter().map(|n| n.to_string()).collect();
+        out += &format!(", \"scene\": 0, \"scenes\": [{{\"nodes\": [{}]}}]", roots.join(", "));
+        for (section, list) in self.sections.iter(){
+            out += &format!(",\n\"{}\": [\n  {}\n]", section, list.join(",\n  "));
+        }
+        if !self.buffer.is_empty(){
+            match buffer_uri{
+                Some(uri) => out += &format!(",\n\"buffers\": [{{\"byteLength\": {}, \"uri\": \"{}\"}}]", self.buffer.len(), uri),
+                None => out += &format!(",\n\"buffers\": [{{\"byteLength\": {}}}]", self.buffer.len()),
+            }
+        }
+        return out + "\n}\n"
+    }
+
+    /// a .gltf with the buffer embedded as a data uri
+    pub fn to_gltf(&self) -> String{
+        return self.json(Some(format!("data:application/octet-stream;base64,{}", base64(&self.buffer))))
+    }
+
+    /// a binary .glb
+    pub fn to_glb(&self) -> Vec<u8>{
+        let mut json = self.json(None).into_bytes();
+        while !json.len().is_multiple_of(4){
+            json.push(b' ');
+        }
+        let mut bin = self.buffer.clone();
+        while !bin.len().is_multiple_of(4){
+            bin.push(0);
+        }
+        let total = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+        let mut out : Vec<u8> = Vec::with_capacity(total);
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.append(&mut json);
+        if !bin.is_empty(){
+            out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            out.extend_from_slice(b"BIN\0");
+            out.append(&mut bin);
+        }
+        return out
+    }
+}
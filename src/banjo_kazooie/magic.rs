@@ -0,0 +1,24 @@
+use super::asset::AssetType;
+
+pub const MODEL           : [u8; 4] = [0x00, 0x00, 0x00, 0x0B];
+pub const QUIZ_QUESTION   : [u8; 5] = [0x01, 0x01, 0x02, 0x05, 0x00];
+pub const GRUNTY_QUESTION : [u8; 5] = [0x01, 0x03, 0x00, 0x05, 0x00];
+pub const DIALOG          : [u8; 3] = [0x01, 0x03, 0x00];
+
+/// header bytes an asset of this type starts with, if it has any
+pub fn for_type(asset_type: AssetType) -> Option<&'static [u8]>{
+    match asset_type{
+        AssetType::Model => Some(&MODEL),
+        AssetType::QuizQuestion => Some(&QUIZ_QUESTION),
+        AssetType::GruntyQuestion => Some(&GRUNTY_QUESTION),
+        AssetType::Dialog => Some(&DIALOG),
+        _ => None,
+    }
+}
+
+pub fn starts_with_magic(bytes: &[u8], asset_type: AssetType) -> bool{
+    match for_type(asset_type){
+        Some(magic) => bytes.starts_with(magic),
+        None => false,
+    }
+}
@@ -0,0 +1,911 @@
+use std::fmt::Write;
+use std::ops::Range;
+use yaml_rust::Yaml;
+
+/// segment 6 music is libultra's compressed midi (ALCSeq): 16 track offsets and the division,
+/// all big endian, followed by the track data. unused tracks have offset 0
+pub const TRACK_COUNT : usize = 16;
+pub const HEADER_LEN : usize = 4*TRACK_COUNT + 4;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SeqHeader{
+    pub track_offsets: [u32; TRACK_COUNT],
+    pub division: u32, //ticks per quarter note
+}
+
+impl SeqHeader{
+    /// None when the file is too short or a track offset points into the header or past the end
+    pub fn from_bytes(bytes: &[u8]) -> Option<SeqHeader>{
+        return SeqHeader::parse(bytes).ok()
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<SeqHeader, MidiError>{
+        if bytes.len() < HEADER_LEN{
+            return Err(MidiError::Short{len: bytes.len()});
+        }
+        let u32_at = |o: usize| u32::from_be_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+        let mut track_offsets = [0u32; TRACK_COUNT];
+        for (i, offset) in track_offsets.iter_mut().enumerate(){
+            *offset = u32_at(4*i);
+            if *offset != 0 && !(HEADER_LEN..bytes.len()).contains(&(*offset as usize)){
+                return Err(MidiError::OffsetOutOfRange{track: i, offset: *offset, len: bytes.len()});
+            }
+        }
+        return Ok(SeqHeader{track_offsets: track_offsets, division: u32_at(4*TRACK_COUNT)});
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out : Vec<u8> = self.track_offsets.iter().flat_map(|o| o.to_be_bytes()).collect();
+        out.extend_from_slice(&self.division.to_be_bytes());
+        return out;
+    }
+
+    pub fn used_tracks(&self) -> Vec<usize>{
+        return (0..TRACK_COUNT).filter(|i| self.track_offsets[*i] != 0).collect();
+    }
+
+    /// a track runs up to the next track that starts after it, the last one to the end of the file
+    pub fn track_range(&self, track: usize, file_len: usize) -> Option<Range<usize>>{
+        let start = *self.track_offsets.get(track)? as usize;
+        if start == 0{
+            return None;
+        }
+        let end = self.track_offsets.iter()
+            .map(|o| *o as usize)
+            .filter(|o| *o > start)
+            .min()
+            .unwrap_or(file_len);
+        return Some(start..end);
+    }
+
+    /// track_range for slicing `bytes`, an error when the track is unused or the header was parsed from other bytes
+    pub fn checked_range(&self, track: usize, bytes: &[u8]) -> Result<Range<usize>, MidiError>{
+        let range = self.track_range(track, bytes.len()).ok_or(MidiError::NoTrack{track: track})?;
+        if range.start > range.end || range.end > bytes.len(){
+            return Err(MidiError::OffsetOutOfRange{track: track, offset: range.start as u32, len: bytes.len()});
+        }
+        return Ok(range);
+    }
+}
+
+/// a decoded track event, note ons carry their length instead of having a note off
+#[derive(PartialEq, Debug, Clone)]
+pub enum SeqEvent{
+    Midi{status: u8, data: Vec<u8>, duration: Option<u32>},
+    Tempo(u32), //microseconds per quarter note
+    LoopStart{count: u8, flag: u8},
+    LoopEnd{count: u8, current: u8, offset: u32}, //offset jumps back from the end of the event
+    Meta(u8), //meta types the player skips
+    End,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TrackEvent{
+    pub time: u32, //ticks from the start of the track
+    pub offset: Option<usize>, //where the event starts in the track, None when it comes from a repeated block
+    pub event: SeqEvent,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum MidiError{
+    NoHeader,
+    Short{len: usize},
+    OffsetOutOfRange{track: usize, offset: u32, len: usize},
+    NoTrack{track: usize},
+    SharedTrack{track: usize, other: usize},
+    TrailingData{track: usize, end: usize, len: usize},
+    Truncated{track: usize, offset: usize},
+    BadBackReference{track: usize, offset: usize},
+    RepeatedBlock{track: usize, offset: usize},
+    NoTempo,
+    NoLoop,
+    BadLoop{start: u32, end: u32},
+    TickOverflow{track: usize},
+}
+
+impl std::fmt::Display for MidiError{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result{
+        match self{
+            MidiError::NoHeader => write!(f, "sequence header does not parse"),
+            MidiError::Short{len} => write!(f, "file is 0x{:X} bytes, shorter than the 0x{:X} byte header", len, HEADER_LEN),
+            MidiError::OffsetOutOfRange{track, offset, len} => write!(f, "track {} starts at 0x{:X}, outside the 0x{:X} byte file", track, offset, len),
+            MidiError::NoTrack{track} => write!(f, "track {} is not used by the sequence", track),
+            MidiError::SharedTrack{track, other} => write!(f, "tracks {} and {} start at the same offset", track, other),
+            MidiError::TrailingData{track, end, len} => write!(f, "track {} ends at 0x{:X} but is followed by 0x{:X} bytes of unused data", track, end, len),
+            MidiError::Truncated{track, offset} => write!(f, "track {} ends in the middle of an event at 0x{:X}", track, offset),
+            MidiError::BadBackReference{track, offset} => write!(f, "track {} repeats bytes from before its start at 0x{:X}", track, offset),
+            MidiError::RepeatedBlock{track, offset} => write!(f, "track {} would need a change inside the repeated block at 0x{:X}", track, offset),
+            MidiError::NoTempo => write!(f, "sequence has no tempo events"),
+            MidiError::NoLoop => write!(f, "sequence has no loop markers"),
+            MidiError::BadLoop{start, end} => write!(f, "loop from tick {} to {} does not fit the tracks", start, end),
+            MidiError::TickOverflow{track} => write!(f, "track {} runs past tick 0x{:X}, the last one a sequence can count to", track, u32::MAX),
+        }
+    }
+}
+
+// 0xFE starts a repeat of earlier bytes (hi, lo, len), 0xFE 0xFE is a literal 0xFE
+const BLOCK_CODE : u8 = 0xFE;
+
+//reads a track the way libultra's compressed sequence player does
+struct TrackReader<'a>{
+    data: &'a [u8],
+    track: usize,
+    pos: usize,
+    repeat: Option<(usize, usize)>, //position and remaining length of the block being repeated
+    repeats: Vec<Repeat>,
+    decoded: usize, //bytes handed to the event parser so far
+}
+
+//a 0xFE code of a track
+#[derive(Clone)]
+struct Repeat{
+    code: usize, //raw position of the code
+    src: usize, //raw position of the repeated bytes
+    len: usize,
+    decoded: usize, //index of the first repeated byte among the bytes the player reads
+}
+
+impl<'a> TrackReader<'a>{
+    fn truncated(&self) -> MidiError{
+        MidiError::Truncated{track: self.track, offset: self.pos}
+    }
+
+    fn raw(&mut self) -> Result<u8, MidiError>{
+        let b = *self.data.get(self.pos).ok_or(self.truncated())?;
+        self.pos += 1;
+        return Ok(b);
+    }
+
+    //position of the next byte when it is read from the track itself rather than a repeated block
+    fn literal_pos(&self) -> Option<usize>{
+        match self.repeat{
+            Some(_) => None,
+            None => Some(self.pos),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, MidiError>{
+        let b = self.next_byte()?;
+        self.decoded += 1;
+        return Ok(b);
+    }
+
+    //loop ends are read straight from the track, even in the middle of a repeated block
+    fn payload(&mut self) -> Result<[u8; 6], MidiError>{
+        let b = [self.raw()?, self.raw()?, self.raw()?, self.raw()?, self.raw()?, self.raw()?];
+        self.decoded += 6;
+        return Ok(b);
+    }
+
+    fn next_byte(&mut self) -> Result<u8, MidiError>{
+        if let Some((pos, len)) = self.repeat{
+            let b = *self.data.get(pos).ok_or(self.truncated())?;
+            self.repeat = if len > 1 { Some((pos + 1, len - 1)) } else { None };
+            return Ok(b);
+        }
+        let b = self.raw()?;
+        if b != BLOCK_CODE{
+            return Ok(b);
+        }
+        let start = self.pos - 1;
+        let hi = self.raw()?;
+        if hi == BLOCK_CODE{
+            return Ok(BLOCK_CODE);
+        }
+        let lo = self.raw()?;
+        let len = self.raw()? as usize;
+        let back = ((hi as usize) << 8) | lo as usize;
+        if back > start || len == 0{
+            return Err(MidiError::BadBackReference{track: self.track, offset: start});
+        }
+        self.repeats.push(Repeat{code: start, src: start - back, len: len, decoded: self.decoded});
+        self.repeat = match len{
+            1 => None,
+            _ => Some((start - back + 1, len - 1)),
+        };
+        return self.data.get(start - back).copied().ok_or(self.truncated());
+    }
+
+    fn var_len(&mut self) -> Result<u32, MidiError>{
+        let mut value : u32 = 0;
+        loop{
+            let b = self.byte()?;
+            value = (value << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0{
+                return Ok(value);
+            }
+        }
+    }
+}
+
+// one event of a scanned track with the raw positions around its delta and event bytes,
+// a position is None while a repeated block is being read
+struct Step{
+    time: u32,
+    delta: u32,
+    event: SeqEvent,
+    running: bool, //status byte left out
+    delta_at: Option<usize>,
+    event_at: Option<usize>,
+}
+
+struct TrackScan{
+    steps: Vec<Step>,
+    end: usize, //raw position after the end event, anything after it is padding
+    repeats: Vec<Repeat>,
+}
+
+fn scan_track(data: &[u8], track: usize) -> Result<TrackScan, MidiError>{
+    let mut reader = TrackReader{data: data, track: track, pos: 0, repeat: None, repeats: Vec::new(), decoded: 0};
+    let mut steps = Vec::new();
+    let mut time : u32 = 0;
+    let mut last_status : u8 = 0;
+    loop{
+        let delta_at = reader.literal_pos();
+        let delta = reader.var_len()?;
+        time = time.checked_add(delta).ok_or(MidiError::TickOverflow{track: track})?;
+        let event_at = reader.literal_pos();
+        let status = reader.byte()?;
+        let mut running = false;
+        let event = match status{
+            0xFF => {
+                last_status = 0;
+                match reader.byte()?{
+                    0x51 => {
+                        let t = [reader.byte()?, reader.byte()?, reader.byte()?];
+                        SeqEvent::Tempo(u32::from_be_bytes([0, t[0], t[1], t[2]]))
+                    },
+                    0x2F => SeqEvent::End,
+                    0x2E => SeqEvent::LoopStart{count: reader.byte()?, flag: reader.byte()?},
+                    0x2D => {
+                        let b = reader.payload()?;
+                        SeqEvent::LoopEnd{count: b[0], current: b[1], offset: u32::from_be_bytes([b[2], b[3], b[4], b[5]])}
+                    },
+                    kind => SeqEvent::Meta(kind),
+                }
+            },
+            _ => {
+                running = status & 0x80 == 0;
+                let (status, first) = match running{
+                    true => (last_status, status),
+                    false => (status, reader.byte()?),
+                };
+                if status == 0{
+                    return Err(reader.truncated());
+                }
+                last_status = status;
+                let mut data = vec![first];
+                let mut duration = None;
+                if status & 0xF0 != 0xC0 && status & 0xF0 != 0xD0{
+                    data.push(reader.byte()?);
+                    if status & 0xF0 == 0x90{
+                        duration = Some(reader.var_len()?);
+                    }
+                }
+                SeqEvent::Midi{status: status, data: data, duration: duration}
+            },
+        };
+        let end = event == SeqEvent::End;
+        steps.push(Step{time: time, delta: delta, event: event, running: running, delta_at: delta_at, event_at: event_at});
+        if end{
+            return Ok(TrackScan{steps: steps, end: reader.pos, repeats: reader.repeats});
+        }
+    }
+}
+
+/// decodes a track up to its end event, loops are not followed
+pub fn decode_track(data: &[u8], track: usize) -> Result<Vec<TrackEvent>, MidiError>{
+    let scan = scan_track(data, track)?;
+    return Ok(scan.steps.into_iter().map(|s| TrackEvent{time: s.time, offset: s.event_at, event: s.event}).collect());
+}
+
+fn write_var_len(out: &mut Vec<u8>, value: u32){
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut v = value >> 7;
+    while v != 0{
+        bytes.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+fn text_meta(kind: u8, text: &str) -> Vec<u8>{
+    let mut out = vec![0xFF, kind];
+    write_var_len(&mut out, text.len() as u32);
+    out.extend_from_slice(text.as_bytes());
+    return out;
+}
+
+/// an SMF type 1 file with one MTrk per used track. note ons get a matching note off, loop
+/// markers become `loop_start`/`loop_end` markers and other metas are kept as text events
+pub fn to_standard_midi(bytes: &[u8], header: &SeqHeader) -> Result<Vec<u8>, MidiError>{
+    let tracks = header.used_tracks();
+    let mut out : Vec<u8> = b"MThd".to_vec();
+    out.extend_from_slice(&6u32.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    out.extend_from_slice(&(header.division as u16).to_be_bytes());
+
+    for track in tracks{
+        let range = header.checked_range(track, bytes)?;
+        let events = decode_track(&bytes[range], track)?;
+        let mut timed : Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut end_time = 0;
+        for e in events.iter(){
+            let smf = match &e.event{
+                SeqEvent::Midi{status, data, duration} => {
+                    if let Some(d) = duration{
+                        let off = e.time.checked_add(*d).ok_or(MidiError::TickOverflow{track: track})?;
+                        timed.push((off, vec![0x80 | (status & 0x0F), data[0], 0]));
+                        end_time = end_time.max(off);
+                    }
+                    [&[*status], &data[..]].concat()
+                },
+                SeqEvent::Tempo(t) => { let b = t.to_be_bytes(); vec![0xFF, 0x51, 0x03, b[1], b[2], b[3]] },
+                SeqEvent::LoopStart{count, flag} => text_meta(0x06, &format!("loop_start {} {}", count, flag)),
+                SeqEvent::LoopEnd{count, current, offset} => text_meta(0x06, &format!("loop_end {} {} {}", count, current, offset)),
+                SeqEvent::Meta(kind) => text_meta(0x01, &format!("meta 0x{:02X}", kind)),
+                SeqEvent::End => { end_time = end_time.max(e.time); continue; },
+            };
+            timed.push((e.time, smf));
+        }
+        //note offs sort before anything else at the same tick so repeated notes don't cut each other
+        timed.sort_by_key(|(t, ev)| (*t, ev[0] & 0xF0 != 0x80));
+
+        let mut data : Vec<u8> = Vec::new();
+        let mut now = 0;
+        for (t, ev) in timed.iter(){
+            write_var_len(&mut data, t - now);
+            data.extend_from_slice(ev);
+            now = *t;
+        }
+        write_var_len(&mut data, end_time.saturating_sub(now));
+        data.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+        out.extend_from_slice(b"MTrk");
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.append(&mut data);
+    }
+    return Ok(out);
+}
+
+//bytes the player reads for an event, flagged when read straight from the track (loop end payloads)
+fn player_bytes(out: &mut Vec<(u8, bool)>, event: &SeqEvent, running: bool){
+    let mut push = |bytes: &[u8]| out.extend(bytes.iter().map(|b| (*b, false)));
+    match event{
+        SeqEvent::Midi{status, data, duration} => {
+            if !running{
+                push(&[*status]);
+            }
+            push(data);
+            if let Some(d) = duration{
+                let mut len = Vec::new();
+                write_var_len(&mut len, *d);
+                push(&len);
+            }
+        },
+        SeqEvent::Tempo(t) => { let b = t.to_be_bytes(); push(&[0xFF, 0x51, b[1], b[2], b[3]]) },
+        SeqEvent::LoopStart{count, flag} => push(&[0xFF, 0x2E, *count, *flag]),
+        SeqEvent::LoopEnd{count, current, offset} => {
+            push(&[0xFF, 0x2D]);
+            let o = offset.to_be_bytes();
+            out.extend([*count, *current, o[0], o[1], o[2], o[3]].iter().map(|b| (*b, true)));
+        },
+        SeqEvent::Meta(kind) => push(&[0xFF, *kind]),
+        SeqEvent::End => push(&[0xFF, 0x2F]),
+    }
+}
+
+//track bytes for what the player should read, 0xFE gets escaped and each (index, distance, length)
+//in `repeats` replaces that many bytes starting at the index with a repeat code
+fn compress(stream: &[(u8, bool)], repeats: &[(usize, usize, usize)]) -> Vec<u8>{
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < stream.len(){
+        if let Some((_, back, len)) = repeats.iter().find(|r| r.0 == i){
+            out.extend_from_slice(&[BLOCK_CODE, (back >> 8) as u8, *back as u8, *len as u8]);
+            i += len;
+            continue;
+        }
+        match stream[i]{
+            (BLOCK_CODE, false) => out.extend_from_slice(&[BLOCK_CODE, BLOCK_CODE]),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    return out;
+}
+
+fn encode_var_len(value: u32) -> Vec<u8>{
+    let mut bytes = Vec::new();
+    write_var_len(&mut bytes, value);
+    return compress(&bytes.iter().map(|b| (*b, false)).collect::<Vec<(u8, bool)>>(), &[]);
+}
+
+//event bytes for the track, midi events always get their status byte
+fn encode_event(event: &SeqEvent) -> Vec<u8>{
+    let mut stream = Vec::new();
+    player_bytes(&mut stream, event, false);
+    return compress(&stream, &[]);
+}
+
+// an event of a track being edited, `orig` is its step in the scan so unchanged events keep their bytes
+struct Entry{
+    delta: u32,
+    event: SeqEvent,
+    orig: Option<usize>,
+}
+
+fn entries(scan: &TrackScan) -> Vec<Entry>{
+    return scan.steps.iter().enumerate().map(|(i, s)| Entry{delta: s.delta, event: s.event.clone(), orig: Some(i)}).collect();
+}
+
+enum Piece{
+    Copy(usize), //delta (even) or event (odd) of the scan
+    Fresh(Vec<u8>),
+}
+
+//writes the edited track, bytes of unchanged deltas and events are copied and repeated blocks pointing
+//across a change get their distance fixed, changes inside a repeated block are refused
+fn rebuild_track(data: &[u8], track: usize, scan: &TrackScan, entries: &[Entry]) -> Result<Vec<u8>, MidiError>{
+    let mut bounds : Vec<Option<usize>> = scan.steps.iter().flat_map(|s| [s.delta_at, s.event_at]).collect();
+    bounds.push(Some(scan.end));
+
+    let mut pieces = Vec::new();
+    let mut last_status = 0;
+    for e in entries.iter(){
+        let orig = e.orig.map(|i| (i, &scan.steps[i]));
+        pieces.push(match orig{
+            Some((i, step)) if step.delta == e.delta => Piece::Copy(2*i),
+            _ => Piece::Fresh(encode_var_len(e.delta)),
+        });
+        let status = match &e.event{
+            SeqEvent::Midi{status, ..} => *status,
+            _ => 0,
+        };
+        pieces.push(match orig{
+            //running status only holds while the event before it still sets that status
+            Some((i, step)) if step.event == e.event && (!step.running || last_status == status) => Piece::Copy(2*i + 1),
+            _ => Piece::Fresh(encode_event(&e.event)),
+        });
+        last_status = status;
+    }
+
+    let refused = |k: usize| MidiError::RepeatedBlock{track: track, offset: bounds[..k].iter().rev().flatten().next().copied().unwrap_or(0)};
+    let mut out = Vec::new();
+    let mut moved : Vec<(usize, usize, usize)> = Vec::new(); //old position, new position, length
+    let mut p = 0;
+    while p < pieces.len(){
+        match &pieces[p]{
+            Piece::Fresh(bytes) => out.extend_from_slice(bytes),
+            Piece::Copy(k) => {
+                let start = bounds[*k].ok_or(refused(*k))?;
+                //pieces read out of a repeated block have no position of their own and move with the block
+                let mut j = k + 1;
+                while bounds[j].is_none(){
+                    match pieces.get(p + 1){
+                        Some(Piece::Copy(next)) if *next == j => p += 1,
+                        _ => return Err(refused(j)),
+                    }
+                    j += 1;
+                }
+                let end = bounds[j].unwrap();
+                moved.push((start, out.len(), end - start));
+                out.extend_from_slice(&data[start..end]);
+            },
+        }
+        p += 1;
+    }
+    out.extend_from_slice(&data[scan.end..]);
+
+    let new_pos = |old: usize| moved.iter().find(|(o, _, l)| (*o..o + l).contains(&old)).map(|(o, n, _)| n + old - o);
+    for Repeat{code, src, len, ..} in scan.repeats.iter(){
+        let code_at = match new_pos(*code){
+            Some(at) => at,
+            None => continue, //rewritten along with its event
+        };
+        let src_at = new_pos(*src).ok_or(MidiError::RepeatedBlock{track: track, offset: *code})?;
+        let back = code_at - src_at;
+        if (0..*len).any(|i| new_pos(src + i) != Some(src_at + i)) || back > 0xFFFF || back >> 8 == BLOCK_CODE as usize{
+            return Err(MidiError::RepeatedBlock{track: track, offset: *code});
+        }
+        out[code_at + 1] = (back >> 8) as u8;
+        out[code_at + 2] = back as u8;
+    }
+    return Ok(out);
+}
+
+//puts the sequence back together with the edited tracks, the header offsets follow the new lengths
+fn replace_tracks(bytes: &[u8], header: &SeqHeader, edited: &[(usize, Vec<u8>)]) -> Result<Vec<u8>, MidiError>{
+    let mut order = header.used_tracks();
+    order.sort_by_key(|t| header.track_offsets[*t]);
+    let first = order.first().map(|t| header.track_offsets[*t] as usize).unwrap_or(bytes.len());
+    let mut out = bytes[..first].to_vec();
+    let mut new_header = header.clone();
+    for track in order{
+        new_header.track_offsets[track] = out.len() as u32;
+        match edited.iter().find(|(t, _)| *t == track){
+            Some((_, data)) => out.extend_from_slice(data),
+            None => out.extend_from_slice(&bytes[header.checked_range(track, bytes)?]),
+        }
+    }
+    out[..HEADER_LEN].copy_from_slice(&new_header.to_bytes());
+    return Ok(out);
+}
+
+/// checks that no two tracks share their data, every track decodes up to its end event and
+/// only zero padding follows it, appended data would otherwise be dropped silently
+pub fn validate(bytes: &[u8], header: &SeqHeader) -> Result<(), MidiError>{
+    let tracks = header.used_tracks();
+    for (i, track) in tracks.iter().enumerate(){
+        if let Some(other) = tracks[..i].iter().find(|t| header.track_offsets[**t] == header.track_offsets[*track]){
+            return Err(MidiError::SharedTrack{track: *track, other: *other});
+        }
+    }
+    for (track, scan) in scan_all(bytes, header)?{
+        let range = header.checked_range(track, bytes)?;
+        let padding = &bytes[range.start + scan.end..range.end];
+        if padding.iter().any(|b| *b != 0){
+            return Err(MidiError::TrailingData{track: track, end: range.start + scan.end, len: padding.len()});
+        }
+    }
+    return Ok(());
+}
+
+fn scan_all(bytes: &[u8], header: &SeqHeader) -> Result<Vec<(usize, TrackScan)>, MidiError>{
+    return header.used_tracks().into_iter()
+        .map(|t| scan_track(&bytes[header.checked_range(t, bytes)?], t).map(|s| (t, s)))
+        .collect();
+}
+
+/// ticks of the first track's loop start and loop end markers
+pub fn loop_points(bytes: &[u8], header: &SeqHeader) -> Option<(u32, u32)>{
+    for (_, scan) in scan_all(bytes, header).ok()?{
+        let start = scan.steps.iter().find(|s| matches!(s.event, SeqEvent::LoopStart{..}));
+        let end = scan.steps.iter().find(|s| matches!(s.event, SeqEvent::LoopEnd{..}));
+        if let (Some(start), Some(end)) = (start, end){
+            return Some((start.time, end.time));
+        }
+    }
+    return None;
+}
+
+//raw positions after the first loop start and after the first loop end, a loop end's offset is usually their distance
+fn loop_span(scan: &TrackScan) -> Option<(usize, usize)>{
+    let after = |kind: fn(&SeqEvent) -> bool| -> Option<usize>{
+        let i = scan.steps.iter().position(|s| kind(&s.event))?;
+        return scan.steps.get(i + 1)?.delta_at;
+    };
+    return after(|e| matches!(e, SeqEvent::LoopStart{..})).zip(after(|e| matches!(e, SeqEvent::LoopEnd{..})));
+}
+
+/// moves the loop markers of every looping track to the given ticks, markers go in front of the
+/// events at that tick and the loop end jumps back to just after the loop start
+pub fn set_loop_points(bytes: &[u8], header: &SeqHeader, start: u32, end: u32) -> Result<Vec<u8>, MidiError>{
+    if end <= start{
+        return Err(MidiError::BadLoop{start: start, end: end});
+    }
+    let mut edited = Vec::new();
+    for (track, scan) in scan_all(bytes, header)?{
+        let loop_start = scan.steps.iter().position(|s| matches!(s.event, SeqEvent::LoopStart{..}));
+        let loop_end = scan.steps.iter().position(|s| matches!(s.event, SeqEvent::LoopEnd{..}));
+        let (loop_start, loop_end) = match (loop_start, loop_end){
+            (Some(s), Some(e)) => (scan.steps[s].event.clone(), scan.steps[e].event.clone()),
+            _ => continue,
+        };
+        if end > scan.steps.last().unwrap().time{
+            return Err(MidiError::BadLoop{start: start, end: end});
+        }
+
+        //take the markers out, their deltas go to the event after them
+        let mut list : Vec<Entry> = Vec::new();
+        let mut carry = 0;
+        for e in entries(&scan){
+            if matches!(e.event, SeqEvent::LoopStart{..} | SeqEvent::LoopEnd{..}){
+                carry += e.delta;
+                continue;
+            }
+            list.push(Entry{delta: e.delta + carry, ..e});
+            carry = 0;
+        }
+        for (tick, marker) in [(start, loop_start), (end, loop_end)]{
+            let mut time = 0;
+            let mut at = 0;
+            while time + list[at].delta < tick{
+                time += list[at].delta;
+                at += 1;
+            }
+            list[at].delta -= tick - time;
+            list.insert(at, Entry{delta: tick - time, event: marker, orig: None});
+        }
+
+        let mut data = rebuild_track(&bytes[header.checked_range(track, bytes)?], track, &scan, &list)?;
+        //point the loop end back at the bytes after the loop start
+        let (target, jump) = loop_span(&scan_track(&data, track)?).ok_or(MidiError::RepeatedBlock{track: track, offset: 0})?;
+        data[jump - 4..jump].copy_from_slice(&((jump - target) as u32).to_be_bytes());
+        edited.push((track, data));
+    }
+    if edited.is_empty(){
+        return Err(MidiError::NoLoop);
+    }
+    return replace_tracks(bytes, header, &edited);
+}
+
+/// microseconds per quarter note of the earliest tempo event
+pub fn tempo(bytes: &[u8], header: &SeqHeader) -> Option<u32>{
+    let scans = scan_all(bytes, header).ok()?;
+    return scans.iter()
+        .flat_map(|(_, scan)| scan.steps.iter())
+        .filter_map(|s| match s.event{ SeqEvent::Tempo(t) => Some((s.time, t)), _ => None })
+        .min_by_key(|(time, _)| *time)
+        .map(|(_, t)| t);
+}
+
+/// sets the starting tempo, later tempo changes are scaled by the same amount
+pub fn set_tempo(bytes: &[u8], header: &SeqHeader, tempo_us: u32) -> Result<Vec<u8>, MidiError>{
+    let first = tempo(bytes, header).ok_or(MidiError::NoTempo)?;
+    let mut edited = Vec::new();
+    for (track, scan) in scan_all(bytes, header)?{
+        if !scan.steps.iter().any(|s| matches!(s.event, SeqEvent::Tempo(_))){
+            continue;
+        }
+        let list : Vec<Entry> = entries(&scan).into_iter().map(|e| match e.event{
+            SeqEvent::Tempo(t) => {
+                let scaled = (t as u64 * tempo_us as u64 / first.max(1) as u64).clamp(1, 0xFFFFFF) as u32;
+                Entry{event: SeqEvent::Tempo(scaled), ..e}
+            },
+            _ => e,
+        }).collect();
+        edited.push((track, rebuild_track(&bytes[header.checked_range(track, bytes)?], track, &scan, &list)?));
+    }
+    return replace_tracks(bytes, header, &edited);
+}
+
+/// a program a track switches to, listed once per channel, bank and program
+#[derive(PartialEq, Debug, Clone)]
+pub struct InstrumentUse{
+    pub track: usize,
+    pub channel: u8,
+    pub bank: Option<u8>, //last bank select (cc 0) on the channel before the program change
+    pub program: u8,
+    pub first_tick: u32,
+}
+
+/// the programs every track selects, in track order and then by first use
+pub fn instruments(bytes: &[u8], header: &SeqHeader) -> Result<Vec<InstrumentUse>, MidiError>{
+    let mut out : Vec<InstrumentUse> = Vec::new();
+    for (track, scan) in scan_all(bytes, header)?{
+        let mut banks : [Option<u8>; 16] = [None; 16];
+        for step in scan.steps.iter(){
+            let (status, data) = match &step.event{
+                SeqEvent::Midi{status, data, ..} => (*status, data),
+                _ => continue,
+            };
+            let channel = status & 0x0F;
+            match status & 0xF0{
+                0xB0 if data[0] == 0x00 => banks[channel as usize] = Some(data[1]),
+                0xC0 => {
+                    let seen = out.iter().any(|u| u.track == track && u.channel == channel && u.bank == banks[channel as usize] && u.program == data[0]);
+                    if !seen{
+                        out.push(InstrumentUse{track: track, channel: channel, bank: banks[channel as usize], program: data[0], first_tick: step.time});
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+    return Ok(out);
+}
+
+const CHANNEL_EVENTS : [(u8, &str, [&str; 2]); 7] = [
+    (0x80, "note_off", ["note", "velocity"]),
+    (0x90, "note_on", ["note", "velocity"]),
+    (0xA0, "key_pressure", ["note", "pressure"]),
+    (0xB0, "control", ["controller", "value"]),
+    (0xC0, "program", ["program", ""]),
+    (0xD0, "channel_pressure", ["pressure", ""]),
+    (0xE0, "pitch_bend", ["lsb", "msb"]),
+];
+
+fn event_yaml(step: &Step, loop_offset: bool) -> String{
+    let mut out = format!("{{delta: {}, ", step.delta);
+    match &step.event{
+        SeqEvent::Midi{status, data, duration} => {
+            match CHANNEL_EVENTS.iter().find(|e| e.0 == status & 0xF0 && status & 0xF0 != 0xF0){
+                Some((_, name, params)) => {
+                    write!(out, "event: {}, channel: {}", name, status & 0x0F).unwrap();
+                    for (p, v) in params.iter().zip(data.iter()){
+                        write!(out, ", {}: {}", p, v).unwrap();
+                    }
+                },
+                None => write!(out, "event: midi, status: 0x{:02X}, data: {:?}", status, data).unwrap(),
+            }
+            if let Some(d) = duration{
+                write!(out, ", duration: {}", d).unwrap();
+            }
+            if step.running{
+                out += ", running: true";
+            }
+        },
+        SeqEvent::Tempo(t) => write!(out, "event: tempo, usec: {}", t).unwrap(),
+        SeqEvent::LoopStart{count, flag} => write!(out, "event: loop_start, count: {}, flag: 0x{:02X}", count, flag).unwrap(),
+        SeqEvent::LoopEnd{count, current, offset} => {
+            write!(out, "event: loop_end, count: {}, current: {}", count, current).unwrap();
+            if loop_offset{
+                write!(out, ", offset: {}", offset).unwrap();
+            }
+        },
+        SeqEvent::Meta(kind) => write!(out, "event: meta, kind: 0x{:02X}", kind).unwrap(),
+        SeqEvent::End => out += "event: end",
+    }
+    return out + "}";
+}
+
+/// the tracks as lists of events. repeated blocks are listed by where they start among the bytes the
+/// player reads so unedited tracks compress the same way again, a loop end pointing just after its
+/// loop start leaves out its offset and gets it recomputed
+pub fn to_yaml(bytes: &[u8], header: &SeqHeader) -> Result<String, MidiError>{
+    let mut out = String::new();
+    writeln!(out, "division: {}", header.division).unwrap();
+    let mut scans = scan_all(bytes, header)?;
+    if scans.is_empty(){
+        writeln!(out, "tracks: []").unwrap();
+        return Ok(out);
+    }
+    scans.sort_by_key(|(t, _)| header.track_offsets[*t]);
+    writeln!(out, "tracks:").unwrap();
+    for (track, scan) in scans.iter(){
+        let range = header.checked_range(*track, bytes)?;
+        writeln!(out, "  - track: {}", track).unwrap();
+        if range.len() > scan.end{
+            writeln!(out, "    padding: {}", range.len() - scan.end).unwrap();
+        }
+        if !scan.repeats.is_empty(){
+            let repeats : Vec<String> = scan.repeats.iter().map(|r| format!("[{}, {}, {}]", r.decoded, r.code - r.src, r.len)).collect();
+            writeln!(out, "    repeats: [{}]", repeats.join(", ")).unwrap();
+        }
+        //only the first loop end is pointed back automatically
+        let first_end = scan.steps.iter().position(|s| matches!(s.event, SeqEvent::LoopEnd{..}));
+        let implied = loop_span(scan).map(|(target, jump)| (jump - target) as u32);
+        writeln!(out, "    events:").unwrap();
+        for (i, step) in scan.steps.iter().enumerate(){
+            let keep_offset = match step.event{
+                SeqEvent::LoopEnd{offset, ..} => Some(i) != first_end || implied != Some(offset),
+                _ => true,
+            };
+            writeln!(out, "      - {}", event_yaml(step, keep_offset)).unwrap();
+        }
+    }
+    return Ok(out);
+}
+
+fn yaml_int(y: &Yaml, key: &str, max: u32) -> Result<u32, String>{
+    match y[key].as_i64(){
+        Some(v) if (0..=max as i64).contains(&v) => Ok(v as u32),
+        Some(v) => Err(format!("{} {} is not between 0 and {}", key, v, max)),
+        None => Err(format!("{} missing or not an integer", key)),
+    }
+}
+
+//an event and whether its status byte is left out, a loop end without offset gets None
+fn event_from_yaml(y: &Yaml) -> Result<(SeqEvent, bool, Option<u32>), String>{
+    let name = y["event"].as_str().ok_or("event missing or not a string")?;
+    let running = y["running"].as_bool().unwrap_or(false);
+    let event = match name{
+        "tempo" => SeqEvent::Tempo(yaml_int(y, "usec", 0xFFFFFF)?),
+        "loop_start" => SeqEvent::LoopStart{count: yaml_int(y, "count", 0xFF)? as u8, flag: yaml_int(y, "flag", 0xFF)? as u8},
+        "loop_end" => {
+            let offset = match y["offset"].is_badvalue(){
+                true => None,
+                false => Some(yaml_int(y, "offset", u32::MAX)?),
+            };
+            let event = SeqEvent::LoopEnd{count: yaml_int(y, "count", 0xFF)? as u8, current: yaml_int(y, "current", 0xFF)? as u8, offset: offset.unwrap_or(0)};
+            return Ok((event, false, offset));
+        },
+        "meta" => SeqEvent::Meta(yaml_int(y, "kind", 0xFF)? as u8),
+        "end" => SeqEvent::End,
+        "midi" => {
+            let status = yaml_int(y, "status", 0xFF)? as u8;
+            if status < 0x80 || status == 0xFF{
+                return Err(format!("status 0x{:02X} is not a midi status", status));
+            }
+            let data = y["data"].as_vec().ok_or("data missing or not a list")?.iter()
+                .map(|b| b.as_i64().filter(|b| (0..=0xFF).contains(b)).map(|b| b as u8).ok_or(String::from("data holds a non byte")))
+                .collect::<Result<Vec<u8>, String>>()?;
+            let duration = match y["duration"].is_badvalue(){
+                true => None,
+                false => Some(yaml_int(y, "duration", 0x0FFFFFFF)?),
+            };
+            SeqEvent::Midi{status: status, data: data, duration: duration}
+        },
+        _ => {
+            let (kind, _, params) = CHANNEL_EVENTS.iter().find(|e| e.1 == name).ok_or(format!("unknown event \"{}\"", name))?;
+            let status = kind | yaml_int(y, "channel", 0x0F)? as u8;
+            let mut data = Vec::new();
+            for p in params.iter().filter(|p| !p.is_empty()){
+                data.push(yaml_int(y, p, 0xFF)? as u8);
+            }
+            let duration = match *kind{
+                0x90 => Some(yaml_int(y, "duration", 0x0FFFFFFF)?),
+                _ => None,
+            };
+            SeqEvent::Midi{status: status, data: data, duration: duration}
+        },
+    };
+    return Ok((event, running, None));
+}
+
+//track bytes for a yaml track, using its repeats when the result still reads back as the listed events
+fn track_from_yaml(y: &Yaml, track: usize) -> Result<Vec<u8>, String>{
+    let events_yaml = y["events"].as_vec().ok_or("events missing or not a list")?;
+    let mut events = Vec::new();
+    for (i, e) in events_yaml.iter().enumerate(){
+        let event = event_from_yaml(e).map_err(|reason| format!("event {}: {}", i, reason))?;
+        let delta = yaml_int(e, "delta", 0x0FFFFFFF).map_err(|reason| format!("event {}: {}", i, reason))?;
+        events.push((delta, event));
+    }
+    if events.last().map(|e| &e.1 .0) != Some(&SeqEvent::End){
+        return Err(String::from("events must finish with an end event"));
+    }
+    let mut repeats = Vec::new();
+    for r in y["repeats"].as_vec().unwrap_or(&Vec::new()).iter(){
+        let r : Vec<i64> = r.as_vec().map(|v| v.iter().filter_map(|n| n.as_i64()).collect()).unwrap_or_default();
+        match r[..]{
+            [at, back, len] if at >= 0 && (1..=0xFFFF).contains(&back) && back >> 8 != BLOCK_CODE as i64 && (1..=0xFF).contains(&len) =>
+                repeats.push((at as usize, back as usize, len as usize)),
+            _ => return Err(String::from("repeats entries are [index, distance, length]")),
+        }
+    }
+    let padding = match y["padding"].is_badvalue(){
+        true => 0,
+        false => yaml_int(y, "padding", 0xFFFF)? as usize,
+    };
+
+    let mut stream = Vec::new();
+    for (delta, (event, running, _)) in events.iter(){
+        let mut len = Vec::new();
+        write_var_len(&mut len, *delta);
+        stream.extend(len.iter().map(|b| (*b, false)));
+        player_bytes(&mut stream, event, *running);
+    }
+    let build = |repeats: &[(usize, usize, usize)]| -> Option<Vec<u8>>{
+        let mut data = compress(&stream, repeats);
+        data.resize(data.len() + padding, 0);
+        if events.iter().any(|(_, (e, _, offset))| matches!(e, SeqEvent::LoopEnd{..}) && offset.is_none()){
+            let (target, jump) = loop_span(&scan_track(&data, track).ok()?)?;
+            data[jump - 4..jump].copy_from_slice(&((jump - target) as u32).to_be_bytes());
+        }
+        let scan = scan_track(&data, track).ok()?;
+        let same = scan.steps.len() == events.len() && scan.steps.iter().zip(events.iter()).all(|(s, (delta, (e, running, offset)))|{
+            let event_same = match (&s.event, e, offset){
+                (SeqEvent::LoopEnd{count, current, ..}, SeqEvent::LoopEnd{count: c, current: cur, ..}, None) => count == c && current == cur,
+                _ => s.event == *e,
+            };
+            s.delta == *delta && s.running == *running && event_same
+        });
+        return same.then_some(data);
+    };
+    //edits that move bytes a repeat points at leave the track uncompressed
+    return build(&repeats).or_else(|| build(&[]))
+        .ok_or(String::from("events don't read back, check running status and loop ends"));
+}
+
+/// rebuilds a sequence from the yaml written by `to_yaml`, tracks are laid out in the listed order
+pub fn from_yaml(doc: &Yaml) -> Result<Vec<u8>, String>{
+    let division = yaml_int(doc, "division", u32::MAX)?;
+    let tracks = doc["tracks"].as_vec().ok_or("tracks missing or not a list")?;
+    let mut header = SeqHeader{track_offsets: [0; TRACK_COUNT], division: division};
+    let mut data = Vec::new();
+    for (i, y) in tracks.iter().enumerate(){
+        let track = yaml_int(y, "track", TRACK_COUNT as u32 - 1).map_err(|reason| format!("tracks entry {}: {}", i, reason))? as usize;
+        if header.track_offsets[track] != 0{
+            return Err(format!("tracks entry {}: track {} is listed twice", i, track));
+        }
+        header.track_offsets[track] = (HEADER_LEN + data.len()) as u32;
+        data.append(&mut track_from_yaml(y, track).map_err(|reason| format!("track {}: {}", track, reason))?);
+    }
+    let mut out = header.to_bytes();
+    out.append(&mut data);
+    return Ok(out);
+}
@@ -1,12 +1,25 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs::{self, DirBuilder};
-use std::io::{Write, Read};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use yaml_rust::{YamlLoader,Yaml};
 
 use rarezip::bk;
 
+pub mod anim;
 pub mod asset;
+pub mod charset;
+pub mod gfx;
+pub mod gltf;
+pub mod magic;
+pub mod midi;
+pub mod model;
+pub mod report;
+pub mod text;
+
+//every folder AssetFolder::write puts assets in
+const ASSET_FOLDERS : [&str; 10] = ["anim", "bin", "demo", "dialog", "grunty_q", "lvl_setup", "midi", "model", "quiz_q", "sprite"];
 
 #[derive(Clone, Copy)]
 struct AssetMeta{
@@ -23,7 +36,7 @@ impl AssetMeta {
         return AssetMeta{offset: offset as usize, c_flag: c_flag != 0, t_flag: t_flag}
     }
 
-    pub fn to_bytes(&self) -> Vec<u8>{
+    pub fn to_bytes(self) -> Vec<u8>{
         let mut out : Vec<u8> = (self.offset as u32).to_be_bytes().to_vec();
         out.push(0x00);
         out.push(self.c_flag as u8);
@@ -35,14 +48,13 @@ impl AssetMeta {
 // struct containing metadata and maybe a dyn asset::Asset
 struct AssetEntry{
     pub uid  : usize,
-    pub seg : usize,
     pub meta : AssetMeta,
     pub data : Option<Box<dyn asset::Asset>>
 }
 
 impl AssetEntry{
     pub fn new(uid:usize)->AssetEntry{
-        AssetEntry{uid: uid, seg: 0, meta: AssetMeta{offset:0, c_flag:false, t_flag:4}, data: None}
+        AssetEntry{uid: uid, meta: AssetMeta{offset:0, c_flag:false, t_flag:4}, data: None}
     }
 
     pub fn from_yaml(yaml:&Yaml)->AssetEntry{
@@ -56,12 +68,23 @@ impl AssetEntry{
 }
 
 pub struct AssetFolder{
-    assets : Vec<AssetEntry>
+    assets : Vec<AssetEntry>,
+    pub text_format : asset::TextFormat, //format dialogs, questions and demos are extracted to
+    pub standard_midi : bool, //write a .mid next to each sequence bin
+    pub model_textures : bool, //write the textures embedded in models as pngs
+    pub charset : charset::CharTable, //font table the text assets are written and read with
+    pub sprite_descriptor : asset::DescriptorFormat, //file the sprite frame layout is written to
+}
+
+impl Default for AssetFolder{
+    fn default() -> AssetFolder{
+        return AssetFolder::new()
+    }
 }
 
 impl AssetFolder{
     pub fn new() -> AssetFolder{
-        return AssetFolder{assets: Vec::new()}
+        return AssetFolder{assets: Vec::new(), text_format: asset::TextFormat::Yaml, standard_midi: false, model_textures: false, charset: charset::DEFAULT, sprite_descriptor: asset::DescriptorFormat::Yaml}
     }
 
     pub fn from_bytes(in_bytes: &[u8]) -> AssetFolder{
@@ -76,7 +99,7 @@ impl AssetFolder{
             let next = &window[1];
 
             if this.t_flag == 4{ //empty entry
-                return AssetEntry{uid : i, seg : 0, meta : this.clone(), data : None};
+                return AssetEntry{uid : i, meta : *this, data : None};
             }
             else if (this.t_flag != 2)
                     && (prev_t & 2) != (this.t_flag & 2)
@@ -92,12 +115,12 @@ impl AssetFolder{
                 false => comp_bin.to_vec(),
             };
             let this_asset = asset::from_seg_indx_and_bytes(segment, i, &decomp_bin);
-            let out = AssetEntry{uid : i, seg :segment, meta : this.clone(), data : Some(this_asset)};
+            let out = AssetEntry{uid : i, meta : *this, data : Some(this_asset)};
             return out
         }).collect();
 
 
-        return AssetFolder{assets: asset_list};
+        return AssetFolder{assets: asset_list, text_format: asset::TextFormat::Yaml, standard_midi: false, model_textures: false, charset: charset::DEFAULT, sprite_descriptor: asset::DescriptorFormat::Yaml};
     }
 
     pub fn to_bytes(&mut self) -> Vec<u8>{
@@ -131,8 +154,7 @@ impl AssetFolder{
         out.append(&mut vec![0xff, 0xff, 0xff, 0xff]);
 
         let mut meta_bytes : Vec<u8> = self.assets.iter()
-            .map(|a|{return a.meta.to_bytes()})
-            .flatten()
+            .flat_map(|a|{return a.meta.to_bytes()})
             .collect();
 
         let mut data_bytes: Vec<u8> = comp_bins.into_iter().flatten().collect();
@@ -145,23 +167,27 @@ impl AssetFolder{
 
     pub fn write(&self, out_dir_path: &Path){
         let asset_yaml_path = out_dir_path.join("assets.yaml");
+        let tmp_yaml_path = out_dir_path.join("assets.yaml.tmp");
 
-        //write assets.yaml
-        let mut asset_yaml = fs::File::create(&asset_yaml_path).expect("could not write file");
-        
+        //write assets.yaml to a temp file, only replacing the old one once every asset is written
+        let mut asset_yaml = fs::File::create(&tmp_yaml_path).expect("could not write file");
+
+        //whatever an earlier extraction wrote for a uid is removed before the uid is written again, so a json
+        //left from --json, a .mid, the other sprite descriptor or the copy in a folder of another type can't go stale
+        let mut previous = AssetFolder::previous_outputs(out_dir_path);
 
         //assets.to_file
         writeln!(asset_yaml, "tbl_len: 0x{:X}", self.assets.len() + 1).unwrap();
         writeln!(asset_yaml, "files:").unwrap();
         for elem in self.assets.iter()
-            .filter(|a| match a.data {None => false, _ => true})
+            .filter(|a| a.data.is_some())
         {
             
             let data = match &elem.data {
                 Some(x) => x,
                 None => panic!("None data element reached"),
             };
-            let mut tmp_str: String;
+            let tmp_str: String;
             let data_type_str = match data.get_type(){
                 asset::AssetType::Animation => "Animation",
                 asset::AssetType::Binary => "Binary",
@@ -173,9 +199,8 @@ impl AssetFolder{
                 asset::AssetType::LevelSetup => "LevelSetup",
                 asset::AssetType::QuizQuestion => "QuizQuestion",
                 asset::AssetType::Sprite(fmt) => {let f = format!("{:?}",fmt).to_uppercase(); tmp_str = String::from("Sprite_") + &f; &tmp_str},
-                _ => "Binary",
             };
-            let mut tmp_str2: String;
+            let tmp_str2: String;
             let file_ext = match data.get_type(){
                 asset::AssetType::Binary => ".bin",
                 asset::AssetType::Dialog => ".dialog",
@@ -185,9 +210,8 @@ impl AssetFolder{
                 asset::AssetType::Midi => ".midi.bin",
                 asset::AssetType::Model => ".model.bin",
                 asset::AssetType::LevelSetup => ".lvl_setup.bin",
-                asset::AssetType::Animation => ".anim.bin",
-                asset::AssetType::Sprite(fmt) => {tmp_str2 = format!(".sprite.{:?}.bin",fmt).to_lowercase(); &tmp_str2.as_str()},
-                _ => ".bin"
+                asset::AssetType::Animation => ".anim",
+                asset::AssetType::Sprite(fmt) => {tmp_str2 = format!(".sprite.{:?}.bin",fmt).to_lowercase(); tmp_str2.as_str()},
             };
             let containing_folder = match data.get_type(){
                 asset::AssetType::Binary => "bin",
@@ -199,28 +223,71 @@ impl AssetFolder{
                 asset::AssetType::Model => "model",
                 asset::AssetType::LevelSetup => "lvl_setup",
                 asset::AssetType::Animation => "anim",
-                asset::AssetType::Sprite(fmt) => "sprite",
-                _ => "bin"
+                asset::AssetType::Sprite(_) => "sprite",
             };
 
+            for stale in previous.remove(&format!("{:04X}", elem.uid)).unwrap_or_default(){
+                match stale.is_dir(){
+                    true => fs::remove_dir_all(&stale).unwrap(),
+                    false => fs::remove_file(&stale).unwrap(),
+                }
+            }
             let elem_folder = out_dir_path.join(containing_folder);
             DirBuilder::new().recursive(true).create(&elem_folder).unwrap();
             assert!(fs::metadata(&elem_folder).unwrap().is_dir());
             
-            let elem_path = elem_folder.join(format!("{:04X}{}", elem.uid, file_ext));
+            let json_ext = match (self.text_format, data.get_type()){
+                (asset::TextFormat::Json, asset::AssetType::Dialog | asset::AssetType::GruntyQuestion | asset::AssetType::QuizQuestion | asset::AssetType::DemoInput) => ".json",
+                _ => "",
+            };
+            let elem_path = elem_folder.join(format!("{:04X}{}{}", elem.uid, file_ext, json_ext));
             let relative_path = elem_path.strip_prefix(out_dir_path).unwrap().to_str().unwrap();
             writeln!(asset_yaml, "  - {{uid: 0x{:04X}, type: {:6}, compressed: {:5}, flags: 0x{:04X}, relative_path: {:?}}}", elem.uid, data_type_str, elem.meta.c_flag, elem.meta.t_flag, relative_path).unwrap();
         
-            data.write(&elem_path);
+            match data.get_type(){
+                asset::AssetType::Midi if self.standard_midi => {
+                    let options = asset::MidiWriteOptions::default().standard_midi(true);
+                    asset::MidiSeqFile::from_bytes(&data.to_bytes()).write_with(&elem_path, &options);
+                },
+                asset::AssetType::Model if self.model_textures => {
+                    let options = asset::ModelWriteOptions::default().textures(true);
+                    asset::Model::from_bytes(&data.to_bytes()).write_with(&elem_path, &options);
+                },
+                asset::AssetType::Sprite(_) if self.sprite_descriptor != asset::DescriptorFormat::Yaml => {
+                    let options = asset::SpriteWriteOptions::default().descriptor(self.sprite_descriptor);
+                    asset::Sprite::from_bytes(&data.to_bytes()).write_with(&elem_path, &options);
+                },
+                asset::AssetType::Dialog => asset::Dialog::parse(&data.to_bytes()).unwrap().write_with(&elem_path, &self.charset),
+                kind @ (asset::AssetType::QuizQuestion | asset::AssetType::GruntyQuestion) => {
+                    asset::QuestionAsset::parse(kind, &data.to_bytes()).unwrap().write_with(&elem_path, &self.charset);
+                },
+                _ => data.write(&elem_path),
+            }
         }
+        drop(asset_yaml);
+        fs::rename(&tmp_yaml_path, &asset_yaml_path).expect("could not write assets.yaml");
 
+    }
 
+    //files and folders in the asset folders grouped by the uid they are named after: 0123.dialog.json,
+    //0123.model.tex0.png and the 0123 folder of sprite frames all belong to 0123, anything else is left alone
+    fn previous_outputs(out_dir_path: &Path) -> HashMap<String, Vec<PathBuf>>{
+        let mut out : HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for folder in ASSET_FOLDERS.iter().map(|f| out_dir_path.join(f)).filter(|f| f.is_dir()){
+            for entry in fs::read_dir(&folder).unwrap(){
+                let path = entry.unwrap().path();
+                let uid = path.file_name().unwrap().to_str().unwrap_or("").split('.').next().unwrap_or("").to_string();
+                if uid.len() == 4 && uid.chars().all(|c| c.is_ascii_hexdigit()){
+                    out.entry(uid).or_default().push(path);
+                }
+            }
+        }
+        return out
     }
 
     pub fn read(&mut self, yaml_path: &Path){
         assert_eq!(yaml_path.extension().unwrap(), "yaml");
         let containing_folder = yaml_path.parent().unwrap();
-        let base_name = yaml_path.file_stem().unwrap();
         
         let doc = &YamlLoader::load_from_str(&fs::read_to_string(yaml_path).expect("could not open yaml")).unwrap()[0];
 
@@ -241,7 +308,7 @@ impl AssetFolder{
         }
 
         for a in asset_meta.into_iter(){
-            let i = a.uid.clone();
+            let i = a.uid;
             self.assets[i] = a;
         }
 
@@ -250,18 +317,164 @@ impl AssetFolder{
             let relative_path = y["relative_path"].as_str().unwrap();
             let data :Option<Box<dyn asset::Asset>> = match y["type"].as_str().unwrap(){
                 "Binary"            => Some(Box::new(asset::Binary::read(&containing_folder.join(relative_path)))),
-                "Dialog"            => Some(Box::new(asset::Dialog::read(&containing_folder.join(relative_path)))),
-                "GruntyQuestion"    => Some(Box::new(asset::GruntyQuestion::read(&containing_folder.join(relative_path)))),
-                "QuizQuestion"      => Some(Box::new(asset::QuizQuestion::read(&containing_folder.join(relative_path)))),
-                "DemoInput"         => Some(Box::new(asset::DemoButtonFile::read(&containing_folder.join(relative_path)))),
-                // "Midi"              => Some(Box::new(asset::MidiSeqFile::read(&containing_folder.join(relative_path)))),
-                // "Model"             => Some(Box::new(asset::Model::read(&containing_folder.join(relative_path)))),
-                // "LevelSetup"        => Some(Box::new(asset::LevelSetup::read(&containing_folder.join(relative_path)))),
-                // "Animation"         => Some(Box::new(asset::Animation::read(&containing_folder.join(relative_path)))),
-                // x if x.starts_with("Sprite_") => Some(Box::new(asset::Sprite::read(&containing_folder.join(relative_path)))),
+                "Dialog"            => Some(Box::new(asset::Dialog::read_with(&containing_folder.join(relative_path), &self.charset).unwrap_or_else(|e| panic!("{}", e)))),
+                "GruntyQuestion"    => Some(Box::new(asset::GruntyQuestion::read_with(&containing_folder.join(relative_path), &self.charset).unwrap_or_else(|e| panic!("{}", e)))),
+                "QuizQuestion"      => Some(Box::new(asset::QuizQuestion::read_with(&containing_folder.join(relative_path), &self.charset).unwrap_or_else(|e| panic!("{}", e)))),
+                "DemoInput"         => Some(Box::new(asset::DemoButtonFile::read(&containing_folder.join(relative_path)).unwrap_or_else(|e| panic!("{}", e)))),
+                "Midi"              => {
+                    //the event listing wins over the bin when both are there
+                    let yaml_path = containing_folder.join(relative_path).with_extension("yaml");
+                    let path = if yaml_path.exists() { yaml_path } else { containing_folder.join(relative_path) };
+                    Some(Box::new(asset::MidiSeqFile::read(&path).unwrap_or_else(|e| panic!("{}", e))))
+                },
+                "Model"             => {
+                    //the descriptor is checked against the bin when it is there
+                    let yaml_path = containing_folder.join(relative_path).with_extension("yaml");
+                    let path = if yaml_path.exists() { yaml_path } else { containing_folder.join(relative_path) };
+                    Some(Box::new(asset::Model::read(&path).unwrap_or_else(|e| panic!("{}", e))))
+                },
+                "LevelSetup"        => {
+                    //the yaml carries the reproduction mode, older extractions only have the bin
+                    let yaml_path = containing_folder.join(relative_path).with_extension("yaml");
+                    let path = if yaml_path.exists() { yaml_path } else { containing_folder.join(relative_path) };
+                    Some(Box::new(asset::LevelSetup::read(&path).unwrap_or_else(|e| panic!("{}", e))))
+                },
+                "Animation"         => Some(Box::new(asset::Animation::read(&containing_folder.join(relative_path)).unwrap_or_else(|e| panic!("{}", e)))),
+                x if x.starts_with("Sprite_") && !x.starts_with("Sprite_UNKNOWN") => {
+                    //the toml wins when both descriptors are there
+                    let base_path = containing_folder.join(relative_path.split('.').next().unwrap());
+                    let toml_path = base_path.with_extension("sprite.toml");
+                    let desc_path = if toml_path.exists() { toml_path } else { base_path.with_extension("sprite.yaml") };
+                    match desc_path.exists(){
+                        true => {
+                            let sprite = asset::Sprite::read(&desc_path)
+                                .unwrap_or_else(|e| panic!("could not read {}: {}", desc_path.display(), e));
+                            Some(Box::new(sprite))
+                        },
+                        //extracted with SpriteWriteMode::BinOnly
+                        false => Some(Box::new(asset::Binary::read(&containing_folder.join(relative_path)))),
+                    }
+                },
                 _ => Some(Box::new(asset::Binary::read(&containing_folder.join(relative_path)))),
             };
             self.assets[uid].data = data;
         }
     }
 }
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //rgba16 sprite made of 4x2 single chunk frames
+    fn sprite_bin(frames: usize) -> Vec<u8>{
+        let mut out = vec![0; 0x10];
+        out[..2].copy_from_slice(&(frames as u16).to_be_bytes());
+        out[2..4].copy_from_slice(&0x0400u16.to_be_bytes());
+        let data_start = 0x10 + 4*frames;
+        out.resize(data_start, 0);
+        for i in 0..frames{
+            //frame header, chunk header, then the pixels 8 byte aligned
+            out.resize((out.len() + 7) & !7, 0);
+            let offset = (out.len() - data_start) as u32;
+            out[0x10 + 4*i..0x14 + 4*i].copy_from_slice(&offset.to_be_bytes());
+            for word in [0u16, 0, 4, 2, 1, 0, 0, 0, 0, 0, 0, 0, 4, 2]{
+                out.extend_from_slice(&word.to_be_bytes());
+            }
+            out.resize((out.len() + 7) & !7, 0);
+            out.extend((0..16).map(|b| (b*17 + i) as u8));
+        }
+        return out
+    }
+
+    //uncompressed assets.bin, alternating flags put the assets in segments 1 to 4
+    fn folder_bin(assets: &[Vec<u8>]) -> Vec<u8>{
+        let mut out = ((assets.len() + 1) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(&[0xFF; 4]);
+        let mut offset = 0;
+        for (i, asset) in assets.iter().enumerate(){
+            out.append(&mut AssetMeta{offset: offset, c_flag: false, t_flag: if i % 2 == 0 {0} else {3}}.to_bytes());
+            offset += asset.len();
+        }
+        out.append(&mut AssetMeta{offset: offset, c_flag: false, t_flag: 4}.to_bytes());
+        out.extend(assets.iter().flatten());
+        return out
+    }
+
+    fn fixture(sprite_frames: usize) -> Vec<u8>{
+        let mut dialog = magic::DIALOG.to_vec();
+        dialog.extend_from_slice(&[1, 0x80, 3, b'H', b'I', 0, 0]);
+        return folder_bin(&[sprite_bin(sprite_frames), vec![1, 2, 3, 4, 5, 6], sprite_bin(1), dialog])
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf{
+        let dir = std::env::temp_dir().join(format!("bk_asset_tool_{}_{}", std::process::id(), name));
+        if dir.exists(){
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        return dir
+    }
+
+    fn construct(dir: &Path) -> Vec<u8>{
+        let mut af = AssetFolder::new();
+        af.read(&dir.join("assets.yaml"));
+        return af.to_bytes()
+    }
+
+    #[test]
+    fn extract_then_construct_gives_back_the_same_bin(){
+        let dir = scratch_dir("extract_construct");
+        let bin = fixture(3);
+        for text_format in [asset::TextFormat::Yaml, asset::TextFormat::Json]{
+            for sprite_descriptor in [asset::DescriptorFormat::Yaml, asset::DescriptorFormat::Toml]{
+                let mut af = AssetFolder::from_bytes(&bin);
+                af.text_format = text_format;
+                af.sprite_descriptor = sprite_descriptor;
+                af.write(&dir);
+                assert_eq!(construct(&dir), bin, "{:?} {:?}", text_format, sprite_descriptor);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn re_extraction_leaves_no_stale_outputs(){
+        let dir = scratch_dir("re_extraction");
+        let mut af = AssetFolder::from_bytes(&fixture(3));
+        af.text_format = asset::TextFormat::Json;
+        af.sprite_descriptor = asset::DescriptorFormat::Toml;
+        af.write(&dir);
+        fs::write(dir.join("sprite/notes.txt"), "not an asset").unwrap();
+
+        let bin = fixture(1);
+        AssetFolder::from_bytes(&bin).write(&dir);
+        assert!(dir.join("sprite/notes.txt").exists());
+        let mut found : Vec<String> = Vec::new();
+        for folder in ASSET_FOLDERS.iter().map(|f| dir.join(f)).filter(|f| f.is_dir()){
+            for entry in fs::read_dir(&folder).unwrap(){
+                let path = entry.unwrap().path();
+                let children = match path.is_dir(){
+                    true => fs::read_dir(&path).unwrap().map(|e| e.unwrap().path()).collect(),
+                    false => vec![path],
+                };
+                found.extend(children.iter().map(|p| p.strip_prefix(&dir).unwrap().to_str().unwrap().to_string()));
+            }
+        }
+        found.sort();
+        assert_eq!(found, [
+            "dialog/0003.dialog",
+            "lvl_setup/0001.lvl_setup.bin",
+            "lvl_setup/0001.lvl_setup.yaml",
+            "sprite/0000.sprite.rgba16.bin",
+            "sprite/0000.sprite.yaml",
+            "sprite/0000/00.rgba16.png",
+            "sprite/0002.sprite.rgba16.bin",
+            "sprite/0002.sprite.yaml",
+            "sprite/0002/00.rgba16.png",
+            "sprite/notes.txt",
+        ]);
+        assert_eq!(construct(&dir), bin);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
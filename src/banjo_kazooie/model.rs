@@ -0,0 +1,882 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+use super::anim::{Bone, ModelSkeleton};
+use super::asset::ImgFmt;
+use super::gfx::{self, DisplayList, GfxCommand, GfxIssue};
+use super::magic;
+
+/// segment 1/3/5 models start with a 0x38 byte header of section offsets from the start of the file,
+/// a section that isn't there has offset 0
+pub const HEADER_LEN : usize = 0x38;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ModelHeader{
+    pub geo_layout: u32, //0x04, mesh/bone tree of geo commands
+    pub texture_list: u16, //0x08
+    pub geo_type: u16, //0x0A
+    pub gfx_list: u32, //0x0C, display list
+    pub vtx_list: u32, //0x10
+    pub unk14: u32,
+    pub bone_list: u32, //0x18, animation setup
+    pub collision_list: u32, //0x1C
+    pub mesh_list: u32, //0x20
+    pub effects_setup: u32, //0x24
+    pub unk28: u32,
+    pub anim_texture_list: u32, //0x2C
+    pub tri_count: u16, //0x30
+    pub vtx_count: u16, //0x32
+    pub unk34: u32,
+}
+
+/// one 0x10 byte record of the texture list, `offset` counts from the start of the texture data
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextureEntry{
+    pub offset: u32,
+    pub format: ImgFmt,
+    pub unk06: u16,
+    pub width: u8,
+    pub height: u8,
+    pub unk0a: [u8; 6],
+}
+
+impl TextureEntry{
+    pub const LEN : usize = 0x10;
+
+    fn from_bytes(bytes: &[u8]) -> TextureEntry{
+        TextureEntry{
+            offset: u32_at(bytes, 0),
+            format: TextureEntry::format_from_code(u16_at(bytes, 4)),
+            unk06: u16_at(bytes, 6),
+            width: bytes[8],
+            height: bytes[9],
+            unk0a: [bytes[0xA], bytes[0xB], bytes[0xC], bytes[0xD], bytes[0xE], bytes[0xF]],
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = self.offset.to_be_bytes().to_vec();
+        out.extend_from_slice(&TextureEntry::format_code(self.format).to_be_bytes());
+        out.extend_from_slice(&self.unk06.to_be_bytes());
+        out.push(self.width);
+        out.push(self.height);
+        out.extend_from_slice(&self.unk0a);
+        return out
+    }
+
+    //models use their own format codes, not the sprite ones
+    fn format_from_code(code: u16) -> ImgFmt{
+        match code{
+            0x01 => ImgFmt::CI4,
+            0x02 => ImgFmt::CI8,
+            0x04 => ImgFmt::RGBA16,
+            0x08 => ImgFmt::RGBA32,
+            0x10 => ImgFmt::IA8,
+            _ => ImgFmt::Unknown(code),
+        }
+    }
+
+    fn format_code(format: ImgFmt) -> u16{
+        match format{
+            ImgFmt::CI4 => 0x01,
+            ImgFmt::CI8 => 0x02,
+            ImgFmt::RGBA16 => 0x04,
+            ImgFmt::RGBA32 => 0x08,
+            ImgFmt::IA8 => 0x10,
+            ImgFmt::Unknown(code) => code,
+            _ => 0,
+        }
+    }
+
+    /// palette plus pixels, None for formats models don't use
+    pub fn byte_len(&self) -> Option<usize>{
+        let pixels = self.width as usize * self.height as usize;
+        match self.format{
+            ImgFmt::CI4 => Some(0x20 + pixels/2),
+            ImgFmt::CI8 => Some(0x200 + pixels),
+            ImgFmt::RGBA16 => Some(2*pixels),
+            ImgFmt::RGBA32 => Some(4*pixels),
+            ImgFmt::IA8 => Some(pixels),
+            _ => None,
+        }
+    }
+}
+
+/// one 0x10 byte N64 Vtx, `color` is a vertex color or a signed normal depending on the display list's lighting mode
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct ModelVertex{
+    pub position: [i16; 3],
+    pub flag: u16,
+    pub uv: [i16; 2], //s10.5 fixed point texels
+    pub color: [u8; 4],
+}
+
+impl ModelVertex{
+    pub const LEN : usize = 0x10;
+
+    pub fn from_bytes(bytes: &[u8]) -> ModelVertex{
+        let s16 = |offset: usize| u16_at(bytes, offset) as i16;
+        ModelVertex{
+            position: [s16(0), s16(2), s16(4)],
+            flag: u16_at(bytes, 6),
+            uv: [s16(8), s16(0xA)],
+            color: [bytes[0xC], bytes[0xD], bytes[0xE], bytes[0xF]],
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = Vec::with_capacity(ModelVertex::LEN);
+        for word in self.position{
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.extend_from_slice(&self.flag.to_be_bytes());
+        out.extend_from_slice(&self.uv[0].to_be_bytes());
+        out.extend_from_slice(&self.uv[1].to_be_bytes());
+        out.extend_from_slice(&self.color);
+        return out
+    }
+
+    /// texel coordinates with the 5 fraction bits applied
+    pub fn uv_f32(&self) -> [f32; 2]{
+        [self.uv[0] as f32 / 32.0, self.uv[1] as f32 / 32.0]
+    }
+
+    /// `color` read as a normal, only meaningful on lit geometry
+    pub fn normal(&self) -> [f32; 3]{
+        let n = |b: u8| b as i8 as f32 / 127.0;
+        [n(self.color[0]), n(self.color[1]), n(self.color[2])]
+    }
+}
+
+/// one triangle of the collision list, `vertices` index the model's vertex list and `flags`
+/// holds the surface type bits (floor, wall, water, damage...) as the game reads them
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct CollisionTri{
+    pub vertices: [u16; 3],
+    pub unk6: u16,
+    pub flags: u32,
+}
+
+impl CollisionTri{
+    pub const LEN : usize = 0xC;
+
+    fn from_bytes(bytes: &[u8]) -> CollisionTri{
+        CollisionTri{
+            vertices: [u16_at(bytes, 0), u16_at(bytes, 2), u16_at(bytes, 4)],
+            unk6: u16_at(bytes, 6),
+            flags: u32_at(bytes, 8),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = Vec::with_capacity(CollisionTri::LEN);
+        for word in self.vertices.iter().chain(std::iter::once(&self.unk6)){
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.extend_from_slice(&self.flags.to_be_bytes());
+        return out
+    }
+}
+
+/// the collision list: a grid of cells over the bounding box, each pointing at a run of triangles
+#[derive(PartialEq, Debug, Clone)]
+pub struct CollisionMesh{
+    pub min: [i16; 3],
+    pub max: [i16; 3],
+    pub stride: [u16; 2], //unkC and unkE, cell grid strides
+    pub unk14: u16,
+    pub cells: Vec<(u16, u16)>, //first triangle and count
+    pub triangles: Vec<CollisionTri>,
+}
+
+impl CollisionMesh{
+    pub const HEADER_LEN : usize = 0x18;
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = Vec::new();
+        for word in self.min.iter().chain(self.max.iter()){
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        for word in [self.stride[0], self.stride[1], self.cells.len() as u16, self.triangles.len() as u16, self.unk14, 0]{
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        for (first, count) in self.cells.iter(){
+            out.extend_from_slice(&first.to_be_bytes());
+            out.extend_from_slice(&count.to_be_bytes());
+        }
+        for tri in self.triangles.iter(){
+            out.extend(tri.to_bytes());
+        }
+        return out
+    }
+
+    /// sorts the triangles into a new grid after their vertices moved. cells are `unk14` units on a
+    /// side, `min` and `max` are the first and last cell on each axis, x varies fastest and a triangle
+    /// is listed in every cell its bounds touch
+    pub fn regrid(&self, vertices: &[ModelVertex]) -> Result<CollisionMesh, ModelError>{
+        let size = self.unk14 as i32;
+        let counts = [0, 1, 2].map(|a| self.max[a] as i32 - self.min[a] as i32 + 1);
+        let layer = counts[0]*counts[1];
+        if size == 0 || counts.iter().any(|c| *c <= 0) || self.stride != [counts[0] as u16, layer as u16] || self.cells.len() != (layer*counts[2]) as usize{
+            return Err(ModelError::CollisionGrid(format!("strides {:?} and {} cells don't match the cells from {:?} to {:?}", self.stride, self.cells.len(), self.min, self.max)));
+        }
+        let mut seen = HashSet::new();
+        let triangles : Vec<CollisionTri> = self.triangles.iter().filter(|t| seen.insert(**t)).cloned().collect();
+        if triangles.is_empty(){
+            return Ok(self.clone())
+        }
+
+        //the cells each triangle's bounds run from and to
+        let mut spans = Vec::with_capacity(triangles.len());
+        for tri in triangles.iter(){
+            let mut corners = Vec::with_capacity(3);
+            for v in tri.vertices{
+                let vertex = vertices.get(v as usize).ok_or_else(|| ModelError::CollisionGrid(format!("a triangle uses vertex {}, the model has {}", v, vertices.len())))?;
+                corners.push(vertex.position.map(|c| (c as i32).div_euclid(size)));
+            }
+            let lo = [0, 1, 2].map(|a| corners.iter().map(|c| c[a]).min().unwrap());
+            let hi = [0, 1, 2].map(|a| corners.iter().map(|c| c[a]).max().unwrap());
+            spans.push((lo, hi));
+        }
+        let min = [0, 1, 2].map(|a| spans.iter().map(|(lo, _)| lo[a]).min().unwrap());
+        let max = [0, 1, 2].map(|a| spans.iter().map(|(_, hi)| hi[a]).max().unwrap());
+        let counts = [0, 1, 2].map(|a| (max[a] - min[a] + 1) as usize);
+        let cell_count = counts[0]*counts[1]*counts[2];
+        if cell_count > u16::MAX as usize{
+            return Err(ModelError::TooMany{what: "collision cells", count: cell_count, limit: u16::MAX as usize});
+        }
+
+        let mut cells : Vec<Vec<CollisionTri>> = vec![Vec::new(); cell_count];
+        for (tri, (lo, hi)) in triangles.iter().zip(spans){
+            for z in lo[2]..=hi[2]{
+                for y in lo[1]..=hi[1]{
+                    for x in lo[0]..=hi[0]{
+                        let cell = (x - min[0]) as usize + (y - min[1]) as usize*counts[0] + (z - min[2]) as usize*counts[0]*counts[1];
+                        cells[cell].push(*tri);
+                    }
+                }
+            }
+        }
+        let listed : usize = cells.iter().map(|c| c.len()).sum();
+        if listed > u16::MAX as usize{
+            return Err(ModelError::TooMany{what: "triangles listed across the collision cells", count: listed, limit: u16::MAX as usize});
+        }
+        let mut runs = Vec::with_capacity(cell_count);
+        let mut listed = Vec::with_capacity(listed);
+        for cell in cells{
+            runs.push((listed.len() as u16, cell.len() as u16));
+            listed.extend(cell);
+        }
+        return Ok(CollisionMesh{
+            min: min.map(|c| c as i16),
+            max: max.map(|c| c as i16),
+            stride: [counts[0] as u16, (counts[0]*counts[1]) as u16],
+            unk14: self.unk14,
+            cells: runs,
+            triangles: listed,
+        })
+    }
+}
+
+/// one mesh list entry: an id the game looks the mesh up by and the vertices that belong to it
+#[derive(PartialEq, Debug, Clone)]
+pub struct MeshInfo{
+    pub id: i16,
+    pub vertices: Vec<u16>,
+}
+
+impl MeshInfo{
+    /// the vertices as runs of consecutive indices
+    pub fn ranges(&self) -> Vec<Range<usize>>{
+        let mut sorted = self.vertices.clone();
+        sorted.sort();
+        sorted.dedup();
+        let mut out : Vec<Range<usize>> = Vec::new();
+        for v in sorted.into_iter().map(|v| v as usize){
+            match out.last_mut(){
+                Some(range) if range.end == v => range.end += 1,
+                _ => out.push(v..v + 1),
+            }
+        }
+        return out
+    }
+}
+
+/// the vertex list starts with bounds, a center and radii before the count
+pub const VTX_HEADER_LEN : usize = 0x18;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ModelError{
+    Short{len: usize},
+    BadMagic,
+    OffsetOutOfRange{section: &'static str, offset: u32, len: usize},
+    Truncated{section: &'static str, offset: usize, needed: usize, len: usize},
+    NoHeader,
+    VertexCount{expected: usize, found: usize},
+    NoTexture{index: usize, count: usize},
+    BadTexture{index: usize, reason: String},
+    Obj{line: usize, reason: String},
+    TooManyMaterials(Vec<String>),
+    OutOfRange{what: &'static str, index: usize, value: f32},
+    TooMany{what: &'static str, count: usize, limit: usize},
+    TextureTooLarge{width: usize, height: usize, bytes: usize, limit: usize},
+    TooManyColors{index: usize, colors: usize, limit: usize},
+    BadScale(f32),
+    CollisionGrid(String),
+}
+
+impl fmt::Display for ModelError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ModelError::Short{len} => write!(f, "file is 0x{:X} bytes, shorter than the 0x{:X} byte model header", len, HEADER_LEN),
+            ModelError::BadMagic => write!(f, "does not start with the model magic 0x0000000B"),
+            ModelError::OffsetOutOfRange{section, offset, len} => write!(f, "{} starts at 0x{:X}, outside the 0x{:X} byte file", section, offset, len),
+            ModelError::Truncated{section, offset, needed, len} => write!(f, "{} needs 0x{:X} bytes at 0x{:X} but the file is 0x{:X} bytes", section, needed, offset, len),
+            ModelError::NoHeader => write!(f, "model was kept as raw bytes"),
+            ModelError::VertexCount{expected, found} => write!(f, "vertex list holds {} vertices, got {}", expected, found),
+            ModelError::NoTexture{index, count} => write!(f, "no texture {}, the model has {}", index, count),
+            ModelError::BadTexture{index, reason} => write!(f, "texture {}: {}", index, reason),
+            ModelError::Obj{line, reason} => write!(f, "obj line {}: {}", line, reason),
+            ModelError::TooManyMaterials(names) => write!(f, "faces use {} materials ({}), only one is supported, merge them into one texture", names.len(), names.join(", ")),
+            ModelError::OutOfRange{what, index, value} => write!(f, "{} {} is {}, outside what the model format can store, scale the mesh down", what, index, value),
+            ModelError::TooMany{what, count, limit} => write!(f, "{} {}, the limit is {}", count, what, limit),
+            ModelError::TextureTooLarge{width, height, bytes, limit} => write!(f, "{}x{} texture needs 0x{:X} bytes of TMEM, only 0x{:X} fit, shrink it or use a smaller format", width, height, bytes, limit),
+            ModelError::BadScale(scale) => write!(f, "scale {} would flip or flatten the model, use a positive scale", scale),
+            ModelError::TooManyColors{index, colors, limit} => write!(f, "texture {} has a {} color palette, the image uses {} colors, reduce them first", index, limit, colors),
+            ModelError::CollisionGrid(reason) => write!(f, "collision grid can't be rebuilt: {}", reason),
+        }
+    }
+}
+
+pub(crate) fn u16_at(bytes: &[u8], offset: usize) -> u16{
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub(crate) fn u32_at(bytes: &[u8], offset: usize) -> u32{
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// `needed` bytes at `offset` or a Truncated error naming the section
+pub(crate) fn section<'a>(bytes: &'a [u8], name: &'static str, offset: usize, needed: usize) -> Result<&'a [u8], ModelError>{
+    bytes.get(offset..offset + needed).ok_or(ModelError::Truncated{section: name, offset: offset, needed: needed, len: bytes.len()})
+}
+
+impl ModelHeader{
+    pub fn parse(bytes: &[u8]) -> Result<ModelHeader, ModelError>{
+        if bytes.len() < HEADER_LEN{
+            return Err(ModelError::Short{len: bytes.len()});
+        }
+        if !bytes.starts_with(&magic::MODEL){
+            return Err(ModelError::BadMagic);
+        }
+        let header = ModelHeader{
+            geo_layout: u32_at(bytes, 0x04),
+            texture_list: u16_at(bytes, 0x08),
+            geo_type: u16_at(bytes, 0x0A),
+            gfx_list: u32_at(bytes, 0x0C),
+            vtx_list: u32_at(bytes, 0x10),
+            unk14: u32_at(bytes, 0x14),
+            bone_list: u32_at(bytes, 0x18),
+            collision_list: u32_at(bytes, 0x1C),
+            mesh_list: u32_at(bytes, 0x20),
+            effects_setup: u32_at(bytes, 0x24),
+            unk28: u32_at(bytes, 0x28),
+            anim_texture_list: u32_at(bytes, 0x2C),
+            tri_count: u16_at(bytes, 0x30),
+            vtx_count: u16_at(bytes, 0x32),
+            unk34: u32_at(bytes, 0x34),
+        };
+        //each known section has to start inside the file with room for its own small header
+        for (name, offset, header_len) in header.sections(){
+            if offset == 0{
+                continue;
+            }
+            if (offset as usize) < HEADER_LEN || offset as usize >= bytes.len(){
+                return Err(ModelError::OffsetOutOfRange{section: name, offset: offset, len: bytes.len()});
+            }
+            section(bytes, name, offset as usize, header_len)?;
+        }
+        return Ok(header)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let mut out = magic::MODEL.to_vec();
+        out.extend_from_slice(&self.geo_layout.to_be_bytes());
+        out.extend_from_slice(&self.texture_list.to_be_bytes());
+        out.extend_from_slice(&self.geo_type.to_be_bytes());
+        for word in [self.gfx_list, self.vtx_list, self.unk14, self.bone_list, self.collision_list, self.mesh_list, self.effects_setup, self.unk28, self.anim_texture_list]{
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.extend_from_slice(&self.tri_count.to_be_bytes());
+        out.extend_from_slice(&self.vtx_count.to_be_bytes());
+        out.extend_from_slice(&self.unk34.to_be_bytes());
+        return out
+    }
+
+    /// name, offset and the length of the fixed header of every section with a known layout
+    pub fn sections(&self) -> [(&'static str, u32, usize); 8]{
+        [
+            ("geo layout", self.geo_layout, 8),
+            ("texture list", self.texture_list as u32, 8),
+            ("display list", self.gfx_list, 8),
+            ("vertex list", self.vtx_list, 0x18),
+            ("bone list", self.bone_list, 8),
+            ("collision list", self.collision_list, 0x18),
+            ("mesh list", self.mesh_list, 4),
+            ("animated textures", self.anim_texture_list, 0),
+        ]
+    }
+
+    /// the texture list records and where the texture data after them starts, empty without a list
+    pub fn textures(&self, bytes: &[u8]) -> Result<(Vec<TextureEntry>, usize), ModelError>{
+        if self.texture_list == 0{
+            return Ok((Vec::new(), 0))
+        }
+        let start = self.texture_list as usize;
+        let count = u16_at(bytes, start + 4) as usize;
+        let records = section(bytes, "texture list", start + 8, count*TextureEntry::LEN)?;
+        let entries = records.chunks_exact(TextureEntry::LEN).map(TextureEntry::from_bytes).collect();
+        return Ok((entries, start + 8 + count*TextureEntry::LEN))
+    }
+
+    /// offset and count of the vertices after the vertex list header, None without a list
+    pub fn vertex_range(&self, bytes: &[u8]) -> Result<Option<(usize, usize)>, ModelError>{
+        if self.vtx_list == 0{
+            return Ok(None)
+        }
+        let start = self.vtx_list as usize + VTX_HEADER_LEN;
+        let count = u16_at(bytes, self.vtx_list as usize + 0x14) as usize;
+        section(bytes, "vertex list", start, count*ModelVertex::LEN)?;
+        return Ok(Some((start, count)))
+    }
+
+    pub fn vertices(&self, bytes: &[u8]) -> Result<Vec<ModelVertex>, ModelError>{
+        let (start, count) = match self.vertex_range(bytes)?{
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        return Ok(bytes[start..start + count*ModelVertex::LEN].chunks_exact(ModelVertex::LEN).map(ModelVertex::from_bytes).collect())
+    }
+
+    /// the commands after the display list's count, empty without a list
+    pub fn display_list(&self, bytes: &[u8]) -> Result<DisplayList, ModelError>{
+        if self.gfx_list == 0{
+            return Ok(DisplayList::default())
+        }
+        let start = self.gfx_list as usize + 8;
+        let count = u32_at(bytes, self.gfx_list as usize) as usize;
+        return Ok(DisplayList::from_bytes(section(bytes, "display list", start, count*8)?))
+    }
+
+    /// the collision list, None without one
+    pub fn collision(&self, bytes: &[u8]) -> Result<Option<CollisionMesh>, ModelError>{
+        if self.collision_list == 0{
+            return Ok(None)
+        }
+        let start = self.collision_list as usize;
+        let head = section(bytes, "collision list", start, CollisionMesh::HEADER_LEN)?;
+        let s16 = |offset: usize| u16_at(head, offset) as i16;
+        let cell_count = u16_at(head, 0x10) as usize;
+        let tri_count = u16_at(head, 0x12) as usize;
+        let cells = section(bytes, "collision list", start + CollisionMesh::HEADER_LEN, 4*cell_count)?;
+        let tris = section(bytes, "collision list", start + CollisionMesh::HEADER_LEN + 4*cell_count, CollisionTri::LEN*tri_count)?;
+        return Ok(Some(CollisionMesh{
+            min: [s16(0), s16(2), s16(4)],
+            max: [s16(6), s16(8), s16(0xA)],
+            stride: [u16_at(head, 0xC), u16_at(head, 0xE)],
+            unk14: u16_at(head, 0x14),
+            cells: cells.chunks_exact(4).map(|c| (u16_at(c, 0), u16_at(c, 2))).collect(),
+            triangles: tris.chunks_exact(CollisionTri::LEN).map(CollisionTri::from_bytes).collect(),
+        }))
+    }
+
+    /// the bone list as a skeleton, parents refer to bone ids which are looked up among the entries
+    pub fn skeleton(&self, bytes: &[u8]) -> Result<ModelSkeleton, ModelError>{
+        if self.bone_list == 0{
+            return Ok(ModelSkeleton::default())
+        }
+        let count = self.bone_count(bytes);
+        let entries = section(bytes, "bone list", self.bone_list as usize + 8, count*0x10)?;
+        let f32_at = |e: &[u8], o: usize| f32::from_bits(u32_at(e, o));
+        let ids : Vec<i16> = entries.chunks_exact(0x10).map(|e| u16_at(e, 0xC) as i16).collect();
+        let bones = entries.chunks_exact(0x10).map(|e| Bone{
+            parent: ids.iter().position(|id| *id == u16_at(e, 0xE) as i16),
+            position: [f32_at(e, 0), f32_at(e, 4), f32_at(e, 8)],
+        }).collect();
+        return Ok(ModelSkeleton{bones: bones})
+    }
+
+    /// the mesh list entries, empty without a list
+    pub fn meshes(&self, bytes: &[u8]) -> Result<Vec<MeshInfo>, ModelError>{
+        let mut offset = self.mesh_list as usize + 4;
+        let mut meshes = Vec::new();
+        for _ in 0..self.mesh_count(bytes){
+            let head = section(bytes, "mesh list", offset, 4)?;
+            let count = u16_at(head, 2) as usize;
+            let indices = section(bytes, "mesh list", offset + 4, 2*count)?;
+            meshes.push(MeshInfo{id: u16_at(head, 0) as i16, vertices: indices.chunks_exact(2).map(|i| u16_at(i, 0)).collect()});
+            offset += 4 + 2*count;
+        }
+        return Ok(meshes)
+    }
+
+    /// entries in the mesh list, 0 without one
+    pub fn mesh_count(&self, bytes: &[u8]) -> usize{
+        match self.mesh_list{
+            0 => 0,
+            offset => u16_at(bytes, offset as usize) as usize,
+        }
+    }
+
+    /// entries in the bone list, 0 without one
+    pub fn bone_count(&self, bytes: &[u8]) -> usize{
+        match self.bone_list{
+            0 => 0,
+            offset => u16_at(bytes, offset as usize + 4) as usize,
+        }
+    }
+}
+
+/// every section's offset and length, lengths run up to the next section so any padding
+/// between them stays with the section before it
+pub fn section_spans(header: &ModelHeader, len: usize) -> Vec<(&'static str, usize, usize)>{
+    let mut offsets : Vec<(&'static str, usize)> = header.sections().iter().map(|(name, offset, _)| (*name, *offset as usize)).collect();
+    offsets.extend_from_slice(&[("unk14", header.unk14 as usize), ("effects", header.effects_setup as usize), ("unk28", header.unk28 as usize)]);
+    offsets.retain(|(_, offset)| *offset >= HEADER_LEN && *offset < len);
+    offsets.sort_by_key(|(_, offset)| *offset);
+    let ends : Vec<usize> = offsets.iter().skip(1).map(|(_, offset)| *offset).chain(std::iter::once(len)).collect();
+    return offsets.iter().zip(ends).map(|((name, offset), end)| (*name, *offset, end - offset)).collect()
+}
+
+/// a triangulated .obj, corners are a position index and an optional uv index, both from 0
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ObjMesh{
+    pub positions: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 3]>, //one per position, white when the file has none
+    pub uvs: Vec<[f32; 2]>,
+    pub triangles: Vec<[(usize, Option<usize>); 3]>,
+    pub mtllib: Option<String>,
+    pub materials: Vec<String>, //materials faces use, in order of first use
+}
+
+impl ObjMesh{
+    pub fn parse(text: &str) -> Result<ObjMesh, ModelError>{
+        let mut mesh = ObjMesh::default();
+        let mut material : Option<String> = None;
+        for (i, line) in text.lines().enumerate(){
+            let error = |reason: String| ModelError::Obj{line: i + 1, reason: reason};
+            let mut words = line.split_whitespace();
+            let floats = |words: std::str::SplitWhitespace| -> Result<Vec<f32>, ModelError>{
+                words.map(|w| w.parse::<f32>().map_err(|_| error(format!("\"{}\" is not a number", w)))).collect()
+            };
+            match words.next(){
+                Some("v") => {
+                    let values = floats(words)?;
+                    if values.len() != 3 && values.len() != 6{
+                        return Err(error(format!("expected x y z or x y z r g b, got {} values", values.len())));
+                    }
+                    mesh.positions.push([values[0], values[1], values[2]]);
+                    mesh.colors.push(match values.len(){ 6 => [values[3], values[4], values[5]], _ => [1.0; 3] });
+                },
+                Some("vt") => {
+                    let values = floats(words)?;
+                    if values.len() < 2{
+                        return Err(error(String::from("expected u v")));
+                    }
+                    mesh.uvs.push([values[0], values[1]]);
+                },
+                Some("f") => {
+                    let mut corners = Vec::new();
+                    for word in words{
+                        //v, v/vt, v/vt/vn or v//vn, negative indices count back from the end
+                        let mut parts = word.split('/');
+                        let index = |part: Option<&str>, count: usize| -> Result<Option<usize>, ModelError>{
+                            let part = match part{ Some(p) if !p.is_empty() => p, _ => return Ok(None) };
+                            let n = part.parse::<i64>().map_err(|_| error(format!("bad face index \"{}\"", word)))?;
+                            let n = if n < 0 { count as i64 + n } else { n - 1 };
+                            match n >= 0 && (n as usize) < count{
+                                true => Ok(Some(n as usize)),
+                                false => Err(error(format!("face index \"{}\" refers to a missing vertex", word))),
+                            }
+                        };
+                        let position = index(parts.next(), mesh.positions.len())?.ok_or(error(format!("face corner \"{}\" has no position", word)))?;
+                        corners.push((position, index(parts.next(), mesh.uvs.len())?));
+                    }
+                    if corners.len() < 3{
+                        return Err(error(String::from("face with fewer than 3 corners")));
+                    }
+                    //polygons are split into a fan
+                    for c in 1..corners.len() - 1{
+                        mesh.triangles.push([corners[0], corners[c], corners[c + 1]]);
+                    }
+                    if let Some(name) = material.take(){
+                        if !mesh.materials.contains(&name){
+                            mesh.materials.push(name.clone());
+                        }
+                        material = Some(name);
+                    }
+                },
+                Some("usemtl") => material = words.next().map(String::from),
+                Some("mtllib") => mesh.mtllib = Some(words.collect::<Vec<&str>>().join(" ")),
+                _ => {},
+            }
+        }
+        return Ok(mesh)
+    }
+}
+
+/// the image file `map_Kd` gives `material` in a .mtl
+pub fn mtl_texture(text: &str, material: &str) -> Option<String>{
+    let mut current = None;
+    for line in text.lines(){
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("newmtl "){
+            current = Some(name.trim());
+        }
+        if let (Some(name), Some(file)) = (current, line.strip_prefix("map_Kd ")){
+            if name == material{
+                //options like -s come first, the file name is last
+                return file.split_whitespace().last().map(String::from)
+            }
+        }
+    }
+    return None
+}
+
+/// N64 format and size codes for the texture formats models use
+pub fn image_codes(format: ImgFmt) -> Option<(u8, u8)>{
+    match format{
+        ImgFmt::RGBA16 => Some((0, 2)),
+        ImgFmt::RGBA32 => Some((0, 3)),
+        ImgFmt::CI4 => Some((2, 0)),
+        ImgFmt::CI8 => Some((2, 1)),
+        ImgFmt::IA8 => Some((3, 1)),
+        _ => None,
+    }
+}
+
+/// bytes of TMEM a texture takes and how many are available for it, CI palettes take the top half
+pub fn tmem_usage(format: ImgFmt, width: usize, height: usize) -> (usize, usize){
+    let bits = match format{ ImgFmt::CI4 | ImgFmt::I4 | ImgFmt::IA4 => 4, ImgFmt::RGBA16 => 16, ImgFmt::RGBA32 => 32, _ => 8 };
+    let limit = match format{ ImgFmt::CI4 | ImgFmt::CI8 => 0x800, _ => 0x1000 };
+    return (width*height*bits/8, limit)
+}
+
+/// counts `Model::stats` reports
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct ModelStats{
+    pub vertices: usize,
+    pub triangles: usize, //drawn by the display list
+    pub textures: usize,
+    pub texture_bytes: usize, //palettes included, textures of unknown formats count 0
+    pub gfx_commands: usize,
+}
+
+/// something `Model::lint` expects to break or misdraw in game
+#[derive(PartialEq, Debug, Clone)]
+pub enum LintIssue{
+    TextureTooLarge{index: usize, width: usize, height: usize, bytes: usize, limit: usize},
+    VertexCacheOverflow{command: usize, first: usize, count: usize},
+    DegenerateTriangle{triangle: usize, vertices: [usize; 3]},
+    DisplayList(GfxIssue),
+}
+
+impl LintIssue{
+    /// stable name of the check that found it
+    pub fn kind(&self) -> &'static str{
+        match self{
+            LintIssue::TextureTooLarge{..} => "texture_tmem",
+            LintIssue::VertexCacheOverflow{..} => "vertex_cache",
+            LintIssue::DegenerateTriangle{..} => "degenerate_triangle",
+            LintIssue::DisplayList(_) => "display_list",
+        }
+    }
+}
+
+impl fmt::Display for LintIssue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            LintIssue::TextureTooLarge{index, width, height, bytes, limit} => write!(f, "texture {} is {}x{} and needs 0x{:X} bytes of TMEM, only 0x{:X} fit", index, width, height, bytes, limit),
+            LintIssue::VertexCacheOverflow{command, first, count} => write!(f, "command {}: loads {} vertices from slot {}, the cache holds {}", command, count, first, gfx::VTX_CACHE),
+            LintIssue::DegenerateTriangle{triangle, vertices} => write!(f, "triangle {} ({}, {}, {}) has no area", triangle, vertices[0], vertices[1], vertices[2]),
+            LintIssue::DisplayList(issue) => write!(f, "{}", issue),
+        }
+    }
+}
+
+/// whether the triangle's corners are repeated or in a line
+pub fn is_degenerate(a: [i16; 3], b: [i16; 3], c: [i16; 3]) -> bool{
+    let d = |p: [i16; 3], q: [i16; 3]| [0, 1, 2].map(|i| q[i] as i64 - p[i] as i64);
+    let (u, v) = (d(a, b), d(a, c));
+    return u[1]*v[2] == u[2]*v[1] && u[2]*v[0] == u[0]*v[2] && u[0]*v[1] == u[1]*v[0]
+}
+
+/// what `build_geometry` made: the vertex list and display list sections and their counts
+pub struct Geometry{
+    pub vtx_section: Vec<u8>,
+    pub gfx_section: Vec<u8>,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+}
+
+/// vertex list and display list for a single material mesh. vertices are batched 32 at a time
+/// in draw order, `texture` is the entry at texture data offset 0
+pub fn build_geometry(mesh: &ObjMesh, texture: Option<&TextureEntry>) -> Result<Geometry, ModelError>{
+    //a model vertex per position and uv pair, repeated in every batch that uses it
+    let mut vertices : Vec<ModelVertex> = Vec::new();
+    let mut batches : Vec<(usize, Vec<[usize; 3]>)> = Vec::new(); //first vertex and triangles in cache slots
+    let mut cache : Vec<(usize, Option<usize>)> = Vec::new();
+    let mut tris : Vec<[usize; 3]> = Vec::new();
+    let size = texture.map(|t| (t.width as f32, t.height as f32));
+    for (i, tri) in mesh.triangles.iter().enumerate(){
+        let new_corners = tri.iter().filter(|c| !cache.contains(c)).collect::<Vec<_>>().len();
+        if cache.len() + new_corners > gfx::VTX_CACHE{
+            batches.push((vertices.len() - cache.len(), std::mem::take(&mut tris)));
+            cache.clear();
+        }
+        let mut slots = [0; 3];
+        for (n, corner) in tri.iter().enumerate(){
+            slots[n] = match cache.iter().position(|c| c == corner){
+                Some(slot) => slot,
+                None => {
+                    let (p, uv) = *corner;
+                    let pos = mesh.positions[p];
+                    let mut position = [0i16; 3];
+                    for a in 0..3{
+                        if pos[a].round() < i16::MIN as f32 || pos[a].round() > i16::MAX as f32{
+                            return Err(ModelError::OutOfRange{what: "vertex coordinate of position", index: p + 1, value: pos[a]});
+                        }
+                        position[a] = pos[a].round() as i16;
+                    }
+                    let mut st = [0i16; 2];
+                    if let (Some(uv), Some((w, h))) = (uv, size){
+                        //inverse of the export, obj v runs bottom to top
+                        let texels = [mesh.uvs[uv][0]*w*32.0, (1.0 - mesh.uvs[uv][1])*h*32.0];
+                        for a in 0..2{
+                            if texels[a].round() < i16::MIN as f32 || texels[a].round() > i16::MAX as f32{
+                                return Err(ModelError::OutOfRange{what: "texture coordinate", index: uv + 1, value: mesh.uvs[uv][a]});
+                            }
+                            st[a] = texels[a].round() as i16;
+                        }
+                    }
+                    let color = mesh.colors[p].map(|c| (c.clamp(0.0, 1.0)*255.0).round() as u8);
+                    vertices.push(ModelVertex{position: position, flag: 0, uv: st, color: [color[0], color[1], color[2], 0xFF]});
+                    cache.push(*corner);
+                    cache.len() - 1
+                },
+            };
+        }
+        tris.push(slots);
+        if i + 1 == mesh.triangles.len(){
+            batches.push((vertices.len() - cache.len(), std::mem::take(&mut tris)));
+        }
+    }
+    if vertices.len() > u16::MAX as usize{
+        return Err(ModelError::TooMany{what: "vertices after splitting into 32 vertex loads", count: vertices.len(), limit: u16::MAX as usize});
+    }
+    if mesh.triangles.len() > u16::MAX as usize{
+        return Err(ModelError::TooMany{what: "triangles", count: mesh.triangles.len(), limit: u16::MAX as usize});
+    }
+
+    let mut commands : Vec<GfxCommand> = Vec::new();
+    commands.push(GfxCommand::pipe_sync());
+    commands.extend_from_slice(&GfxCommand::geometry_mode(gfx::G_LIGHTING, gfx::G_SHADE | gfx::G_SHADING_SMOOTH | gfx::G_CULL_BACK));
+    commands.push(GfxCommand::combine(texture.is_some()));
+    match texture{
+        Some(entry) => {
+            let (format, size) = image_codes(entry.format).ok_or(ModelError::BadTexture{index: 0, reason: format!("{} textures can't be drawn", entry.format)})?;
+            let address = (gfx::TEXTURE_SEGMENT as u32) << 24;
+            let palette = match entry.format{ ImgFmt::CI4 => 16, ImgFmt::CI8 => 256, _ => 0 };
+            if palette != 0{
+                commands.extend(GfxCommand::load_tlut(address, palette));
+            }
+            commands.extend(GfxCommand::load_texture_block(format, size, entry.width as usize, entry.height as usize, address + 2*palette as u32));
+            commands.push(GfxCommand::texture(true));
+        },
+        None => commands.push(GfxCommand::texture(false)),
+    }
+    for (first, tris) in batches.iter(){
+        let count = batches.iter().find(|(f, _)| f > first).map(|(f, _)| *f).unwrap_or(vertices.len()) - first;
+        commands.push(GfxCommand::vtx(0, count, (gfx::VTX_SEGMENT as u32) << 24 | (first*ModelVertex::LEN) as u32));
+        for pair in tris.chunks(2){
+            commands.push(match pair{
+                [a, b] => GfxCommand::tri2(*a, *b),
+                _ => GfxCommand::tri1(pair[0]),
+            });
+        }
+    }
+    commands.push(GfxCommand::end_dl());
+
+    let mut gfx_section = (commands.len() as u32).to_be_bytes().to_vec();
+    gfx_section.extend_from_slice(&[0; 4]);
+    gfx_section.extend(DisplayList{commands: commands}.to_bytes());
+    return Ok(Geometry{
+        vtx_section: vertex_section(&vertices),
+        gfx_section: gfx_section,
+        vertex_count: vertices.len(),
+        triangle_count: mesh.triangles.len(),
+    })
+}
+
+/// a vertex list with its bounds, center and radii worked out from the vertices
+pub fn vertex_section(vertices: &[ModelVertex]) -> Vec<u8>{
+    let mut min = [i16::MAX; 3];
+    let mut max = [i16::MIN; 3];
+    for v in vertices.iter(){
+        for a in 0..3{
+            min[a] = min[a].min(v.position[a]);
+            max[a] = max[a].max(v.position[a]);
+        }
+    }
+    if vertices.is_empty(){
+        min = [0; 3];
+        max = [0; 3];
+    }
+    let center = [0, 1, 2].map(|a| ((min[a] as i32 + max[a] as i32)/2) as i16);
+    let radius = |from: [i16; 3]| vertices.iter()
+        .map(|v| (0..3).map(|a| (v.position[a] as f32 - from[a] as f32).powi(2)).sum::<f32>().sqrt())
+        .fold(0.0f32, f32::max).ceil().min(i16::MAX as f32) as i16;
+    let mut out = Vec::with_capacity(VTX_HEADER_LEN + vertices.len()*ModelVertex::LEN);
+    for word in min.iter().chain(max.iter()).chain(center.iter()){
+        out.extend_from_slice(&word.to_be_bytes());
+    }
+    out.extend_from_slice(&radius(center).to_be_bytes());
+    out.extend_from_slice(&(vertices.len() as u16).to_be_bytes());
+    out.extend_from_slice(&radius([0; 3]).to_be_bytes());
+    for v in vertices.iter(){
+        out.extend(v.to_bytes());
+    }
+    return out
+}
+
+/// swaps the section at `offset` for `section` and moves everything after it, header offsets past
+/// `offset` are shifted to match. sections only address their own contents so moving them is safe
+pub fn splice_section(bytes: &[u8], header: &ModelHeader, offset: usize, section: &[u8]) -> Vec<u8>{
+    let old_len = section_spans(header, bytes.len()).iter().find(|(_, o, _)| *o == offset).map(|(_, _, len)| *len).unwrap_or(0);
+    let mut new_section = section.to_vec();
+    //keep whatever follows 8 byte aligned
+    while !new_section.len().is_multiple_of(8) && offset + old_len < bytes.len(){
+        new_section.push(0);
+    }
+    let delta = new_section.len() as i64 - old_len as i64;
+    let mut header = header.clone();
+    let shift = |field: u32| if field as usize > offset { (field as i64 + delta) as u32 } else { field };
+    header.geo_layout = shift(header.geo_layout);
+    header.texture_list = shift(header.texture_list as u32) as u16;
+    header.gfx_list = shift(header.gfx_list);
+    header.vtx_list = shift(header.vtx_list);
+    header.unk14 = shift(header.unk14);
+    header.bone_list = shift(header.bone_list);
+    header.collision_list = shift(header.collision_list);
+    header.mesh_list = shift(header.mesh_list);
+    header.effects_setup = shift(header.effects_setup);
+    header.unk28 = shift(header.unk28);
+    header.anim_texture_list = shift(header.anim_texture_list);
+
+    let mut out = header.to_bytes();
+    out.extend_from_slice(&bytes[HEADER_LEN..offset]);
+    out.extend(new_section);
+    out.extend_from_slice(&bytes[offset + old_len..]);
+    return out
+}
@@ -0,0 +1,621 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::asset::{self, Asset, AssetType, ImgFmt};
+use super::anim;
+use super::model;
+use super::text;
+
+pub struct SizeChange{
+    pub seg: usize,
+    pub idx: usize,
+    pub asset_type: AssetType,
+    pub original: usize,
+    pub repacked: usize,
+}
+
+impl SizeChange{
+    pub fn growth(&self) -> isize{
+        self.repacked as isize - self.original as isize
+    }
+}
+
+pub struct TypeTotals{
+    pub asset_type: AssetType,
+    pub changed: usize,
+    pub original: usize,
+    pub repacked: usize,
+}
+
+pub struct SizeReport{
+    pub changes: Vec<SizeChange>, //only assets whose size differs
+    pub by_type: Vec<TypeTotals>,
+    pub original_total: usize,
+    pub repacked_total: usize,
+}
+
+/// compares repacked asset sizes against the originals, assets missing from `repacked` count as size 0
+pub fn size_regressions(original: &[(usize, usize, usize)], repacked: &[(usize, usize, Vec<u8>)]) -> SizeReport{
+    let mut changes : Vec<SizeChange> = Vec::new();
+    let mut by_type : Vec<TypeTotals> = Vec::new();
+    let mut original_total = 0;
+    let mut repacked_total = 0;
+
+    for (seg, idx, orig_size) in original.iter(){
+        let new_bytes = repacked.iter().find(|(s, i, _)| s == seg && i == idx).map(|(_, _, b)| b);
+        let asset_type = match new_bytes{
+            Some(b) => asset::from_seg_indx_and_bytes(*seg, *idx, b).get_type(),
+            None => AssetType::Binary,
+        };
+        let new_size = new_bytes.map(|b| b.len()).unwrap_or(0);
+        original_total += orig_size;
+        repacked_total += new_size;
+
+        if new_size == *orig_size{ continue; }
+
+        match by_type.iter_mut().find(|t| t.asset_type == asset_type){
+            Some(t) => {t.changed += 1; t.original += orig_size; t.repacked += new_size},
+            None => by_type.push(TypeTotals{asset_type: asset_type, changed: 1, original: *orig_size, repacked: new_size}),
+        }
+        changes.push(SizeChange{seg: *seg, idx: *idx, asset_type: asset_type, original: *orig_size, repacked: new_size});
+    }
+
+    SizeReport{changes: changes, by_type: by_type, original_total: original_total, repacked_total: repacked_total}
+}
+
+impl SizeReport{
+    /// changed assets sorted by growth, largest first
+    pub fn top_offenders(&self, n: usize) -> Vec<&SizeChange>{
+        let mut sorted : Vec<&SizeChange> = self.changes.iter().collect();
+        sorted.sort_by_key(|r| std::cmp::Reverse(r.growth()));
+        sorted.truncate(n);
+        return sorted;
+    }
+
+    /// `allowed` is the per asset growth in bytes tolerated for a type, unlisted types may not grow at all
+    pub fn has_unexpected_growth(&self, allowed: &[(AssetType, usize)]) -> bool{
+        self.changes.iter().any(|c|{
+            let limit = allowed.iter().find(|(t, _)| *t == c.asset_type).map(|(_, l)| *l).unwrap_or(0);
+            c.growth() > limit as isize
+        })
+    }
+}
+
+impl fmt::Display for SizeReport{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        writeln!(f, "total: 0x{:X} -> 0x{:X} ({:+} bytes), {} assets changed size",
+            self.original_total, self.repacked_total, self.repacked_total as isize - self.original_total as isize, self.changes.len())?;
+        for t in self.by_type.iter(){
+            writeln!(f, "  {:?}: {} assets, 0x{:X} -> 0x{:X} ({:+} bytes)",
+                t.asset_type, t.changed, t.original, t.repacked, t.repacked as isize - t.original as isize)?;
+        }
+        for c in self.top_offenders(10){
+            writeln!(f, "  seg {} idx 0x{:04X} {:?}: 0x{:X} -> 0x{:X} ({:+} bytes)",
+                c.seg, c.idx, c.asset_type, c.original, c.repacked, c.growth())?;
+        }
+        Ok(())
+    }
+}
+
+pub struct FrameDiff{
+    pub frame: usize,
+    pub size: Option<((usize, usize), (usize, usize))>, //only set when the dimensions differ
+    pub header_changed: bool, //position and unknown bytes of the frame header
+    pub layout_changed: bool, //chunk headers or frame offset
+    pub palette_changed: bool,
+    pub pixels_changed: usize,
+    pub bounds: Option<(usize, usize, usize, usize)>, //inclusive x0, y0, x1, y1 of the differing pixels
+}
+
+impl FrameDiff{
+    pub fn is_visual(&self) -> bool{
+        self.size.is_some() || self.pixels_changed != 0
+    }
+
+    pub fn is_structural(&self) -> bool{
+        self.header_changed || self.layout_changed || self.palette_changed
+    }
+}
+
+pub struct SpriteDiff{
+    pub format: (ImgFmt, ImgFmt),
+    pub header_changed: bool, //sprite header, ignoring the frame count
+    pub frame_count: (usize, usize),
+    pub raw_changed: bool, //byte comparison, the only thing available for unparsable sprites
+    pub frames: Vec<FrameDiff>, //only frames that differ
+}
+
+impl SpriteDiff{
+    pub fn is_identical(&self) -> bool{
+        !self.raw_changed
+    }
+
+    /// true when the decoded images match even though the bytes don't
+    pub fn is_structural_only(&self) -> bool{
+        let parsed = !matches!(self.format.0, ImgFmt::Unknown(_)) && !matches!(self.format.1, ImgFmt::Unknown(_));
+        parsed && self.frame_count.0 == self.frame_count.1 && self.frames.iter().all(|f| !f.is_visual())
+    }
+}
+
+/// parses both sprites and compares them frame by frame, pixels are compared after decoding so
+/// re-encodings that look the same only show up as structural changes
+pub fn sprite_diff(original: &[u8], repacked: &[u8]) -> SpriteDiff{
+    let a = asset::Sprite::from_bytes(original);
+    let b = asset::Sprite::from_bytes(repacked);
+
+    let strip_cnt = |h: &[u8]| if h.len() > 2 {h[2..].to_vec()} else {Vec::new()};
+    let mut frames : Vec<FrameDiff> = Vec::new();
+    for (i, (fa, fb)) in a.frame.iter().zip(b.frame.iter()).enumerate(){
+        //size and chunk count are reported on their own
+        let placement = |h: &asset::SpriteFrameHeader| asset::SpriteFrameHeader{w: 0, h: 0, chunk_count: 0, ..*h};
+        let mut diff = FrameDiff{
+            frame: i,
+            size: None,
+            header_changed: placement(&fa.header) != placement(&fb.header),
+            layout_changed: fa.chk_hdrs != fb.chk_hdrs || fa.offset() != fb.offset(),
+            palette_changed: fa.palette() != fb.palette(),
+            pixels_changed: 0,
+            bounds: None,
+        };
+
+        if (fa.width(), fa.height()) != (fb.width(), fb.height()){
+            diff.size = Some(((fa.width(), fa.height()), (fb.width(), fb.height())));
+        }
+        else{
+            let w = fa.width();
+            for (j, (pa, pb)) in fa.rgba32().chunks_exact(4).zip(fb.rgba32().chunks_exact(4)).enumerate(){
+                if pa == pb{ continue; }
+                let (x, y) = (j % w, j / w);
+                diff.pixels_changed += 1;
+                diff.bounds = Some(match diff.bounds{
+                    None => (x, y, x, y),
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                });
+            }
+        }
+
+        if diff.is_visual() || diff.is_structural(){
+            frames.push(diff);
+        }
+    }
+
+    SpriteDiff{
+        format: (a.format(), b.format()),
+        header_changed: strip_cnt(a.header()) != strip_cnt(b.header()),
+        frame_count: (a.frame.len(), b.frame.len()),
+        raw_changed: original != repacked,
+        frames: frames,
+    }
+}
+
+impl fmt::Display for SpriteDiff{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        if self.is_identical(){
+            return writeln!(f, "identical");
+        }
+        if self.format.0 != self.format.1{
+            writeln!(f, "format: {:?} -> {:?}", self.format.0, self.format.1)?;
+        }
+        if self.header_changed{
+            writeln!(f, "sprite header differs")?;
+        }
+        if self.frame_count.0 != self.frame_count.1{
+            writeln!(f, "frames: {} -> {}", self.frame_count.0, self.frame_count.1)?;
+        }
+        for d in self.frames.iter(){
+            let mut parts : Vec<String> = Vec::new();
+            if let Some(((wa, ha), (wb, hb))) = d.size{
+                parts.push(format!("size {}x{} -> {}x{}", wa, ha, wb, hb));
+            }
+            if d.header_changed{ parts.push(String::from("header differs")); }
+            if d.layout_changed{ parts.push(String::from("chunk layout differs")); }
+            if d.palette_changed{ parts.push(String::from("palette differs")); }
+            if let Some((x0, y0, x1, y1)) = d.bounds{
+                parts.push(format!("{} pixels differ in ({}, {})-({}, {})", d.pixels_changed, x0, y0, x1, y1));
+            }
+            writeln!(f, "  frame {}: {}", d.frame, parts.join(", "))?;
+        }
+        if matches!(self.format.0, ImgFmt::Unknown(_)) || matches!(self.format.1, ImgFmt::Unknown(_)){
+            return writeln!(f, "bytes differ, sprite could not be decoded");
+        }
+        match self.is_structural_only(){
+            true => writeln!(f, "structural changes only, decoded frames match"),
+            false => writeln!(f, "visual changes"),
+        }
+    }
+}
+
+/// parses an asset's bytes, writes its yaml to the scratch path and rebuilds the bytes from it.
+/// None when the bytes are not a type the check covers
+pub type RoundTrip = fn(&[u8], &Path) -> Option<Result<Vec<u8>, String>>;
+
+pub struct RoundTripFailure{
+    pub file: PathBuf,
+    pub reason: String,
+}
+
+pub struct RoundTripReport{
+    pub checked: usize,
+    pub skipped: usize,
+    pub failures: Vec<RoundTripFailure>,
+}
+
+impl RoundTripReport{
+    pub fn passed(&self) -> bool{
+        self.failures.is_empty()
+    }
+}
+
+/// runs `check` over every file below `dir`, a corpus of extracted asset bins, and compares the
+/// rebuilt bytes with the originals
+pub fn round_trip_dir(dir: &Path, scratch: &Path, check: RoundTrip) -> RoundTripReport{
+    let mut report = RoundTripReport{checked: 0, skipped: 0, failures: Vec::new()};
+    let mut files : Vec<PathBuf> = Vec::new();
+    collect_files(dir, &mut files);
+    files.sort();
+    fs::create_dir_all(scratch).unwrap();
+
+    for file in files{
+        let original = match fs::read(&file){
+            Ok(bytes) => bytes,
+            Err(e) => {report.failures.push(RoundTripFailure{file: file, reason: e.to_string()}); continue;},
+        };
+        let name = file.file_name().unwrap().to_string_lossy().to_string() + ".yaml";
+        let reason = match check(&original, &scratch.join(name)){
+            None => {report.skipped += 1; continue;},
+            Some(Err(e)) => Some(e),
+            Some(Ok(rebuilt)) => byte_diff(&original, &rebuilt),
+        };
+        report.checked += 1;
+        if let Some(reason) = reason{
+            report.failures.push(RoundTripFailure{file: file, reason: reason});
+        }
+    }
+    return report;
+}
+
+/// the round trip for Dialog, QuizQuestion and GruntyQuestion bins
+pub fn text_round_trip(bytes: &[u8], yaml_path: &Path) -> Option<Result<Vec<u8>, String>>{
+    let detection = text::detect_kind(bytes);
+    if detection.confidence != text::Confidence::Full{
+        return None;
+    }
+    let rebuilt = match detection.kind{
+        AssetType::Dialog => match asset::Dialog::parse(bytes){
+            Ok(d) => {d.write(yaml_path); asset::Dialog::read(yaml_path).map(|a| a.to_bytes()).map_err(|e| e.to_string())},
+            Err(e) => Err(e.to_string()),
+        },
+        AssetType::QuizQuestion => match asset::QuizQuestion::parse(bytes){
+            Ok(q) => {q.write(yaml_path); asset::QuizQuestion::read(yaml_path).map(|a| a.to_bytes()).map_err(|e| e.to_string())},
+            Err(e) => Err(e.to_string()),
+        },
+        AssetType::GruntyQuestion => match asset::GruntyQuestion::parse(bytes){
+            Ok(q) => {q.write(yaml_path); asset::GruntyQuestion::read(yaml_path).map(|a| a.to_bytes()).map_err(|e| e.to_string())},
+            Err(e) => Err(e.to_string()),
+        },
+        _ => return None,
+    };
+    return Some(rebuilt);
+}
+
+/// the round trip for `.lvl_setup.bin` files, straight through the bytes and then through the yaml
+pub fn level_setup_round_trip(bytes: &[u8], yaml_path: &Path) -> Option<Result<Vec<u8>, String>>{
+    let name = yaml_path.file_name()?.to_string_lossy();
+    if !name.ends_with(".lvl_setup.bin.yaml"){
+        return None;
+    }
+    let setup = asset::LevelSetup::from_bytes(bytes);
+    let direct = setup.to_bytes();
+    if direct != bytes{
+        return Some(Ok(direct));
+    }
+    setup.write(&yaml_path.with_extension("bin"));
+    return Some(asset::LevelSetup::read(yaml_path).map(|a| a.to_bytes()).map_err(|e| e.to_string()));
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>){
+    let entries = match fs::read_dir(dir){
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten(){
+        let path = entry.path();
+        if path.is_dir(){ collect_files(&path, out); }
+        else { out.push(path); }
+    }
+}
+
+//None when equal, otherwise sizes, first differing offset and how many bytes differ
+fn byte_diff(original: &[u8], rebuilt: &[u8]) -> Option<String>{
+    if original == rebuilt{
+        return None;
+    }
+    let first = original.iter().zip(rebuilt.iter()).position(|(a, b)| a != b).unwrap_or(original.len().min(rebuilt.len()));
+    let differing = original.iter().zip(rebuilt.iter()).filter(|(a, b)| a != b).count();
+    return Some(format!("0x{:X} -> 0x{:X} bytes, first difference at 0x{:X}, {} overlapping bytes differ",
+        original.len(), rebuilt.len(), first, differing));
+}
+
+impl fmt::Display for RoundTripReport{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        for failure in self.failures.iter(){
+            writeln!(f, "{}: {}", failure.file.display(), failure.reason)?;
+        }
+        writeln!(f, "{} checked, {} skipped, {} failed", self.checked, self.skipped, self.failures.len())
+    }
+}
+
+pub struct TextStatsSummary{
+    pub files: Vec<(PathBuf, text::TextStats)>,
+    pub total_size: usize,
+    pub total_entries: usize,
+}
+
+impl TextStatsSummary{
+    /// the file holding the longest string, with that string's length
+    pub fn longest(&self) -> Option<(&PathBuf, usize)>{
+        self.files.iter()
+            .filter_map(|(file, stats)| stats.longest.map(|(_, _, len)| (file, len)))
+            .max_by_key(|(_, len)| *len)
+    }
+}
+
+/// `stats` of every dialog and question bin below `dir`, other files are ignored
+pub fn text_stats_dir(dir: &Path) -> TextStatsSummary{
+    let mut files : Vec<PathBuf> = Vec::new();
+    collect_files(dir, &mut files);
+    files.sort();
+    let mut summary = TextStatsSummary{files: Vec::new(), total_size: 0, total_entries: 0};
+    for file in files{
+        let bytes = match fs::read(&file){
+            Ok(bytes) => bytes,
+            Err(_) => continue,
+        };
+        let detection = text::detect_kind(&bytes);
+        if detection.confidence != text::Confidence::Full{
+            continue;
+        }
+        let stats = match detection.kind{
+            AssetType::Dialog => match asset::Dialog::parse(&bytes){ Ok(d) => d.stats(), Err(_) => continue },
+            AssetType::QuizQuestion => match asset::QuizQuestion::parse(&bytes){ Ok(q) => q.stats(), Err(_) => continue },
+            AssetType::GruntyQuestion => match asset::GruntyQuestion::parse(&bytes){ Ok(q) => q.stats(), Err(_) => continue },
+            _ => continue,
+        };
+        summary.total_size += stats.total_size;
+        summary.total_entries += stats.entry_count();
+        summary.files.push((file, stats));
+    }
+    return summary;
+}
+
+/// which animations below a folder parse, for triaging the ones still kept as raw bytes
+pub struct AnimStatsSummary{
+    pub parsed: Vec<(PathBuf, anim::AnimStats)>,
+    pub raw: Vec<PathBuf>,
+}
+
+/// `stats` of every `.anim` yaml and `.anim.bin` below `dir`
+pub fn anim_stats_dir(dir: &Path) -> AnimStatsSummary{
+    let mut files : Vec<PathBuf> = Vec::new();
+    collect_files(dir, &mut files);
+    files.sort();
+    let mut summary = AnimStatsSummary{parsed: Vec::new(), raw: Vec::new()};
+    for file in files{
+        let name = file.to_string_lossy();
+        if !name.ends_with(".anim") && !name.ends_with(".anim.bin"){
+            continue;
+        }
+        match asset::Animation::read(&file).ok().and_then(|a| a.stats()){
+            Some(stats) => summary.parsed.push((file, stats)),
+            None => summary.raw.push(file),
+        }
+    }
+    return summary;
+}
+
+/// how a keyframe differs between two animations, values are in degrees, scale or units
+#[derive(PartialEq, Debug, Clone)]
+pub enum KeyChange{
+    Added{frame: u16, value: f32},
+    Removed{frame: u16, value: f32},
+    Changed{frame: u16, from: f32, to: f32, flags: (u8, u8)},
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ChannelDiff{
+    pub bone: u16,
+    pub transform: anim::Transform,
+    pub keys: Vec<KeyChange>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AnimDiff{
+    pub frames: ((u16, u16), (u16, u16)), //start and end frame of each
+    pub added: Vec<(u16, anim::Transform)>, //channels only the second animation has
+    pub removed: Vec<(u16, anim::Transform)>,
+    pub changed: Vec<ChannelDiff>, //only channels with differing keys
+}
+
+impl AnimDiff{
+    pub fn is_identical(&self) -> bool{
+        self.frames.0 == self.frames.1 && self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// the largest value change among keys present in both
+    pub fn max_delta(&self) -> f32{
+        self.changed.iter().flat_map(|c| c.keys.iter()).fold(0.0, |max, k| match k{
+            KeyChange::Changed{from, to, ..} => max.max((to - from).abs()),
+            _ => max,
+        })
+    }
+}
+
+//channels are paired by bone and transform, repeats of the same pair are paired in file order
+fn channel_key(channels: &[anim::AnimChannel], i: usize) -> (u16, u8, usize){
+    let c = &channels[i];
+    let repeat = channels[..i].iter().filter(|o| o.bone == c.bone && o.transform == c.transform).count();
+    return (c.bone, c.transform.to_u8(), repeat)
+}
+
+/// compares two animations keyframe by keyframe, values closer than `tolerance` count as equal
+pub fn anim_diff(a: &anim::AnimFile, b: &anim::AnimFile, tolerance: f32) -> AnimDiff{
+    let keys_a : Vec<(u16, u8, usize)> = (0..a.channels.len()).map(|i| channel_key(&a.channels, i)).collect();
+    let keys_b : Vec<(u16, u8, usize)> = (0..b.channels.len()).map(|i| channel_key(&b.channels, i)).collect();
+    let mut diff = AnimDiff{frames: ((a.start_frame, a.end_frame), (b.start_frame, b.end_frame)), added: Vec::new(), removed: Vec::new(), changed: Vec::new()};
+
+    for (i, key) in keys_a.iter().enumerate(){
+        let ca = &a.channels[i];
+        let cb = match keys_b.iter().position(|k| k == key){
+            Some(j) => &b.channels[j],
+            None => { diff.removed.push((ca.bone, ca.transform)); continue; },
+        };
+        let mut keys = Vec::new();
+        for ka in ca.keyframes.iter(){
+            match cb.keyframes.iter().find(|kb| kb.frame == ka.frame){
+                None => keys.push(KeyChange::Removed{frame: ka.frame, value: ka.value_f32()}),
+                Some(kb) if (kb.value_f32() - ka.value_f32()).abs() > tolerance || kb.flags != ka.flags =>
+                    keys.push(KeyChange::Changed{frame: ka.frame, from: ka.value_f32(), to: kb.value_f32(), flags: (ka.flags, kb.flags)}),
+                Some(_) => (),
+            }
+        }
+        for kb in cb.keyframes.iter().filter(|kb| !ca.keyframes.iter().any(|ka| ka.frame == kb.frame)){
+            keys.push(KeyChange::Added{frame: kb.frame, value: kb.value_f32()});
+        }
+        if !keys.is_empty(){
+            diff.changed.push(ChannelDiff{bone: ca.bone, transform: ca.transform, keys: keys});
+        }
+    }
+    for (j, key) in keys_b.iter().enumerate(){
+        if !keys_a.contains(key){
+            diff.added.push((b.channels[j].bone, b.channels[j].transform));
+        }
+    }
+    return diff;
+}
+
+impl fmt::Display for AnimDiff{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        if self.is_identical(){
+            return writeln!(f, "identical");
+        }
+        if self.frames.0 != self.frames.1{
+            writeln!(f, "frames: {}-{} -> {}-{}", self.frames.0.0, self.frames.0.1, self.frames.1.0, self.frames.1.1)?;
+        }
+        for (bone, transform) in self.removed.iter(){
+            writeln!(f, "bone {} {}: removed", bone, transform)?;
+        }
+        for (bone, transform) in self.added.iter(){
+            writeln!(f, "bone {} {}: added", bone, transform)?;
+        }
+        for c in self.changed.iter(){
+            let keys : Vec<String> = c.keys.iter().map(|k| match k{
+                KeyChange::Added{frame, value} => format!("+{}: {}", frame, value),
+                KeyChange::Removed{frame, value} => format!("-{}: {}", frame, value),
+                KeyChange::Changed{frame, from, to, flags} if flags.0 != flags.1 => format!("{}: {} -> {} (flags {} -> {})", frame, from, to, flags.0, flags.1),
+                KeyChange::Changed{frame, from, to, ..} => format!("{}: {} -> {}", frame, from, to),
+            }).collect();
+            writeln!(f, "bone {} {}: {}", c.bone, c.transform, keys.join(", "))?;
+        }
+        return Ok(())
+    }
+}
+
+pub struct ModelLint{
+    pub stats: model::ModelStats,
+    pub issues: Vec<model::LintIssue>,
+}
+
+impl ModelLint{
+    pub fn passed(&self) -> bool{
+        self.issues.is_empty()
+    }
+}
+
+pub fn model_lint(model: &asset::Model) -> Result<ModelLint, model::ModelError>{
+    return Ok(ModelLint{stats: model.stats()?, issues: model.lint()?})
+}
+
+/// yaml, so CI jobs can read it back
+impl fmt::Display for ModelLint{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let s = &self.stats;
+        writeln!(f, "stats: {{vertices: {}, triangles: {}, textures: {}, texture_bytes: {}, gfx_commands: {}}}",
+            s.vertices, s.triangles, s.textures, s.texture_bytes, s.gfx_commands)?;
+        if self.issues.is_empty(){
+            return writeln!(f, "issues: []")
+        }
+        writeln!(f, "issues:")?;
+        for issue in self.issues.iter(){
+            writeln!(f, "  - {{kind: {}, message: {:?}}}", issue.kind(), issue.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    type Repacked = Vec<(usize, usize, Vec<u8>)>;
+
+    //segment 2 bins are level setups and segment 0 ones animations whatever their bytes, 7 is plain binary
+    fn sizes() -> (Vec<(usize, usize, usize)>, Repacked){
+        let original = vec![(2, 0, 0x10), (2, 1, 0x20), (0, 2, 0x10), (7, 3, 0x08), (7, 4, 0x30)];
+        let repacked = vec![(2, 0, vec![0; 0x18]), (2, 1, vec![0; 0x20]), (0, 2, vec![0; 0x40]), (7, 3, vec![0; 0x08])];
+        return (original, repacked)
+    }
+
+    #[test]
+    fn size_regressions_count_missing_assets_as_empty(){
+        let (original, repacked) = sizes();
+        let report = size_regressions(&original, &repacked);
+        assert_eq!((report.original_total, report.repacked_total), (0x78, 0x80));
+        let changes : Vec<(usize, AssetType, usize, usize)> = report.changes.iter().map(|c| (c.idx, c.asset_type, c.original, c.repacked)).collect();
+        assert_eq!(changes, vec![
+            (0, AssetType::LevelSetup, 0x10, 0x18),
+            (2, AssetType::Animation, 0x10, 0x40),
+            (4, AssetType::Binary, 0x30, 0),
+        ]);
+        let totals : Vec<(AssetType, usize, usize, usize)> = report.by_type.iter().map(|t| (t.asset_type, t.changed, t.original, t.repacked)).collect();
+        assert_eq!(totals, vec![
+            (AssetType::LevelSetup, 1, 0x10, 0x18),
+            (AssetType::Animation, 1, 0x10, 0x40),
+            (AssetType::Binary, 1, 0x30, 0),
+        ]);
+    }
+
+    #[test]
+    fn size_regressions_totals_sum_per_type(){
+        let original = vec![(2, 0, 0x10), (2, 1, 0x20), (2, 2, 0x30)];
+        let repacked = vec![(2, 0, vec![0; 0x14]), (2, 1, vec![0; 0x20]), (2, 2, vec![0; 0x28])];
+        let report = size_regressions(&original, &repacked);
+        assert_eq!(report.by_type.len(), 1);
+        let t = &report.by_type[0];
+        assert_eq!((t.asset_type, t.changed, t.original, t.repacked), (AssetType::LevelSetup, 2, 0x40, 0x3C));
+    }
+
+    #[test]
+    fn top_offenders_are_the_largest_growth_first(){
+        let (original, repacked) = sizes();
+        let report = size_regressions(&original, &repacked);
+        let idx = |n: usize| report.top_offenders(n).iter().map(|c| c.idx).collect::<Vec<usize>>();
+        assert_eq!(idx(2), vec![2, 0]);
+        assert_eq!(idx(10), vec![2, 0, 4]);
+        assert_eq!(report.top_offenders(1)[0].growth(), 0x30);
+    }
+
+    #[test]
+    fn unexpected_growth_respects_the_per_type_limits(){
+        let (original, repacked) = sizes();
+        let report = size_regressions(&original, &repacked);
+        assert!(report.has_unexpected_growth(&[]), "unlisted types may not grow");
+        assert!(!report.has_unexpected_growth(&[(AssetType::Animation, 0x30), (AssetType::LevelSetup, 8)]));
+        assert!(report.has_unexpected_growth(&[(AssetType::Animation, 0x2F), (AssetType::LevelSetup, 8)]));
+        assert!(report.has_unexpected_growth(&[(AssetType::Animation, 0x30)]));
+
+        //shrinking is never growth
+        let shrunk = size_regressions(&[(7, 0, 0x10)], &[(7, 0, vec![0; 4])]);
+        assert!(!shrunk.has_unexpected_growth(&[]));
+    }
+}
@@ -0,0 +1,818 @@
+use std::fmt;
+use regex::RegexBuilder;
+
+use super::asset::{self, AssetType, BKString, Dialog};
+use super::charset;
+use super::magic;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Confidence{
+    Full,       // magic matched and the structural probe consumed the whole buffer
+    PrefixOnly, // magic matched, structure did not
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Detection{
+    pub kind: AssetType,
+    pub confidence: Confidence,
+}
+
+//checks whether the bytes have the structure of one kind
+type Probe = fn(&[u8])->bool;
+
+/// classifies segment 4 assets (Dialog, QuizQuestion, GruntyQuestion, DemoInput)
+pub fn detect_kind(bytes: &[u8]) -> Detection{
+    let candidates : [(AssetType, Probe); 3] = [
+        (AssetType::QuizQuestion,   probe_question),
+        (AssetType::GruntyQuestion, probe_question),
+        (AssetType::Dialog,         probe_dialog),
+    ];
+
+    let matched : Vec<&(AssetType, Probe)> = candidates.iter()
+        .filter(|(kind, _)| magic::starts_with_magic(bytes, *kind))
+        .collect();
+
+    //prefer a candidate whose structure fits, e.g. a dialog with 5 bottom strings looks like a GruntyQuestion
+    if let Some((kind, _)) = matched.iter().find(|(_, probe)| probe(bytes)){
+        return Detection{kind: *kind, confidence: Confidence::Full};
+    }
+    if let Some((kind, _)) = matched.first(){
+        return Detection{kind: *kind, confidence: Confidence::PrefixOnly};
+    }
+
+    let confidence = match probe_demo(bytes){
+        true => Confidence::Full,
+        false => Confidence::PrefixOnly,
+    };
+    Detection{kind: AssetType::DemoInput, confidence: confidence}
+}
+
+//walks `cnt` strings starting at `offset`, returns the offset past the last one
+fn walk_strings(bytes: &[u8], mut offset: usize, cnt: u8) -> Option<usize>{
+    for _ in 0..cnt{
+        let str_size = *bytes.get(offset + 1)? as usize;
+        offset += 2 + str_size;
+    }
+    match offset <= bytes.len(){
+        true => Some(offset),
+        false => None,
+    }
+}
+
+fn probe_dialog(bytes: &[u8]) -> bool{
+    let walk = || -> Option<usize>{
+        let offset = walk_strings(bytes, 4, *bytes.get(3)?)?;
+        walk_strings(bytes, offset + 1, *bytes.get(offset)?)
+    };
+    walk() == Some(bytes.len())
+}
+
+fn probe_question(bytes: &[u8]) -> bool{
+    match bytes.get(5){
+        Some(cnt) if *cnt >= 3 => walk_strings(bytes, 6, *cnt) == Some(bytes.len()),
+        _ => false,
+    }
+}
+
+fn probe_demo(bytes: &[u8]) -> bool{
+    if bytes.len() < 4 { return true; } //empty demo
+    let expect_len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    expect_len.is_multiple_of(6) && expect_len == bytes.len() - 4
+}
+
+/// common access to the strings of Dialog, QuizQuestion and GruntyQuestion
+pub trait TextAsset{
+    fn sections(&self) -> Vec<(&'static str, &[BKString])>;
+    fn sections_mut(&mut self) -> Vec<(&'static str, &mut [BKString])>;
+
+    /// questions store one count byte for the question and the options together
+    fn shares_count(&self) -> bool{
+        false
+    }
+
+    /// checks the string lengths and entry counts fit the u8 fields they are written to
+    fn validate(&self) -> Result<(), TextError>{
+        let sections = self.sections();
+        for (name, entries) in sections.iter(){
+            if let Some((index, text)) = entries.iter().enumerate().find(|(_, t)| t.string.len() > MAX_STRING_LEN){
+                return Err(TextError::StringTooLong{section: name, index: index, len: text.string.len()});
+            }
+        }
+        let counts : Vec<(&'static str, usize)> = match self.shares_count(){
+            true => vec![("question/options", sections.iter().map(|(_, e)| e.len()).sum())],
+            false => sections.iter().map(|(name, e)| (*name, e.len())).collect(),
+        };
+        match counts.into_iter().find(|(_, len)| *len > MAX_ENTRIES){
+            Some((name, len)) => Err(TextError::TooManyEntries{section: name, len: len}),
+            None => Ok(()),
+        }
+    }
+
+    /// strings wider than the textbox and sections with more lines than their box holds
+    fn check_layout(&self, rules: &LayoutRules) -> Vec<LayoutWarning>{
+        let mut warnings = Vec::new();
+        for (name, entries) in self.sections(){
+            for (index, text) in entries.iter().enumerate(){
+                let width = rules.font.measure(&text.string);
+                if width > rules.line_width{
+                    warnings.push(LayoutWarning::Overflow{section: name, index: index, width: width, max: rules.line_width});
+                }
+            }
+            if let Some((_, max)) = rules.max_lines.iter().find(|(s, _)| *s == name){
+                if entries.len() > *max{
+                    warnings.push(LayoutWarning::TooManyLines{section: name, lines: entries.len(), max: *max});
+                }
+            }
+        }
+        return warnings;
+    }
+}
+
+/// the byte in front of every string, values from 0x80 up pick the speaker portrait shown next to it
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DialogCmd{
+    Portrait(u8), //portrait id, cmd - 0x80
+    Raw(u8),
+}
+
+impl From<u8> for DialogCmd{
+    fn from(cmd: u8) -> DialogCmd{
+        match cmd{
+            0x80..=0xFF => DialogCmd::Portrait(cmd - 0x80),
+            _ => DialogCmd::Raw(cmd),
+        }
+    }
+}
+
+impl From<DialogCmd> for u8{
+    fn from(cmd: DialogCmd) -> u8{
+        match cmd{
+            DialogCmd::Portrait(id) => 0x80 + id,
+            DialogCmd::Raw(cmd) => cmd,
+        }
+    }
+}
+
+impl fmt::Display for DialogCmd{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            DialogCmd::Portrait(id) => write!(f, "portrait_{:02X}", id),
+            DialogCmd::Raw(cmd) => write!(f, "0x{:02X}", cmd),
+        }
+    }
+}
+
+impl std::str::FromStr for DialogCmd{
+    type Err = String;
+
+    /// takes the names Display writes as well as plain hex bytes
+    fn from_str(s: &str) -> Result<DialogCmd, String>{
+        let hex = |v: &str| u8::from_str_radix(v, 16).ok();
+        let val = match s.strip_prefix("portrait_"){
+            Some(id) => hex(id).filter(|id| *id < 0x80).map(|id| id + 0x80),
+            None => s.strip_prefix("0x").and_then(hex),
+        };
+        match val{
+            Some(cmd) => Ok(DialogCmd::from(cmd)),
+            None => Err(format!("unrecognized dialog cmd \"{}\", expected portrait_00 to portrait_7F or a hex byte like 0x04", s)),
+        }
+    }
+}
+
+const MAX_STRING_LEN : usize = 0xFF;
+const MAX_ENTRIES : usize = 0xFF;
+
+#[derive(Clone)]
+pub struct PseudoConfig{
+    pub expansion: f32, // target length of the visible text relative to the original
+    pub open: u8,
+    pub close: u8,
+    pub filler: u8,
+    pub table: charset::CharTable, // letters are swapped for the accented glyphs of this table
+}
+
+impl Default for PseudoConfig{
+    fn default() -> PseudoConfig{
+        PseudoConfig{expansion: 1.3, open: b'[', close: b']', filler: b'~', table: charset::DEFAULT}
+    }
+}
+
+/// rewrites every string as `[accented text~~~]`, keeping leading control codes in front
+pub fn pseudolocalize(assets: &mut [&mut dyn TextAsset], config: PseudoConfig){
+    for asset in assets.iter_mut(){
+        for (_, section) in asset.sections_mut(){
+            for text in section.iter_mut(){
+                text.string = pseudolocalize_string(&text.string, &config);
+            }
+        }
+    }
+}
+
+fn pseudolocalize_string(string: &[u8], config: &PseudoConfig) -> Vec<u8>{
+    let (body, terminator) = match string.last(){
+        Some(0) => (&string[..string.len() - 1], &string[string.len() - 1..]),
+        _ => (string, &string[string.len()..]),
+    };
+
+    let prefix_len = control_prefix_len(body);
+    let (prefix, visible) = body.split_at(prefix_len);
+
+    let mut accented : Vec<u8> = Vec::with_capacity(visible.len());
+    let mut i = 0;
+    while i < visible.len(){
+        let b = visible[i];
+        if b == 0xFD && i + 1 < visible.len(){ //inline control code
+            accented.extend_from_slice(&visible[i..i+2]);
+            i += 2;
+            continue;
+        }
+        accented.push(config.table.accented(b).unwrap_or(b));
+        i += 1;
+    }
+
+    let fixed_len = prefix.len() + accented.len() + 2 + terminator.len();
+    if fixed_len > MAX_STRING_LEN{
+        //markers don't fit, fall back to accents only
+        return match prefix.len() + accented.len() + terminator.len() <= MAX_STRING_LEN{
+            true => [prefix, &accented, terminator].concat(),
+            false => string.to_vec(),
+        }
+    }
+    let target = (visible.len() as f32 * config.expansion).ceil() as usize;
+    let fill_cnt = target.saturating_sub(accented.len()).min(MAX_STRING_LEN - fixed_len);
+
+    let mut out = prefix.to_vec();
+    out.push(config.open);
+    out.append(&mut accented);
+    out.resize(out.len() + fill_cnt, config.filler);
+    out.push(config.close);
+    out.extend_from_slice(terminator);
+    return out;
+}
+
+//leading `0xFD xx` pairs and raw control bytes
+fn control_prefix_len(body: &[u8]) -> usize{
+    let mut i = 0;
+    while i < body.len(){
+        if body[i] == 0xFD && i + 1 < body.len() { i += 2; }
+        else if body[i] < 0x20 { i += 1; }
+        else { break; }
+    }
+    return i;
+}
+
+#[derive(Debug, Clone)]
+pub enum TextError{
+    IndexOutOfRange{section: &'static str, index: usize, len: usize},
+    MissingOptions{found: usize},
+    Truncated{offset: usize, len: usize},
+    StringTooLong{section: &'static str, index: usize, len: usize},
+    TooManyEntries{section: &'static str, len: usize},
+    WordTooWide{section: &'static str, word: String},
+}
+
+impl fmt::Display for TextError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            TextError::IndexOutOfRange{section, index, len} =>
+                write!(f, "{} entry {} does not exist, section has {} entries", section, index, len),
+            TextError::MissingOptions{found} =>
+                write!(f, "question has {} strings, expected the question followed by 3 options", found),
+            TextError::Truncated{offset, len} =>
+                write!(f, "string at 0x{:X} runs past the end of the {} byte asset", offset, len),
+            TextError::StringTooLong{section, index, len} =>
+                write!(f, "{} entry {} is {} bytes long, strings are limited to {} bytes", section, index, len, MAX_STRING_LEN),
+            TextError::TooManyEntries{section, len} =>
+                write!(f, "{} has {} entries, at most {} fit", section, len, MAX_ENTRIES),
+            TextError::WordTooWide{section, word} =>
+                write!(f, "{}: \"{}\" is wider than the textbox on its own", section, word),
+        }
+    }
+}
+
+/// pixel widths of the dialog font glyphs, indexed by byte
+#[derive(Clone)]
+pub struct FontWidths{
+    pub widths: [u8; 256],
+}
+
+impl FontWidths{
+    /// PLACEHOLDER, these widths are rough guesses and not taken from the game's font.
+    /// layout checks made with them are estimates, pass real widths when you have them
+    pub fn placeholder() -> FontWidths{
+        let mut widths = [8u8; 256];
+        widths[b' ' as usize] = 5;
+        for c in b"il!.,':;|".iter(){ widths[*c as usize] = 4; }
+        for c in b"fjrt()[]1".iter(){ widths[*c as usize] = 6; }
+        for c in b"MWmw".iter(){ widths[*c as usize] = 10; }
+        return FontWidths{widths: widths};
+    }
+
+    /// width of the visible text, control bytes, `0xFD xx` codes and the terminator take no space
+    pub fn measure(&self, string: &[u8]) -> u32{
+        let mut width = 0;
+        let mut i = 0;
+        while i < string.len(){
+            let b = string[i];
+            if b == 0xFD { i += 2; continue; }
+            if b >= 0x20 { width += self.widths[b as usize] as u32; }
+            i += 1;
+        }
+        return width;
+    }
+}
+
+/// textbox size used by `TextAsset::check_layout`. questions show all their lines at once,
+/// dialogs page through theirs, so their limits cap how many pages a bottom or top box takes
+#[derive(Clone)]
+pub struct LayoutRules{
+    pub font: FontWidths,
+    pub line_width: u32,
+    pub max_lines: Vec<(&'static str, usize)>,
+}
+
+impl LayoutRules{
+    /// the game's textbox sizes, measured with `font`
+    pub fn new(font: FontWidths) -> LayoutRules{
+        LayoutRules{font: font, line_width: 240, max_lines: vec![("question", 4), ("options", 3), ("bottom", 16), ("top", 8)]}
+    }
+}
+
+#[derive(Clone)]
+pub struct PlainTextOptions{
+    pub cmd: u8, //given to every generated string
+    pub rules: LayoutRules,
+    pub charset: charset::CharTable,
+}
+
+impl PlainTextOptions{
+    pub fn new(font: FontWidths) -> PlainTextOptions{
+        PlainTextOptions{cmd: 0x80, rules: LayoutRules::new(font), charset: charset::DEFAULT}
+    }
+}
+
+/// word wraps every line of `text` to the textbox width, each wrapped line becomes one string
+pub fn wrap_plain_text(text: &str, section: &'static str, options: &PlainTextOptions) -> Result<Vec<BKString>, TextError>{
+    let mut out : Vec<BKString> = Vec::new();
+    let fits = |line: &str| options.rules.font.measure(&asset::string_to_vecu8(line, &options.charset)) <= options.rules.line_width;
+    for paragraph in text.lines(){
+        let mut line = String::new();
+        for word in paragraph.split_whitespace(){
+            if !fits(word){
+                return Err(TextError::WordTooWide{section: section, word: word.to_string()});
+            }
+            let candidate = match line.is_empty(){
+                true => word.to_string(),
+                false => format!("{} {}", line, word),
+            };
+            match fits(&candidate){
+                true => line = candidate,
+                false => {out.push(BKString{cmd: options.cmd, string: asset::string_to_vecu8(&line, &options.charset)}); line = word.to_string();},
+            }
+        }
+        if !line.is_empty(){
+            out.push(BKString{cmd: options.cmd, string: asset::string_to_vecu8(&line, &options.charset)});
+        }
+    }
+    if let Some((index, text)) = out.iter().enumerate().find(|(_, t)| t.string.len() > MAX_STRING_LEN){
+        return Err(TextError::StringTooLong{section: section, index: index, len: text.string.len()});
+    }
+    if out.len() > MAX_ENTRIES{
+        return Err(TextError::TooManyEntries{section: section, len: out.len()});
+    }
+    return Ok(out);
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum LayoutWarning{
+    Overflow{section: &'static str, index: usize, width: u32, max: u32},
+    TooManyLines{section: &'static str, lines: usize, max: usize},
+}
+
+impl fmt::Display for LayoutWarning{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            LayoutWarning::Overflow{section, index, width, max} =>
+                write!(f, "{} entry {} is {}px wide, the textbox fits {}px", section, index, width, max),
+            LayoutWarning::TooManyLines{section, lines, max} =>
+                write!(f, "{} has {} lines, the textbox fits {}", section, lines, max),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SectionStats{
+    pub name: &'static str,
+    pub entry_sizes: Vec<usize>, //serialized bytes per string, cmd and length byte included
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextStats{
+    pub total_size: usize,
+    pub sections: Vec<SectionStats>,
+    pub longest: Option<(&'static str, usize, usize)>, //section, index and length of the longest string
+}
+
+impl TextStats{
+    pub fn entry_count(&self) -> usize{
+        self.sections.iter().map(|s| s.entry_sizes.len()).sum()
+    }
+}
+
+/// per string sizes of an asset, `total_size` is its serialized size
+pub fn text_stats(asset: &dyn TextAsset, total_size: usize) -> TextStats{
+    let mut longest : Option<(&'static str, usize, usize)> = None;
+    let mut sections = Vec::new();
+    for (name, entries) in asset.sections(){
+        for (i, text) in entries.iter().enumerate(){
+            if longest.is_none_or(|(_, _, len)| text.string.len() > len){
+                longest = Some((name, i, text.string.len()));
+            }
+        }
+        sections.push(SectionStats{name: name, entry_sizes: entries.iter().map(|t| 2 + t.string.len()).collect()});
+    }
+    return TextStats{total_size: total_size, sections: sections, longest: longest};
+}
+
+#[derive(Default, Clone)]
+pub struct SearchOptions{
+    pub case_insensitive: bool,
+    pub regex: bool, //query is a regular expression instead of plain text
+    pub charset: charset::CharTable,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SearchMatch{
+    pub asset: usize,
+    pub section: &'static str,
+    pub index: usize,
+    pub preview: String,
+}
+
+/// finds strings whose visible text matches `query`, control codes are left out before matching
+/// so a line is found no matter what codes sit inside it
+pub fn search<'a, I>(assets: I, query: &str, options: &SearchOptions) -> Result<Vec<SearchMatch>, regex::Error>
+    where I: IntoIterator<Item = (usize, &'a dyn TextAsset)>
+{
+    let pattern = match options.regex{
+        true => query.to_string(),
+        false => regex::escape(query),
+    };
+    let re = RegexBuilder::new(&pattern).case_insensitive(options.case_insensitive).build()?;
+    let mut matches = Vec::new();
+    for (uid, asset) in assets{
+        for (name, entries) in asset.sections(){
+            for (i, text) in entries.iter().enumerate(){
+                let preview = visible_text(&text.string, &options.charset);
+                if re.is_match(&preview){
+                    matches.push(SearchMatch{asset: uid, section: name, index: i, preview: preview});
+                }
+            }
+        }
+    }
+    return Ok(matches);
+}
+
+/// the text a string shows in game, without control bytes, `0xFD xx` codes or the terminator
+pub fn visible_text(string: &[u8], table: &charset::CharTable) -> String{
+    let mut out = String::new();
+    let mut i = 0;
+    while i < string.len(){
+        let b = string[i];
+        if b == 0xFD { i += 2; continue; }
+        if b >= 0x20{
+            match table.glyph(b){
+                Some(glyph) => out += glyph,
+                None => out.push(b as char),
+            }
+        }
+        i += 1;
+    }
+    return out;
+}
+
+const CSV_HEADER : &str = "asset,section,index,cmd,text";
+
+/// one row per string: uid, section, entry index, cmd and the escaped text as in the yamls
+pub fn export_csv(assets: &[(usize, &dyn TextAsset)], table: &charset::CharTable) -> String{
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    for (uid, asset) in assets.iter(){
+        for (name, entries) in asset.sections(){
+            for (i, text) in entries.iter().enumerate(){
+                let fields = [format!("0x{:04X}", uid), name.to_string(), i.to_string(), DialogCmd::from(text.cmd).to_string(), asset::vecu8_to_string(&text.string, table)];
+                let row : Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                out += &row.join(",");
+                out.push('\n');
+            }
+        }
+    }
+    return out;
+}
+
+/// applies the cmd and text of every row onto the matching string, returns the number of rows applied.
+/// rows can only edit existing strings, an unknown uid, section or index fails with the line number
+pub fn import_csv(csv: &str, assets: &mut [(usize, &mut dyn TextAsset)], table: &charset::CharTable) -> Result<usize, CsvError>{
+    let rows = parse_csv(csv)?;
+    let mut applied = 0;
+    for (line, row) in rows.iter(){
+        if row.join(",") == CSV_HEADER { continue; }
+        let err = |reason: String| CsvError{line: *line, reason: reason};
+        if row.len() != 5{
+            return Err(err(format!("expected 5 fields ({}), found {}", CSV_HEADER, row.len())));
+        }
+        let uid = match row[0].strip_prefix("0x"){
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => row[0].parse::<usize>().ok(),
+        }.ok_or_else(|| err(format!("asset \"{}\" is not a number", row[0])))?;
+        let index = row[2].parse::<usize>().map_err(|_| err(format!("index \"{}\" is not a number", row[2])))?;
+        let cmd : DialogCmd = row[3].parse().map_err(err)?;
+        let mut string = asset::string_to_vecu8(&row[4], table);
+
+        let (_, asset) = assets.iter_mut().find(|(u, _)| *u == uid).ok_or_else(|| err(format!("no text asset 0x{:04X}", uid)))?;
+        let mut sections = asset.sections_mut();
+        let (name, entries) = sections.iter_mut().find(|(name, _)| *name == row[1])
+            .ok_or_else(|| err(format!("asset 0x{:04X} has no section \"{}\"", uid, row[1])))?;
+        if index >= entries.len(){
+            return Err(err(TextError::IndexOutOfRange{section: name, index: index, len: entries.len()}.to_string()));
+        }
+        if string.len() > MAX_STRING_LEN{
+            return Err(err(TextError::StringTooLong{section: name, index: index, len: string.len()}.to_string()));
+        }
+        entries[index].cmd = cmd.into();
+        if !entries[index].is_terminated(){ string.pop(); }
+        entries[index].string = string;
+        applied += 1;
+    }
+    return Ok(applied);
+}
+
+fn csv_field(field: &str) -> String{
+    match field.contains([',', '"', '\n', '\r']){
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+//rows with the line they start on, quoted fields may contain commas, doubled quotes and newlines
+fn parse_csv(csv: &str) -> Result<Vec<(usize, Vec<String>)>, CsvError>{
+    let mut rows = Vec::new();
+    let mut row : Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = csv.chars().peekable();
+    while let Some(ch) = chars.next(){
+        if quoted{
+            match ch{
+                '"' if chars.peek() == Some(&'"') => {chars.next(); field.push('"');},
+                '"' => quoted = false,
+                _ => {if ch == '\n' {line += 1;} field.push(ch);},
+            }
+            continue;
+        }
+        match ch{
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {},
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if !(row.len() == 1 && row[0].is_empty()){ rows.push((row_line, std::mem::take(&mut row))); }
+                row.clear();
+                line += 1;
+                row_line = line;
+            },
+            _ => field.push(ch),
+        }
+    }
+    if quoted{
+        return Err(CsvError{line: row_line, reason: String::from("quoted field is never closed")});
+    }
+    row.push(field);
+    if !(row.len() == 1 && row[0].is_empty()){ rows.push((row_line, row)); }
+    return Ok(rows);
+}
+
+#[derive(Debug)]
+pub struct CsvError{
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for CsvError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DialogSection{
+    Bottom,
+    Top,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct DeltaInfo{
+    pub total_size: usize,
+    pub section_sizes: [usize; 2], //bottom, top; including the count byte
+}
+
+/// tracks the serialized size of a Dialog while single strings are edited
+pub struct DialogBuffer{
+    dialog: Dialog,
+    entry_sizes: [Vec<usize>; 2],
+    section_sizes: [usize; 2],
+}
+
+impl DialogBuffer{
+    pub fn new(dialog: &Dialog) -> DialogBuffer{
+        let dialog = dialog.clone();
+        let sections = dialog.sections();
+        let entry_sizes : [Vec<usize>; 2] = [
+            sections[0].1.iter().map(|t| 2 + t.string.len()).collect(),
+            sections[1].1.iter().map(|t| 2 + t.string.len()).collect(),
+        ];
+        let section_sizes = [
+            1 + entry_sizes[0].iter().sum::<usize>(),
+            1 + entry_sizes[1].iter().sum::<usize>(),
+        ];
+        DialogBuffer{dialog: dialog, entry_sizes: entry_sizes, section_sizes: section_sizes}
+    }
+
+    pub fn update_string(&mut self, section: DialogSection, index: usize, new_text: &str, table: &charset::CharTable) -> Result<DeltaInfo, TextError>{
+        let s_indx = section as usize;
+        let mut sections = self.dialog.sections_mut();
+        let (name, entries) = &mut sections[s_indx];
+        if index >= entries.len(){
+            return Err(TextError::IndexOutOfRange{section: name, index: index, len: entries.len()});
+        }
+        let encoded = asset::string_to_vecu8(new_text, table);
+        if encoded.len() > MAX_STRING_LEN{
+            return Err(TextError::StringTooLong{section: name, index: index, len: encoded.len()});
+        }
+
+        let new_size = 2 + encoded.len();
+        entries[index].string = encoded;
+        self.section_sizes[s_indx] = self.section_sizes[s_indx] + new_size - self.entry_sizes[s_indx][index];
+        self.entry_sizes[s_indx][index] = new_size;
+        Ok(self.delta())
+    }
+
+    /// byte offset of a section's count byte in the serialized dialog
+    pub fn section_offset(&self, section: DialogSection) -> usize{
+        match section{
+            DialogSection::Bottom => magic::DIALOG.len(),
+            DialogSection::Top => magic::DIALOG.len() + self.section_sizes[0],
+        }
+    }
+
+    pub fn delta(&self) -> DeltaInfo{
+        DeltaInfo{total_size: magic::DIALOG.len() + self.section_sizes.iter().sum::<usize>(), section_sizes: self.section_sizes}
+    }
+
+    pub fn commit(&self) -> Dialog{
+        self.dialog.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::asset::Asset;
+
+    //header, then a count byte ahead of each group of (cmd, string) pairs
+    fn text_bytes(header: &[u8], groups: &[&[&[u8]]]) -> Vec<u8>{
+        let mut out = header.to_vec();
+        for group in groups.iter(){
+            out.push(group.len() as u8);
+            for string in group.iter(){
+                out.extend_from_slice(&[0x00, string.len() as u8]);
+                out.extend_from_slice(string);
+            }
+        }
+        return out
+    }
+
+    #[test]
+    fn detect_kind_tells_dialogs_from_grunty_questions(){
+        //5 bottom strings and a first cmd of 0 give a dialog the GruntyQuestion magic
+        let dialog = text_bytes(&magic::DIALOG, &[&[b"a\0", b"b\0", b"c\0", b"d\0", b"e\0"], &[]]);
+        assert!(dialog.starts_with(&magic::GRUNTY_QUESTION));
+        assert_eq!(detect_kind(&dialog), Detection{kind: AssetType::Dialog, confidence: Confidence::Full});
+
+        let grunty = text_bytes(&magic::GRUNTY_QUESTION, &[&[b"why?\0", b"a\0", b"b\0", b"c\0"]]);
+        assert_eq!(detect_kind(&grunty), Detection{kind: AssetType::GruntyQuestion, confidence: Confidence::Full});
+        let quiz = text_bytes(&magic::QUIZ_QUESTION, &[&[b"who?\0", b"a\0", b"b\0", b"c\0"]]);
+        assert_eq!(detect_kind(&quiz), Detection{kind: AssetType::QuizQuestion, confidence: Confidence::Full});
+        let talk = text_bytes(&magic::DIALOG, &[&[b"hi\0"], &[b"bye\0"]]);
+        assert_eq!(detect_kind(&talk), Detection{kind: AssetType::Dialog, confidence: Confidence::Full});
+
+        //a cut off asset keeps the kind its magic points at
+        assert_eq!(detect_kind(&dialog[..dialog.len() - 1]), Detection{kind: AssetType::GruntyQuestion, confidence: Confidence::PrefixOnly});
+        assert_eq!(detect_kind(&talk[..talk.len() - 1]), Detection{kind: AssetType::Dialog, confidence: Confidence::PrefixOnly});
+
+        let demo = [0, 0, 0, 6, 0x80, 0x00, 10, 20, 0, 1];
+        assert_eq!(detect_kind(&demo), Detection{kind: AssetType::DemoInput, confidence: Confidence::Full});
+        assert_eq!(detect_kind(&demo[..9]), Detection{kind: AssetType::DemoInput, confidence: Confidence::PrefixOnly});
+    }
+
+    fn dialog(bottom: &[&str], top: &[&str]) -> Dialog{
+        let rules = LayoutRules{line_width: u32::MAX, ..LayoutRules::new(FontWidths::placeholder())};
+        let options = PlainTextOptions{rules: rules, ..PlainTextOptions::new(FontWidths::placeholder())};
+        return Dialog::from_plain_text(&bottom.join("\n"), &top.join("\n"), &options).unwrap()
+    }
+
+    #[test]
+    fn layout_accepts_text_that_fits(){
+        let rules = LayoutRules::new(FontWidths::placeholder());
+        let fits = dialog(&["Hello there, Banjo!"; 16], &["Grunty's rhyme"; 8]);
+        assert!(fits.check_layout(&rules).is_empty());
+        assert_eq!(rules.font.measure(b"Hi!\0"), 8 + 4 + 4);
+    }
+
+    #[test]
+    fn layout_flags_wide_strings_and_long_sections(){
+        let rules = LayoutRules::new(FontWidths::placeholder());
+        let wide = "W".repeat(25);
+        let overflowing = dialog(&["fine", &wide], &["fine"; 9]);
+        assert_eq!(overflowing.check_layout(&rules), vec![
+            LayoutWarning::Overflow{section: "bottom", index: 1, width: 250, max: 240},
+            LayoutWarning::TooManyLines{section: "top", lines: 9, max: 8},
+        ]);
+        let long = dialog(&["fine"; 17], &[]);
+        assert_eq!(long.check_layout(&rules), vec![LayoutWarning::TooManyLines{section: "bottom", lines: 17, max: 16}]);
+    }
+
+    #[test]
+    fn pseudolocalize_takes_accents_from_the_table(){
+        let config = PseudoConfig::default();
+        let out = pseudolocalize_string(b"Banjo\0", &config);
+        assert_eq!(out, [b'[', b'B', 0xE0, 0xF1, b'j', 0xF6, b'~', b'~', b']', 0]);
+        assert_eq!(pseudolocalize_string(b"Banjo\0", &config), out, "same input, same output");
+
+        let plain = PseudoConfig{table: charset::CharTable::empty(), ..PseudoConfig::default()};
+        assert_eq!(pseudolocalize_string(b"Banjo\0", &plain), b"[Banjo~~]\0");
+        let mut table = charset::CharTable::empty();
+        table.set(0x90, "á");
+        let custom = PseudoConfig{table: table, ..PseudoConfig::default()};
+        assert_eq!(pseudolocalize_string(b"Banjo\0", &custom), [b'[', b'B', 0x90, b'n', b'j', b'o', b'~', b'~', b']', 0]);
+    }
+
+    #[test]
+    fn pseudolocalize_passes_control_codes_through(){
+        let config = PseudoConfig::default();
+        //leading codes stay in front of the marker, inline 0xFD pairs keep their argument
+        let out = pseudolocalize_string(&[0x01, 0xFD, 0x6A, b'h', b'i', 0xFD, b'a', 0], &config);
+        assert_eq!(out, [0x01, 0xFD, 0x6A, b'[', b'h', 0xEE, 0xFD, b'a', b'~', b'~', b']', 0]);
+        assert_eq!(pseudolocalize_string(&[0x01, 0], &config), [0x01, b'[', b']', 0]);
+    }
+
+    #[test]
+    fn pseudolocalize_stays_within_the_string_limit(){
+        let config = PseudoConfig::default();
+        for len in [200, 240, 252, 253, 254]{
+            let mut string = vec![b'x'; len];
+            string.push(0);
+            let out = pseudolocalize_string(&string, &config);
+            assert!(out.len() <= MAX_STRING_LEN, "{} byte string grew to {}", len, out.len());
+            assert_eq!(out.last(), Some(&0));
+            assert!(out.len() > len, "{} byte string lost text", len);
+        }
+        //markers that don't fit are dropped, the accents stay
+        let mut string = vec![b'a'; 253];
+        string.push(0);
+        let out = pseudolocalize_string(&string, &config);
+        assert_eq!(out.len(), 254);
+        assert!(out[..253].iter().all(|b| *b == 0xE0));
+    }
+
+    #[test]
+    fn dialog_buffer_matches_to_bytes_after_random_edits(){
+        //xorshift, the same edits on every run
+        let mut seed : u32 = 0x2545F491;
+        let mut next = |max: usize|{ seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as usize % max };
+        let pieces = ["a", "W", " ", "é", "\\x01", "\\\\", "''", "\\xFD\\x6A", "Banjo"];
+
+        let mut buffer = DialogBuffer::new(&dialog(&["one", "two", "three"], &["top"]));
+        for _ in 0..500{
+            let (section, len) = match next(2){
+                0 => (DialogSection::Bottom, 3),
+                _ => (DialogSection::Top, 1),
+            };
+            let index = next(len + 1); //one past the end now and then
+            let text : String = (0..next(80)).map(|_| pieces[next(pieces.len())]).collect();
+            let before = buffer.delta();
+            match buffer.update_string(section, index, &text, &charset::DEFAULT){
+                Ok(delta) => assert_eq!(delta, buffer.delta()),
+                Err(TextError::IndexOutOfRange{..}) | Err(TextError::StringTooLong{..}) => assert_eq!(buffer.delta(), before),
+                Err(e) => panic!("{}", e),
+            }
+            let bytes = buffer.commit().to_bytes();
+            let delta = buffer.delta();
+            assert_eq!(delta.total_size, bytes.len());
+            assert_eq!(buffer.section_offset(DialogSection::Top), magic::DIALOG.len() + delta.section_sizes[0]);
+            assert_eq!(Dialog::parse(&bytes).unwrap().to_bytes(), bytes);
+        }
+    }
+}
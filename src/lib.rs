@@ -0,0 +1,4 @@
+//explicit returns and `field: field` initializers are this crate's style
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+pub mod banjo_kazooie;
@@ -1,4 +1,7 @@
-mod banjo_kazooie;
+//explicit returns and `field: field` initializers are this crate's style
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use bk_asset_tool::banjo_kazooie;
 
 use std::env;
 use std::fs::{self, DirBuilder};
@@ -8,6 +11,11 @@ use std::path::Path;
 enum Direction {
     Extract,
     Construct,
+    DiffSprite,
+    RoundTripText,
+    ModelObj,
+    ModelGlb,
+    LintModel,
 }
 
 fn main() {
@@ -16,10 +24,36 @@ fn main() {
     let direction = match arg1.as_str() {
         "--extract" | "-e" => Direction::Extract,
         "--construct" | "-c" => Direction::Construct,
-        _=> panic!("invalid direction \"{}\" provided\n try: --extract, -e, --construct, or -c", arg1),
+        "--diff-sprite" | "-d" => Direction::DiffSprite,
+        "--round-trip-text" | "-r" => Direction::RoundTripText,
+        "--model-obj" | "-o" => Direction::ModelObj,
+        "--model-glb" | "-g" => Direction::ModelGlb,
+        "--lint-model" | "-l" => Direction::LintModel,
+        _=> panic!("invalid direction \"{}\" provided\n try: --extract, -e, --construct, -c, --diff-sprite, -d, --round-trip-text, -r, --model-obj, -o, --model-glb, -g, --lint-model or -l", arg1),
     };
     let in_path = env::args().nth(2).expect("No in path provided");
     let out_path = env::args().nth(3).expect("No out path provided");
+
+    //optional character table for non english fonts, json text output, .mid copies of the music, model textures as pngs and toml sprite descriptors
+    let mut text_format = banjo_kazooie::asset::TextFormat::Yaml;
+    let mut standard_midi = false;
+    let mut model_textures = false;
+    let mut charset = banjo_kazooie::charset::DEFAULT;
+    let mut sprite_descriptor = banjo_kazooie::asset::DescriptorFormat::Yaml;
+    let mut options = env::args().skip(4);
+    while let Some(flag) = options.next(){
+        match flag.as_str(){
+            "--charset" => {
+                let table_path = options.next().expect("No character table path provided");
+                charset = banjo_kazooie::charset::CharTable::load(Path::new(&table_path)).unwrap_or_else(|e| panic!("{}", e));
+            },
+            "--json" => text_format = banjo_kazooie::asset::TextFormat::Json,
+            "--midi" => standard_midi = true,
+            "--textures" => model_textures = true,
+            "--toml" => sprite_descriptor = banjo_kazooie::asset::DescriptorFormat::Toml,
+            _ => panic!("unknown option \"{}\", try: --charset <path/to/table.yaml>, --json, --midi, --textures or --toml", flag),
+        }
+    }
     
     match direction {
         Direction::Extract => {
@@ -28,7 +62,12 @@ fn main() {
             let in_bytes : Vec<u8> = fs::read(in_path).expect("Could not read file");
             
             // parse binary
-            let af = banjo_kazooie::AssetFolder::from_bytes(&in_bytes);
+            let mut af = banjo_kazooie::AssetFolder::from_bytes(&in_bytes);
+            af.text_format = text_format;
+            af.standard_midi = standard_midi;
+            af.model_textures = model_textures;
+            af.charset = charset;
+            af.sprite_descriptor = sprite_descriptor;
 
             //create output
             DirBuilder::new().recursive(true).create(&out_path).unwrap();
@@ -39,6 +78,7 @@ fn main() {
         Direction::Construct => {
             assert!(fs::metadata(&in_path).unwrap().is_file());
             let mut af = banjo_kazooie::AssetFolder::new();
+            af.charset = charset;
             af.read(Path::new(&in_path));
 
             let mut decomp_buffer = af.to_bytes();
@@ -47,5 +87,46 @@ fn main() {
             out_bin.write_all(&decomp_buffer).unwrap();
 
         }
+        Direction::DiffSprite => {
+            //in and out path are the original and repacked sprite binaries
+            let original = fs::read(&in_path).expect("Could not read file");
+            let repacked = fs::read(&out_path).expect("Could not read file");
+            let diff = banjo_kazooie::report::sprite_diff(&original, &repacked);
+            print!("{}", diff);
+            if !diff.is_identical(){
+                std::process::exit(1);
+            }
+        }
+        Direction::RoundTripText => {
+            //in path is a folder of asset bins, out path a scratch folder for the yamls
+            let report = banjo_kazooie::report::round_trip_dir(Path::new(&in_path), Path::new(&out_path), banjo_kazooie::report::text_round_trip);
+            print!("{}", report);
+            if !report.passed(){
+                std::process::exit(1);
+            }
+        }
+        Direction::ModelObj => {
+            //in path is an extracted .model.bin, out path the folder for the obj, mtl and pngs
+            let model = banjo_kazooie::asset::Model::parse(&fs::read(&in_path).expect("Could not read file")).unwrap_or_else(|e| panic!("{}", e));
+            let name = Path::new(&in_path).file_name().unwrap().to_str().unwrap().split('.').next().unwrap().to_string();
+            model.to_obj(Path::new(&out_path), &name).unwrap_or_else(|e| panic!("{}", e));
+            model.collision_to_obj(Path::new(&out_path), &name).unwrap_or_else(|e| panic!("{}", e));
+        }
+        Direction::ModelGlb => {
+            //in path is an extracted .model.bin, out path the .glb
+            let model = banjo_kazooie::asset::Model::parse(&fs::read(&in_path).expect("Could not read file")).unwrap_or_else(|e| panic!("{}", e));
+            let glb = model.to_gltf().unwrap_or_else(|e| panic!("{}", e));
+            fs::write(&out_path, glb).expect("Could not write glb");
+        }
+        Direction::LintModel => {
+            //in path is an extracted .model.bin, out path the yaml report
+            let model = banjo_kazooie::asset::Model::parse(&fs::read(&in_path).expect("Could not read file")).unwrap_or_else(|e| panic!("{}", e));
+            let lint = banjo_kazooie::report::model_lint(&model).unwrap_or_else(|e| panic!("{}", e));
+            print!("{}", lint);
+            fs::write(&out_path, lint.to_string()).expect("Could not write report");
+            if !lint.passed(){
+                std::process::exit(1);
+            }
+        }
     }
 }
@@ -0,0 +1,22 @@
+//the game's assets aren't in the repo, point BK_ASSET_DIR at a folder of extracted asset bins to run this
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use bk_asset_tool::banjo_kazooie::report;
+
+#[test]
+fn level_setups_rebuild_byte_identical(){
+    let dir = match env::var_os("BK_ASSET_DIR"){
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            eprintln!("BK_ASSET_DIR not set, skipping the level setup round trip");
+            return;
+        },
+    };
+    let scratch = env::temp_dir().join(format!("bk_asset_tool_{}_lvl_setup_corpus", std::process::id()));
+    let report = report::round_trip_dir(&dir, &scratch, report::level_setup_round_trip);
+    fs::remove_dir_all(&scratch).unwrap();
+    assert!(report.checked > 0, "no .lvl_setup.bin files below {}", dir.display());
+    assert!(report.passed(), "\n{}", report);
+}